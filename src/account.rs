@@ -0,0 +1,112 @@
+//! #account
+//!
+//! This holds the `Account` type, a named set of holdings and cash belonging to a `User`, such as a brokerage or
+//! retirement account
+
+// std
+use std::collections::{BTreeMap, HashMap};
+
+// external crates
+use serde::{Serialize, Deserialize}; // So accounts may be written with their user
+
+// internal crates
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::stock::{Stock, StockUnit};
+use crate::validate;
+
+/// The account every user has, which holds the portfolio of users from before accounts existed
+pub const DEFAULT_ACCOUNT: &str = "main";
+
+/// The `Account` struct represents holdings and cash kept together, e.g. in one brokerage account
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Account {
+    /// Cash in the base currency, as the account was opened with
+    #[serde(default)]
+    pub cash: f64,
+    /// The account's holdings by ticker
+    #[serde(default)]
+    pub holdings: HashMap<String, StockUnit>,
+}
+
+impl Account {
+    /// Opens an account holding `cash`, which must be a finite amount no less than zero for the account to be stored
+    pub fn new(cash: f64) -> Result<Account, ProjectError> {
+        validate::cash(cash)?;
+        Ok(Account { cash, holdings: HashMap::new() })
+    }
+
+    /// The `add_stock()` method adds `qt` shares of `stock` bought at its current value.
+    pub fn add_stock(&mut self, stock: &Stock, qt: u32) -> Result<(), ProjectError> {
+        match self.holdings.get_mut(&stock.ticker) {
+            Some(stock_unit) => stock_unit.add_stock(qt, stock.value)?,
+            None if qt > 0 => { self.holdings.insert(stock.ticker.clone(), StockUnit::new(stock.clone(), qt)?); },
+            None => return Err(InvalidInputError),
+        }
+        Ok(())
+    }
+
+    /// Returns the value of the holdings at the current prices in `stock_map`, or the value recorded with a holding if
+    /// its stock no longer exists
+    pub fn market_value(&self, stock_map: &HashMap<String, Stock>) -> f64 {
+        self.holdings.values()
+            .map(|x| stock_map.get(&x.stock.ticker).unwrap_or(&x.stock).value * f64::from(x.quantity))
+            .sum()
+    }
+}
+
+/// The `consolidate()` function combines the holdings of `accounts` by ticker, adding up their quantities and costs.
+pub fn consolidate<'a, I>(accounts: I) -> BTreeMap<String, StockUnit> where
+    I: IntoIterator<Item = &'a Account>, {
+    let mut combined: BTreeMap<String, StockUnit> = BTreeMap::new();
+    for stock_unit in accounts.into_iter().flat_map(|x| x.holdings.values()) {
+        match combined.get_mut(&stock_unit.stock.ticker) {
+            Some(x) => {
+                x.cost = Some(x.cost_basis() + stock_unit.cost_basis());
+                x.quantity += stock_unit.quantity;
+            },
+            None => { combined.insert(stock_unit.stock.ticker.clone(), stock_unit.clone()); },
+        }
+    }
+    combined
+}
+
+/// Account names are typed on the command line, so only letters, digits, `-` and `_` are allowed
+pub fn validate_name(name: &str) -> Result<(), ProjectError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(AccountNameError(String::from(name)))
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn purchases_are_consolidated() {
+        let foo = Stock::new_from_details("FOO", "Foo Inc", 10.0).unwrap();
+        let mut brokerage = Account::new(100.0).unwrap();
+        brokerage.add_stock(&foo, 3).unwrap();
+        brokerage.add_stock(&foo, 2).unwrap();
+        assert_eq!(brokerage.cash, 100.0);
+        assert!(matches!(brokerage.add_stock(&Stock::new_from_ticker("BAR").unwrap(), 0), Err(InvalidInputError)));
+
+        let mut ira = Account::default();
+        ira.add_stock(&Stock { value: 20.0, ..foo.clone() }, 1).unwrap();
+
+        let combined = consolidate([&brokerage, &ira]);
+        assert_eq!((combined["FOO"].quantity, combined["FOO"].cost_basis()), (6, 70.0));
+        assert_eq!(brokerage.market_value(&HashMap::from([(String::from("FOO"), Stock { value: 12.0, ..foo })])), 60.0);
+    }
+
+    #[test]
+    fn accounts_may_not_open_with_unstorable_cash() {
+        for cash in [f64::NAN, f64::INFINITY, -5.0] {
+            assert!(matches!(Account::new(cash), Err(InvalidNumberError(validate::Field::Cash, _))));
+        }
+        let account = Account::new(250.0).unwrap();
+        let json = serde_json::to_string(&account).unwrap();
+        assert_eq!(serde_json::from_str::<Account>(&json).unwrap(), account);
+    }
+}
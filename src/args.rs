@@ -0,0 +1,221 @@
+//! #args
+//!
+//! This holds the `FlagValue` type and the `parse()` function, which turns command-line arguments into a `Command`,
+//! its positional arguments and its flags as declared by the `CommandSpec` table
+
+// std
+use std::collections::BTreeMap;
+use std::fmt;
+
+// internal crates
+use crate::command::*;
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+
+/// The `FlagValue` enum represents the value given to a flag, typed according to its `FlagKind`
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlagValue {
+    Switch,
+    Text(String),
+    Integer(i64),
+    Decimal(f64),
+}
+
+impl FlagValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FlagValue::Text(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            FlagValue::Integer(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn as_decimal(&self) -> Option<f64> {
+        match self {
+            FlagValue::Decimal(x) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FlagValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlagValue::Switch => write!(f, "true"),
+            FlagValue::Text(x) => write!(f, "{}", x),
+            FlagValue::Integer(x) => write!(f, "{}", x),
+            FlagValue::Decimal(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+/// The `ParsedArgs` struct holds the result of `parse()`
+#[derive(Debug)]
+pub struct ParsedArgs {
+    pub command: Command,
+    /// The positional arguments following the command
+    pub remainder: Vec<String>,
+    /// The flags given, keyed by name without the leading `--`
+    pub flags: BTreeMap<String, FlagValue>,
+    /// The global flags as they were given, other than `--help`
+    pub global_args: Vec<String>,
+}
+
+/// The `parse()` function parses `args` (not including the program name) against the `CommandSpec` table.
+///
+/// Flags are written `--name VALUE`, `--name=VALUE` or `--name` for switches, and `-h` is short for `--help`. Global
+/// flags may appear anywhere, while a command's own flags must follow it. Everything after `--` is positional. Given
+/// `--help`, the command is replaced by `help` for that command.
+pub fn parse(args: Vec<String>) -> Result<ParsedArgs, ProjectError> {
+    let mut spec: Option<&'static CommandSpec> = None;
+    let mut remainder = Vec::new();
+    let mut flags = BTreeMap::new();
+    let mut global_args = Vec::new();
+    let mut only_positional = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // Flags
+        if !only_positional && arg == "--" {
+            only_positional = true;
+            continue
+        }
+        if !only_positional && (arg.starts_with("--") || arg == "-h") {
+            let (name, inline_value) = match arg.as_str() {
+                "-h" => ("help", None),
+                _ => match arg[2..].split_once('=') {
+                    Some((name, value)) => (name, Some(String::from(value))),
+                    None => (&arg[2..], None),
+                },
+            };
+
+            let flag = match spec {
+                Some(x) => x.flag(name),
+                None => GLOBAL_FLAGS.iter().find(|flag| flag.name == name),
+            }.ok_or_else(|| ConfigUnknownFlagError(arg.clone()))?;
+
+            let value = match (flag.kind, inline_value) {
+                (FlagKind::Switch, None) => FlagValue::Switch,
+                (FlagKind::Switch, Some(_)) => return Err(ConfigUnknownFlagError(arg.clone())),
+                (kind, Some(x)) => parse_value(kind, x)?,
+                (kind, None) => match args.next() {
+                    Some(x) => parse_value(kind, x)?,
+                    None => return Err(ConfigFlagValueError(flag.usage())),
+                },
+            };
+
+            if flag.name != "help" && GLOBAL_FLAGS.iter().any(|x| x.name == flag.name) {
+                global_args.push(format!("--{}", flag.name));
+                if value != FlagValue::Switch {
+                    global_args.push(value.to_string());
+                }
+            }
+            flags.insert(String::from(flag.name), value);
+            continue
+        }
+
+        // Command
+        if spec.is_none() {
+            spec = Some(match Command::group(&arg) {
+                // Commands such as "profile list" are named by two words
+                Some(group) => match args.next() {
+                    Some(sub) => CommandSpec::find(&format!("{} {}", group, sub)).ok_or(CommandInvalidError)?,
+                    None => return Err(ConfigArgumentsError(format!("{} COMMAND", group))),
+                },
+                None => CommandSpec::find(&arg).ok_or(CommandInvalidError)?,
+            });
+            continue
+        }
+
+        // Positional arguments
+        remainder.push(arg);
+    }
+
+    // `--help` shows help for the command given, if any, rather than running it
+    if flags.remove("help").is_some() {
+        let remainder = match spec {
+            Some(x) => x.name.split(' ').map(String::from).collect(),
+            None => Vec::new(),
+        };
+        return Ok(ParsedArgs { command: Command::Help, remainder, flags, global_args })
+    }
+
+    let spec = spec.ok_or(ConfigNoCommandError)?;
+
+    // Check if a valid # of args have been provided
+    if remainder.len() < spec.min_args() {
+        return Err(ConfigArgumentsError(spec.usage()))
+    }
+    if spec.max_args().is_some_and(|max| remainder.len() > max) {
+        return Err(ConfigExtraArgumentsError(spec.usage()))
+    }
+
+    Ok(ParsedArgs { command: spec.command.clone(), remainder, flags, global_args })
+}
+
+/// Parses the value of a flag according to its `FlagKind`
+fn parse_value(kind: FlagKind, value: String) -> Result<FlagValue, ProjectError> {
+    match kind {
+        FlagKind::Switch => Ok(FlagValue::Switch),
+        FlagKind::Text => Ok(FlagValue::Text(value)),
+        FlagKind::Integer => value.parse().map(FlagValue::Integer).map_err(|_| InputParseError(value, String::from("integer"))),
+        FlagKind::Decimal => value.parse().map(FlagValue::Decimal).map_err(|_| InputParseError(value, String::from("decimal"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(s: &str) -> Result<ParsedArgs, ProjectError> {
+        parse(s.split_whitespace().map(String::from).collect())
+    }
+
+    #[test]
+    fn commands_resolve_by_alias_and_group() {
+        let parsed = parse_str("pf ls").unwrap();
+        assert_eq!(parsed.command, Command::ProfileC(ProfileCommand::List));
+
+        let parsed = parse_str("es FOO value -3").unwrap();
+        assert_eq!(parsed.command, Command::StockC(StockCommand::Edit));
+        assert_eq!(parsed.remainder, vec!["FOO", "value", "-3"]);
+
+        assert!(matches!(parse_str("frobnicate"), Err(CommandInvalidError)));
+        assert!(matches!(parse_str(""), Err(ConfigNoCommandError)));
+    }
+
+    #[test]
+    fn flags_are_typed_and_validated() {
+        let parsed = parse_str("--profile=paper check --repair --decimal-places 3").unwrap();
+        assert_eq!(parsed.command, Command::Check);
+        assert_eq!(parsed.flags["repair"], FlagValue::Switch);
+        assert_eq!(parsed.flags["profile"], FlagValue::Text(String::from("paper")));
+        assert_eq!(parsed.flags["decimal-places"], FlagValue::Integer(3));
+        assert_eq!(parsed.global_args, vec!["--profile", "paper", "--decimal-places", "3"]);
+
+        assert!(matches!(parse_str("list-users --repair"), Err(ConfigUnknownFlagError(_))));
+        assert!(matches!(parse_str("list-users --decimal-places two"), Err(InputParseError(_, _))));
+        assert!(matches!(parse_str("list-users --profile"), Err(ConfigFlagValueError(_))));
+    }
+
+    #[test]
+    fn argument_counts_are_checked() {
+        assert!(matches!(parse_str("edit-stock FOO value"), Err(ConfigArgumentsError(_))));
+        assert!(matches!(parse_str("login alice bob"), Err(ConfigExtraArgumentsError(_))));
+        assert_eq!(parse_str("undo 2").unwrap().remainder, vec!["2"]);
+        assert_eq!(parse_str("login -- --alice").unwrap().remainder, vec!["--alice"]);
+    }
+
+    #[test]
+    fn help_flag_replaces_command() {
+        let parsed = parse_str("profile create --help").unwrap();
+        assert_eq!(parsed.command, Command::Help);
+        assert_eq!(parsed.remainder, vec!["profile", "create"]);
+    }
+}
@@ -0,0 +1,382 @@
+//! #audit
+//!
+//! This holds the `AuditEntry` type and the functions which append to and read the audit log, a record of who changed
+//! what kept for bookkeeping. Entries are only ever added: no command edits or removes them, not even `init` or `undo`.
+
+// std
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+// external crates
+use serde::{Serialize, Deserialize}; // So we may write entries to the log
+
+// internal crates
+use crate::account::Account;
+use crate::crypto;
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::group::Group;
+use crate::journal::Snapshot;
+use crate::stock::Stock;
+use crate::user::User;
+use crate::Config;
+
+/// An `AuditEntry` records a single command which changed the data
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    /// When the command was run, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// The user logged in when the command was run, if any
+    pub user: Option<String>,
+    /// The canonical name of the command, e.g. "buy-stock"
+    pub command: String,
+    /// The arguments and options of the command as given
+    pub arguments: Vec<String>,
+    /// What the command changed, e.g. "stock FOO value 10 -> 12.5"
+    pub changes: Vec<String>,
+}
+
+/// An `AuditFilter` selects the entries shown by `audit`. Every criterion given must match.
+#[derive(Debug, Default, PartialEq)]
+pub struct AuditFilter {
+    /// Only entries of commands run by this user
+    pub user: Option<String>,
+    /// Only entries at or after this time, in seconds since the Unix epoch
+    pub since: Option<u64>,
+    /// Only entries of the command with this canonical name
+    pub command: Option<String>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.user.as_ref().is_none_or(|x| entry.user.as_ref() == Some(x))
+            && self.since.is_none_or(|x| entry.timestamp >= x)
+            && self.command.as_ref().is_none_or(|x| entry.command == *x)
+    }
+}
+
+/// `append()` adds `entry` to the end of the audit log. A plain log is appended to in place, while an encrypted log
+/// is sealed as a whole and so is written anew with the entry added, then renamed over the old log so that a failure
+/// part way through never loses the entries already logged.
+pub fn append(config: &Config, entry: &AuditEntry) -> Result<(), ProjectError> {
+    let path = config.audit_path();
+    let mut line = serde_json::to_string(entry).map_err(|_| SerializeJSONError)?;
+    line.push('\n');
+
+    if crypto::is_encrypted(config) {
+        let mut contents = read_contents(&path)?;
+        contents.extend(line.into_bytes());
+        let contents = crypto::seal(&path, contents)?;
+        return crate::replace_file(&path, &contents).map_err(|_| IOAuditWriteError(path))
+    }
+
+    let mut file = match fs::OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(x) => x,
+        Err(_) => return Err(IOAuditOpenError(path)),
+    };
+    file.write_all(line.as_bytes()).map_err(|_| IOAuditWriteError(path))
+}
+
+/// `read()` returns every entry of the audit log, oldest first, or none if nothing has been logged yet.
+pub fn read(config: &Config) -> Result<Vec<AuditEntry>, ProjectError> {
+    let path = config.audit_path();
+    let contents = String::from_utf8(read_contents(&path)?).map_err(|_| IOAuditOpenError(path.clone()))?;
+
+    contents.lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| serde_json::from_str(x).map_err(|_| DeserializeJSONError(path.clone())))
+        .collect()
+}
+
+/// `summarize()` describes every difference between the data of `before` and `after`, one line per change to a
+/// user, account, holding, stock or group.
+pub fn summarize(before: &Snapshot, after: &Snapshot) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    for username in union(&before.user_map, &after.user_map) {
+        let archived = (before.archive_map.contains_key(username), after.archive_map.contains_key(username));
+        match (before.user_map.get(username), after.user_map.get(username)) {
+            (None, Some(_)) if archived == (true, false) => changes.push(format!("restored user {}", username)),
+            (None, Some(_)) => changes.push(format!("created user {}", username)),
+            (Some(_), None) if archived == (false, true) => changes.push(format!("archived user {}", username)),
+            (Some(_), None) => changes.push(format!("deleted user {}", username)),
+            (Some(x), Some(y)) if x != y => changes.extend(user_changes(x, y)),
+            _ => (),
+        }
+    }
+
+    for ticker in union(&before.stock_map, &after.stock_map) {
+        match (before.stock_map.get(ticker), after.stock_map.get(ticker)) {
+            (None, Some(x)) => changes.push(format!("created stock {} ({}) at {}", ticker, x.company_name, x.value)),
+            (Some(_), None) => changes.push(format!("deleted stock {}", ticker)),
+            (Some(x), Some(y)) if x != y => changes.extend(stock_changes(x, y)),
+            _ => (),
+        }
+    }
+
+    for name in union(&before.group_map, &after.group_map) {
+        let members = |group: Option<&Group>| group.map(|x| x.members.clone()).unwrap_or_default();
+        let (x, y) = (members(before.group_map.get(name)), members(after.group_map.get(name)));
+        match (before.group_map.contains_key(name), after.group_map.contains_key(name)) {
+            (false, true) => changes.push(format!("created group {}", name)),
+            (true, false) => changes.push(format!("deleted group {}", name)),
+            _ => (),
+        }
+        changes.extend(y.difference(&x).map(|username| format!("added {} to group {}", username, name)));
+        changes.extend(x.difference(&y).map(|username| format!("removed {} from group {}", username, name)));
+    }
+
+    if before.state.current_user != after.state.current_user {
+        changes.push(format!("logged in user {} -> {}", or_none(&before.state.current_user), or_none(&after.state.current_user)));
+    }
+
+    changes
+}
+
+/// `parse_since()` reads the time given to `audit --since`, either a date such as 2024-03-01 (midnight UTC) or a
+/// period before `now` such as 30m, 12h or 7d.
+pub fn parse_since(s: &str, now: u64) -> Result<u64, ProjectError> {
+    let error = || InputParseError(String::from(s), String::from("a date (YYYY-MM-DD) or period (e.g. 7d)"));
+
+    let unit = match s.chars().last() {
+        Some('s') => Some(1),
+        Some('m') => Some(60),
+        Some('h') => Some(3600),
+        Some('d') => Some(86400),
+        _ => None,
+    };
+    if let Some(unit) = unit {
+        let n: u64 = s[..s.len() - 1].parse().map_err(|_| error())?;
+        return n.checked_mul(unit).map(|x| now.saturating_sub(x)).ok_or_else(error)
+    }
+
+    let parts: Vec<&str> = s.split('-').collect();
+    let (year, month, day) = match parts.as_slice() {
+        [y, m, d] if y.len() == 4 && m.len() == 2 && d.len() == 2 => {
+            (y.parse::<i64>().map_err(|_| error())?, m.parse::<u32>().map_err(|_| error())?, d.parse::<u32>().map_err(|_| error())?)
+        },
+        _ => return Err(error()),
+    };
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return Err(error())
+    }
+    u64::try_from(days_from_civil(year, month, day) * 86400).map_err(|_| error())
+}
+
+/// Formats `timestamp`, in seconds since the Unix epoch, as a UTC date and time such as "2024-03-01 09:30:00"
+pub fn format_timestamp(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86400) as i64);
+    let seconds = timestamp % 86400;
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+//
+// Assistive functions
+//
+
+fn read_contents(path: &Path) -> Result<Vec<u8>, ProjectError> {
+    if !path.exists() {
+        return Ok(Vec::new())
+    }
+    let contents = fs::read(path).map_err(|_| IOAuditOpenError(path.to_path_buf()))?;
+    crypto::open(path, contents)
+}
+
+/// The keys of both maps in order
+fn union<'a, T>(a: &'a HashMap<String, T>, b: &'a HashMap<String, T>) -> BTreeSet<&'a String> {
+    a.keys().chain(b.keys()).collect()
+}
+
+fn or_none(s: &Option<String>) -> &str {
+    s.as_deref().unwrap_or("(none)")
+}
+
+fn user_changes(before: &User, after: &User) -> Vec<String> {
+    let username = before.username();
+    let mut changes = Vec::new();
+
+    let properties = [
+        ("first-name", before.first_name(), after.first_name()),
+        ("last-name", before.last_name(), after.last_name()),
+        ("middle-initial", before.middle_initial(), after.middle_initial()),
+    ];
+    for (property, x, y) in properties {
+        if x != y {
+            changes.push(format!("user {} {} {} -> {}", username, property, x, y));
+        }
+    }
+    let optional = [
+        ("email", before.email().map(String::from), after.email().map(String::from)),
+        ("base-currency", before.base_currency().map(String::from), after.base_currency().map(String::from)),
+        ("risk-tolerance", before.risk_tolerance().map(|x| x.to_string()), after.risk_tolerance().map(|x| x.to_string())),
+        ("tax-jurisdiction", before.tax_jurisdiction().map(String::from), after.tax_jurisdiction().map(String::from)),
+    ];
+    for (property, x, y) in optional {
+        if x != y {
+            changes.push(format!("user {} {} {} -> {}", username, property, or_none(&x), or_none(&y)));
+        }
+    }
+    if before.role() != after.role() {
+        changes.push(format!("user {} role {} -> {}", username, before.role(), after.role()));
+    }
+    match (before.has_password(), after.has_password()) {
+        (false, true) => changes.push(format!("user {} password set", username)),
+        (true, false) => changes.push(format!("user {} password removed", username)),
+        (true, true) if !before.same_password(after) => changes.push(format!("user {} password changed", username)),
+        _ => (),
+    }
+
+    let names: BTreeSet<&String> = before.accounts.keys().chain(after.accounts.keys()).collect();
+    for name in names {
+        match (before.accounts.get(name), after.accounts.get(name)) {
+            (None, Some(x)) => changes.push(format!("user {} opened account {} with cash {}", username, name, x.cash)),
+            (Some(_), None) => changes.push(format!("user {} closed account {}", username, name)),
+            (Some(x), Some(y)) if x != y => changes.extend(account_changes(username, name, x, y)),
+            _ => (),
+        }
+    }
+
+    changes
+}
+
+fn account_changes(username: &str, name: &str, before: &Account, after: &Account) -> Vec<String> {
+    let mut changes = Vec::new();
+    for ticker in union(&before.holdings, &after.holdings) {
+        let x = before.holdings.get(ticker).map_or(0, |x| x.quantity);
+        let y = after.holdings.get(ticker).map_or(0, |x| x.quantity);
+        if x != y {
+            changes.push(format!("user {} account {} {} shares {} -> {}", username, name, ticker, x, y));
+        }
+    }
+    if before.cash != after.cash {
+        changes.push(format!("user {} account {} cash {} -> {}", username, name, before.cash, after.cash));
+    }
+    changes
+}
+
+fn stock_changes(before: &Stock, after: &Stock) -> Vec<String> {
+    let mut changes = Vec::new();
+    if before.company_name != after.company_name {
+        changes.push(format!("stock {} company-name {} -> {}", before.ticker, before.company_name, after.company_name));
+    }
+    if before.value != after.value {
+        changes.push(format!("stock {} value {} -> {}", before.ticker, before.value, after.value));
+    }
+    changes
+}
+
+/// The number of days in `month` of `year` of the proleptic Gregorian calendar, where `month` is from 1 to 12
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The number of days from 1970-01-01 to the given date of the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((i64::from(month) + 9) % 12) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The reverse of `days_from_civil()`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::State;
+
+    fn snapshot(user_map: HashMap<String, User>, stock_map: HashMap<String, Stock>) -> Snapshot {
+        Snapshot { user_map, stock_map, archive_map: HashMap::new(), group_map: HashMap::new(), state: State::default() }
+    }
+
+    #[test]
+    fn changes_are_summarized() {
+        let foo = Stock::new_from_details("FOO", "Foo Inc", 10.0).unwrap();
+        let bob = User::new_from_username("bob").unwrap();
+        let before = snapshot(
+            HashMap::from([(String::from("bob"), bob.clone())]),
+            HashMap::from([(String::from("FOO"), foo.clone())]),
+        );
+
+        let mut rich_bob = bob;
+        rich_bob.add_stock(crate::account::DEFAULT_ACCOUNT, &foo, 3).unwrap();
+        let after = snapshot(
+            HashMap::from([(String::from("bob"), rich_bob), (String::from("carol"), User::new_from_username("carol").unwrap())]),
+            HashMap::from([(String::from("FOO"), Stock { value: 12.5, ..foo })]),
+        );
+
+        assert_eq!(summarize(&before, &after), vec![
+            "user bob account main FOO shares 0 -> 3",
+            "created user carol",
+            "stock FOO value 10 -> 12.5",
+        ]);
+        assert!(summarize(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn entries_are_appended_and_filtered() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+        assert!(read(&config).unwrap().is_empty());
+
+        for (timestamp, user, command) in [(100, "alice", "create-stock"), (200, "bob", "buy-stock"), (300, "bob", "undo")] {
+            let entry = AuditEntry {
+                timestamp,
+                user: Some(String::from(user)),
+                command: String::from(command),
+                arguments: Vec::new(),
+                changes: Vec::new(),
+            };
+            // The last entry is added to an encrypted log
+            if command == "undo" {
+                crypto::enable(&config, "hunter2").unwrap();
+            }
+            append(&config, &entry).unwrap();
+        }
+        assert!(!crate::temporary_path(&config.audit_path()).exists());
+
+        let entries = read(&config).unwrap();
+        assert_eq!(entries.len(), 3);
+        let filter = AuditFilter { since: Some(150), ..AuditFilter::default() };
+        assert_eq!(entries.iter().filter(|x| filter.matches(x)).map(|x| x.command.as_str()).collect::<Vec<_>>(), ["buy-stock", "undo"]);
+        let filter = AuditFilter { user: Some(String::from("alice")), command: Some(String::from("buy-stock")), ..AuditFilter::default() };
+        assert!(!entries.iter().any(|x| filter.matches(x)));
+    }
+
+    #[test]
+    fn times_are_parsed_and_formatted() {
+        assert_eq!(parse_since("2024-03-01", 0).unwrap(), 1709251200);
+        assert_eq!(format_timestamp(1709251200 + 3600 + 61), "2024-03-01 01:01:01");
+        assert_eq!(parse_since("2h", 10000).unwrap(), 2800);
+        assert!(matches!(parse_since("yesterday", 0), Err(InputParseError(_, _))));
+        assert!(matches!(parse_since("2024-13-01", 0), Err(InputParseError(_, _))));
+        assert!(matches!(parse_since("2024-02-31", 0), Err(InputParseError(_, _))));
+        assert!(matches!(parse_since("2023-04-31", 0), Err(InputParseError(_, _))));
+        assert!(matches!(parse_since("2023-02-29", 0), Err(InputParseError(_, _))));
+        assert_eq!(parse_since("2024-02-29", 0).unwrap(), 1709164800);
+        assert_eq!(parse_since("2000-02-29", 0).unwrap(), 951782400);
+        assert!(matches!(parse_since("99999999999999999d", 0), Err(InputParseError(_, _))));
+    }
+}
@@ -0,0 +1,240 @@
+//! #check
+//!
+//! This holds the `Violation` type and the functions which find and repair violations of the invariants between the
+//! user map, the stock map, the groups and the `State`
+
+// std
+use std::fmt;
+
+// internal crates
+use crate::journal::Snapshot;
+use crate::stock::Stock;
+
+/// The `Violation` enum represents each way the stored data may be inconsistent with itself
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// The `State` is logged in as a user which is not a key of the user map
+    UnknownCurrentUser(String),
+    /// The `State`'s `logged_in` flag disagrees with whether `current_user` is set
+    InconsistentLogin,
+    /// A user map key differs from the `User.username` stored under it (key, username)
+    UserKeyMismatch(String, String),
+    /// A stock map key differs from the `Stock.ticker` stored under it (key, ticker)
+    StockKeyMismatch(String, String),
+    /// An account's holdings key differs from the ticker of the `StockUnit` stored under it (username, account, key,
+    /// ticker)
+    HoldingKeyMismatch(String, String, String, String),
+    /// An account holds a ticker which is not a key of the stock map (username, account, ticker)
+    MissingStock(String, String, String),
+    /// A group has a member which is not a key of the user map (group, username)
+    UnknownGroupMember(String, String),
+}
+
+impl Violation {
+    /// Returns the kebab-case name of the kind of violation, as shown in structured output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Violation::UnknownCurrentUser(_) => "unknown-current-user",
+            Violation::InconsistentLogin => "inconsistent-login",
+            Violation::UserKeyMismatch(_, _) => "user-key-mismatch",
+            Violation::StockKeyMismatch(_, _) => "stock-key-mismatch",
+            Violation::HoldingKeyMismatch(_, _, _, _) => "holding-key-mismatch",
+            Violation::MissingStock(_, _, _) => "missing-stock",
+            Violation::UnknownGroupMember(_, _) => "unknown-group-member",
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::UnknownCurrentUser(x) => write!(f, "Logged in as user {}, which does not exist.", x),
+            Violation::InconsistentLogin => write!(f, "Login state is inconsistent with the current user."),
+            Violation::UserKeyMismatch(key, username) => write!(f, "User stored under {} has username {}.", key, username),
+            Violation::StockKeyMismatch(key, ticker) => write!(f, "Stock stored under {} has ticker {}.", key, ticker),
+            Violation::HoldingKeyMismatch(username, account, key, ticker) => write!(f,
+                "User {} has a holding in account {} stored under {} with ticker {}.", username, account, key, ticker),
+            Violation::MissingStock(username, account, ticker) => write!(f,
+                "User {} holds stock {} in account {}, which does not exist.", username, ticker, account),
+            Violation::UnknownGroupMember(group, username) => write!(f,
+                "Group {} has member {}, who does not exist.", group, username),
+        }
+    }
+}
+
+/// The `find_violations()` function reports every violation in `snapshot`, ordered by user, ticker and group.
+pub fn find_violations(snapshot: &Snapshot) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    // State
+    let state = &snapshot.state;
+    if state.logged_in != state.current_user.is_some() {
+        violations.push(Violation::InconsistentLogin);
+    }
+    if let Some(x) = &state.current_user {
+        if !snapshot.user_map.contains_key(x) {
+            violations.push(Violation::UnknownCurrentUser(x.clone()));
+        }
+    }
+
+    // Users and their holdings
+    let mut usernames: Vec<&String> = snapshot.user_map.keys().collect();
+    usernames.sort();
+    for key in usernames {
+        let user = &snapshot.user_map[key];
+        if user.username() != key {
+            violations.push(Violation::UserKeyMismatch(key.clone(), String::from(user.username())));
+        }
+
+        for (name, account) in &user.accounts {
+            let mut tickers: Vec<&String> = account.holdings.keys().collect();
+            tickers.sort();
+            for ticker in tickers {
+                let stock_unit = &account.holdings[ticker];
+                if stock_unit.stock.ticker != *ticker {
+                    let found = stock_unit.stock.ticker.clone();
+                    violations.push(Violation::HoldingKeyMismatch(key.clone(), name.clone(), ticker.clone(), found));
+                }
+                if !snapshot.stock_map.contains_key(ticker) {
+                    violations.push(Violation::MissingStock(key.clone(), name.clone(), ticker.clone()));
+                }
+            }
+        }
+    }
+
+    // Stocks
+    let mut tickers: Vec<&String> = snapshot.stock_map.keys().collect();
+    tickers.sort();
+    for key in tickers {
+        let stock = &snapshot.stock_map[key];
+        if stock.ticker != *key {
+            violations.push(Violation::StockKeyMismatch(key.clone(), stock.ticker.clone()));
+        }
+    }
+
+    // Groups
+    let mut names: Vec<&String> = snapshot.group_map.keys().collect();
+    names.sort();
+    for name in names {
+        for username in &snapshot.group_map[name].members {
+            if !snapshot.user_map.contains_key(username) {
+                violations.push(Violation::UnknownGroupMember(name.clone(), username.clone()));
+            }
+        }
+    }
+
+    violations
+}
+
+/// The `repair()` function fixes every violation in `snapshot` and returns the violations which were repaired.
+///
+/// Map keys are treated as authoritative, so mismatched usernames and tickers are set to their keys. An invalid login
+/// is logged out. A holding of a missing stock is kept, and the stock is recreated in the stock map from the copy
+/// recorded in the holding. A member of a group who does not exist is removed from the group.
+pub fn repair(snapshot: &mut Snapshot) -> Vec<Violation> {
+    let violations = find_violations(snapshot);
+
+    for violation in &violations {
+        match violation {
+            Violation::UnknownCurrentUser(_) | Violation::InconsistentLogin => {
+                snapshot.state.logged_in = false;
+                snapshot.state.current_user = None;
+                snapshot.state.session = None;
+                snapshot.state.current_account = None;
+            },
+            Violation::UserKeyMismatch(key, _) => {
+                if let Some(user) = snapshot.user_map.get_mut(key) {
+                    user.set_username(key);
+                }
+            },
+            Violation::StockKeyMismatch(key, _) => {
+                if let Some(stock) = snapshot.stock_map.get_mut(key) {
+                    stock.ticker = key.clone();
+                }
+            },
+            Violation::HoldingKeyMismatch(username, account, key, _) => {
+                if let Some(stock_unit) = snapshot.user_map.get_mut(username)
+                    .and_then(|user| user.accounts.get_mut(account))
+                    .and_then(|account| account.holdings.get_mut(key)) {
+                    stock_unit.stock.ticker = key.clone();
+                }
+            },
+            Violation::MissingStock(username, account, ticker) => {
+                let stock = match snapshot.user_map.get(username)
+                    .and_then(|user| user.accounts.get(account))
+                    .and_then(|account| account.holdings.get(ticker)) {
+                    Some(stock_unit) => Stock { ticker: ticker.clone(), ..stock_unit.stock.clone() },
+                    None => continue,
+                };
+                // Several users may hold the same missing stock, the first copy found is used
+                snapshot.stock_map.entry(ticker.clone()).or_insert(stock);
+            },
+            Violation::UnknownGroupMember(group, username) => {
+                if let Some(group) = snapshot.group_map.get_mut(group) {
+                    group.members.remove(username);
+                }
+            },
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::DEFAULT_ACCOUNT;
+    use crate::group::Group;
+    use crate::user::User;
+    use crate::State;
+    use std::collections::HashMap;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            user_map: HashMap::new(),
+            stock_map: HashMap::new(),
+            archive_map: HashMap::new(),
+            group_map: HashMap::new(),
+            state: State::default(),
+        }
+    }
+
+    #[test]
+    fn consistent_data_has_no_violations() {
+        let mut snapshot = snapshot();
+        let stock = Stock::new_from_ticker("FOO").unwrap();
+        let mut user = User::new_from_username("alice").unwrap();
+        user.add_stock(DEFAULT_ACCOUNT, &stock, 3).unwrap();
+        snapshot.stock_map.insert(String::from("FOO"), stock);
+        snapshot.user_map.insert(String::from("alice"), user);
+        snapshot.state = State { logged_in: true, current_user: Some(String::from("alice")), ..State::default() };
+
+        assert!(find_violations(&snapshot).is_empty());
+    }
+
+    #[test]
+    fn repair_fixes_deleted_user_and_stock() {
+        let mut snapshot = snapshot();
+        let mut user = User::new_from_username("alice").unwrap();
+        user.add_stock(DEFAULT_ACCOUNT, &Stock::new_from_ticker("FOO").unwrap(), 3).unwrap();
+        snapshot.user_map.insert(String::from("bob"), user);
+        snapshot.state = State { logged_in: true, current_user: Some(String::from("carol")), ..State::default() };
+        let mut group = Group::default();
+        group.members.extend([String::from("bob"), String::from("carol")]);
+        snapshot.group_map.insert(String::from("home"), group);
+
+        assert_eq!(find_violations(&snapshot), vec![
+            Violation::UnknownCurrentUser(String::from("carol")),
+            Violation::UserKeyMismatch(String::from("bob"), String::from("alice")),
+            Violation::MissingStock(String::from("bob"), String::from(DEFAULT_ACCOUNT), String::from("FOO")),
+            Violation::UnknownGroupMember(String::from("home"), String::from("carol")),
+        ]);
+
+        assert_eq!(repair(&mut snapshot).len(), 4);
+        assert!(find_violations(&snapshot).is_empty());
+        assert_eq!(snapshot.user_map["bob"].username(), "bob");
+        assert!(snapshot.stock_map.contains_key("FOO"));
+        assert_eq!(snapshot.state.current_user, None);
+        assert_eq!(snapshot.group_map["home"].members.len(), 1);
+    }
+}
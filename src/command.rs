@@ -1,29 +1,36 @@
 //! #command
 //!
-//! This holds the `Command` type and related methods
+//! This holds the `Command` type and related methods, along with the `CommandSpec` table which declares the name,
+//! aliases, arguments and flags of every command
 
 use crate::ProjectError;
 use crate::ProjectError::*;
 use std::fmt; // So we may define `Display` for `Command`
 
 /// `StateCommand` represents commands that relate to the state, such as logging in or out.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum StateCommand {
     Login,
     Logout,
+    WhoAmI,
 }
 
 /// `UserCommand` represents commands that relate to `User` management, such as creating or deleting `User`s.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum UserCommand {
     Create,
     Delete,
+    Restore,
     Edit,
     List,
+    Show,
+    SetPassword,
+    ChangePassword,
+    SetRole,
 }
 
 /// `StockCommand` represents commands that relate to `Stock` management, such as creating or deleting `Stock`s
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum StockCommand {
     Create,
     Delete,
@@ -32,104 +39,690 @@ pub enum StockCommand {
 }
 
 /// `PortfolioCommand` represents commands that relate to management of the logged in user's `portfolio` of `StockUnit`s
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PortfolioCommand {
     Buy,
     List,
 }
 
+/// `HistoryCommand` represents commands that concern the history of changes, such as undoing them through the
+/// `Journal` or viewing the audit log.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryCommand {
+    Undo,
+    Redo,
+    Audit,
+}
+
+/// `EncryptionCommand` represents commands that manage encryption of the data files at rest
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncryptionCommand {
+    Encrypt,
+    Decrypt,
+    ChangePassphrase,
+}
+
+/// `ProfileCommand` represents commands that manage named profiles, each with its own configuration directory
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileCommand {
+    List,
+    Create,
+    Switch,
+    Delete,
+}
+
+/// `AccountCommand` represents commands that manage the logged in user's accounts
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountCommand {
+    Create,
+    List,
+    Switch,
+}
+
+/// `GroupCommand` represents commands that manage groups of users, such as a household, and view their combined
+/// holdings
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupCommand {
+    Create,
+    Delete,
+    Add,
+    Remove,
+    List,
+    Portfolio,
+}
+
+/// `SettingsCommand` represents commands that read or change the settings file
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingsCommand {
+    Get,
+    Set,
+    List,
+}
+
 /// The `Command` enum represents the variety of input cases a user could specify.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     Init,
+    Check,
+    Help,
+    Completions,
+    Complete,
     Console,
     Exit, // Only accessible in console mode 
+    RunScript,
+    Dashboard,
     StateC(StateCommand),
     UserC(UserCommand),
     StockC(StockCommand),
     PortfolioC(PortfolioCommand),
+    AccountC(AccountCommand),
+    GroupC(GroupCommand),
+    HistoryC(HistoryCommand),
+    EncryptionC(EncryptionCommand),
+    ProfileC(ProfileCommand),
+    SettingsC(SettingsCommand),
+}
+
+/// The `FlagKind` enum represents the type of value a flag takes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlagKind {
+    /// The flag takes no value, e.g. `--repair`
+    Switch,
+    /// The flag takes any string, e.g. `--profile paper`
+    Text,
+    /// The flag takes a whole number
+    Integer,
+    /// The flag takes a decimal number
+    Decimal,
 }
 
+/// The `Completion` enum represents what a positional argument may be completed with by the shell completion scripts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Completion {
+    /// The argument is free-form, such as a new name or a number
+    Nothing,
+    /// The first word of a command
+    Command,
+    /// The second word of the command group given by the previous argument
+    Subcommand,
+    Username,
+    Ticker,
+    Profile,
+    /// An account of the logged in user
+    Account,
+    /// A group of users
+    Group,
+    /// The name of a setting
+    Setting,
+    /// A value of the setting given by the previous argument
+    SettingValue,
+    /// A property of a `User` accepted by `edit-user`
+    UserProperty,
+    /// A property of a `Stock` accepted by `edit-stock`
+    StockProperty,
+    /// A `Role` of a `User`
+    Role,
+    /// A shell supported by `completions`
+    Shell,
+}
+
+/// The `Access` enum represents who may run a command. Access is only enforced once the user map has an
+/// administrator; until then anyone may run any command, so that a fresh data directory can be set up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    /// Anyone, logged in or not
+    Anyone,
+    /// Any logged-in user, including read-only users
+    User,
+    /// Logged-in members and administrators
+    Member,
+    /// Logged-in members for their own user, given as the first argument, and administrators for any user
+    OwnUser,
+    /// Logged-in users for a group they belong to, given as the first argument, and administrators for any group
+    GroupMember,
+    /// Logged-in administrators
+    Admin,
+}
+
+/// An `ArgSpec` declares a positional argument of a command
+#[derive(Debug)]
+pub struct ArgSpec {
+    /// The name shown in usage text, e.g. TICKER
+    pub name: &'static str,
+    /// Whether the argument must be provided. Optional arguments always follow required ones.
+    pub required: bool,
+    /// Whether the argument may be repeated any number of times. Only the last argument may repeat.
+    pub repeats: bool,
+    /// What the argument may be completed with
+    pub completion: Completion,
+}
+
+/// A `FlagSpec` declares a named option of a command, given as `--name` or `--name VALUE`
+#[derive(Debug)]
+pub struct FlagSpec {
+    /// The name of the flag without its leading `--`
+    pub name: &'static str,
+    /// The type of value the flag takes
+    pub kind: FlagKind,
+    /// The name shown for the flag's value in usage text, empty for a `Switch`
+    pub value_name: &'static str,
+    pub description: &'static str,
+}
+
+/// A `CommandSpec` declares everything needed to parse, validate and describe a `Command`
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub command: Command,
+    /// The canonical name of the command. Commands of a group, such as "profile list", are named by two words.
+    pub name: &'static str,
+    /// Other names the command may be called by
+    pub aliases: &'static [&'static str],
+    pub args: &'static [ArgSpec],
+    pub flags: &'static [FlagSpec],
+    /// Whether the command may modify the user map or stock map, in which case its effects are recorded in the
+    /// `Journal`
+    pub mutating: bool,
+    /// Who may run the command once an administrator exists
+    pub access: Access,
+    pub description: &'static str,
+}
+
+const fn required(name: &'static str, completion: Completion) -> ArgSpec {
+    ArgSpec { name, required: true, repeats: false, completion }
+}
+
+const fn optional(name: &'static str, completion: Completion) -> ArgSpec {
+    ArgSpec { name, required: false, repeats: false, completion }
+}
+
+const fn repeated(name: &'static str, completion: Completion) -> ArgSpec {
+    ArgSpec { name, required: false, repeats: true, completion }
+}
+
+const fn switch(name: &'static str, description: &'static str) -> FlagSpec {
+    FlagSpec { name, kind: FlagKind::Switch, value_name: "", description }
+}
+
+const fn option(name: &'static str, kind: FlagKind, value_name: &'static str, description: &'static str) -> FlagSpec {
+    FlagSpec { name, kind, value_name, description }
+}
+
+/// The flags accepted by every command, anywhere on the command line
+pub const GLOBAL_FLAGS: &[FlagSpec] = &[
+    option("profile", FlagKind::Text, "NAME", "Use the data of profile NAME instead of the active profile"),
+    option("base-currency", FlagKind::Text, "CODE", "Override the base-currency setting"),
+    option("format", FlagKind::Text, "FORMAT", "Output format: text, json or csv (overrides the format setting)"),
+    option("decimal-places", FlagKind::Integer, "N", "Override the decimal-places setting"),
+    option("confirm-delete", FlagKind::Text, "BOOL", "Override the confirm-delete setting"),
+    option("lot-matching", FlagKind::Text, "METHOD", "Override the lot-matching setting (reserved, not yet used)"),
+    option("quote-provider", FlagKind::Text, "PROVIDER", "Override the quote-provider setting (reserved, not yet used)"),
+    switch("yes", "Answer yes to every confirmation"),
+    switch("quiet", "Do not print notifications"),
+    switch("no-input", "Never ask a question, failing instead (implied when standard input is not a terminal)"),
+    switch("help", "Show help for the command instead of running it"),
+];
+
+/// The first words of two-word commands and their aliases, e.g. "pf list" is "profile list"
+pub const GROUPS: &[(&str, &[&str])] = &[
+    ("profile", &["pf"]),
+    ("account", &["acct"]),
+    ("group", &["grp"]),
+    ("config", &["cfg"]),
+];
+
+/// Every command, in the order they are listed by `help`
+pub const COMMANDS: &[CommandSpec] = &[
+    // Special Commands
+    CommandSpec {
+        command: Command::Init, name: "init", aliases: &["i"],
+        args: &[], flags: &[], mutating: true, access: Access::Admin,
+        description: "Reset/initialize all user and stock data.",
+    },
+    CommandSpec {
+        command: Command::Check, name: "check", aliases: &["ch"],
+        args: &[], flags: &[switch("repair", "Fix every problem found and summarize the repairs")],
+        mutating: true, access: Access::Admin,
+        description: "Report inconsistencies between users, stocks and the login state.",
+    },
+    CommandSpec {
+        command: Command::Help, name: "help", aliases: &["h", "?"],
+        args: &[optional("COMMAND", Completion::Command), optional("SUBCOMMAND", Completion::Subcommand)], flags: &[],
+        mutating: false, access: Access::Anyone,
+        description: "List every command, or describe COMMAND in detail.",
+    },
+    CommandSpec {
+        command: Command::Completions, name: "completions", aliases: &[],
+        args: &[required("SHELL", Completion::Shell)], flags: &[], mutating: false, access: Access::Anyone,
+        description: "Print the completion script for SHELL (bash, zsh or fish).",
+    },
+    CommandSpec {
+        command: Command::Complete, name: "__complete", aliases: &[],
+        args: &[repeated("WORDS", Completion::Nothing)], flags: &[], mutating: false, access: Access::Anyone,
+        description: "Print the completions of the next word after WORDS, used by the completion scripts.",
+    },
+    CommandSpec {
+        command: Command::Console, name: "console", aliases: &["co"],
+        args: &[], flags: &[], mutating: false, access: Access::Anyone,
+        description: "Enter console mode, where commands are read one line at a time.",
+    },
+    CommandSpec {
+        command: Command::Exit, name: "exit", aliases: &["q", "quit"],
+        args: &[], flags: &[], mutating: false, access: Access::Anyone,
+        description: "Leave console mode.",
+    },
+    CommandSpec {
+        command: Command::RunScript, name: "run-script", aliases: &["rs"],
+        args: &[required("FILE", Completion::Nothing)],
+        flags: &[option("on-error", FlagKind::Text, "POLICY", "stop (the default) or continue when a line fails")],
+        mutating: false, access: Access::Anyone,
+        description: "Run the commands in FILE (- for standard input) one per line, skipping blank lines and # comments.",
+    },
+    CommandSpec {
+        command: Command::Dashboard, name: "dashboard", aliases: &["db"],
+        args: &[], flags: &[], mutating: false, access: Access::Anyone,
+        description: "Show stocks and the portfolio full-screen, where stocks may be bought and edited.",
+    },
+    // State Management Commands
+    CommandSpec {
+        command: Command::StateC(StateCommand::Login), name: "login", aliases: &["li"],
+        args: &[optional("USERNAME", Completion::Username)],
+        flags: &[switch("read-only", "Refuse every command which changes data until logging out")],
+        mutating: false, access: Access::Anyone,
+        description: "Log in as USERNAME, or view as a guest with --read-only and no USERNAME.",
+    },
+    CommandSpec {
+        command: Command::StateC(StateCommand::Logout), name: "logout", aliases: &["lo"],
+        args: &[], flags: &[], mutating: false, access: Access::Anyone,
+        description: "Log out.",
+    },
+    CommandSpec {
+        command: Command::StateC(StateCommand::WhoAmI), name: "whoami", aliases: &["who"],
+        args: &[], flags: &[], mutating: false, access: Access::Anyone,
+        description: "Show the logged-in user, how long ago they logged in and when their session expires.",
+    },
+    // User Management Commands
+    CommandSpec {
+        command: Command::UserC(UserCommand::Create), name: "create-user", aliases: &["cu"],
+        args: &[required("USERNAME", Completion::Nothing)],
+        flags: &[
+            option("first", FlagKind::Text, "NAME", "The user's first name"),
+            option("last", FlagKind::Text, "NAME", "The user's last name"),
+            option("middle", FlagKind::Text, "INITIAL", "The user's middle initial"),
+            option("role", FlagKind::Text, "ROLE", "admin, member (the default) or read-only; the first user is always admin"),
+        ],
+        mutating: true, access: Access::Admin,
+        description: "Create a user, asking for any details not given when run from a terminal.",
+    },
+    CommandSpec {
+        command: Command::UserC(UserCommand::Delete), name: "delete-user", aliases: &["du"],
+        args: &[required("USERNAME", Completion::Username)],
+        flags: &[switch("archive", "Archive the user so that restore-user may restore them, instead of deleting them")],
+        mutating: true, access: Access::OwnUser,
+        description: "Delete a user after confirmation, exporting any holdings to a CSV file first.",
+    },
+    CommandSpec {
+        command: Command::UserC(UserCommand::Restore), name: "restore-user", aliases: &["ru"],
+        args: &[required("USERNAME", Completion::Nothing)], flags: &[], mutating: true, access: Access::Admin,
+        description: "Restore a user archived by delete-user --archive.",
+    },
+    CommandSpec {
+        command: Command::UserC(UserCommand::Edit), name: "edit-user", aliases: &["eu"],
+        args: &[required("USERNAME", Completion::Username), required("PROPERTY", Completion::UserProperty), required("VALUE", Completion::Nothing)], flags: &[],
+        mutating: true, access: Access::OwnUser,
+        description: "Set PROPERTY (username, first-name, last-name, middle-initial, email, base-currency, \
+            risk-tolerance or tax-jurisdiction) of a user to VALUE, where \"\" clears the last four.",
+    },
+    CommandSpec {
+        command: Command::UserC(UserCommand::List), name: "list-users", aliases: &["lu"],
+        args: &[], flags: &[switch("archived", "List the archived users instead")], mutating: false, access: Access::Anyone,
+        description: "List every user.",
+    },
+    CommandSpec {
+        command: Command::UserC(UserCommand::Show), name: "show-user", aliases: &["su"],
+        args: &[required("USERNAME", Completion::Username)], flags: &[], mutating: false, access: Access::User,
+        description: "Show every detail of a user.",
+    },
+    CommandSpec {
+        command: Command::UserC(UserCommand::SetPassword), name: "set-password", aliases: &["spw"],
+        args: &[], flags: &[], mutating: true, access: Access::User,
+        description: "Set a password for the logged-in user, which login will then ask for.",
+    },
+    CommandSpec {
+        command: Command::UserC(UserCommand::ChangePassword), name: "change-password", aliases: &["cpw"],
+        args: &[], flags: &[switch("remove", "Remove the password instead of changing it")],
+        mutating: true, access: Access::User,
+        description: "Change the password of the logged-in user, after asking for the current one.",
+    },
+    CommandSpec {
+        command: Command::UserC(UserCommand::SetRole), name: "set-role", aliases: &["sr"],
+        args: &[required("USERNAME", Completion::Username), required("ROLE", Completion::Role)], flags: &[],
+        mutating: true, access: Access::Admin,
+        description: "Make USERNAME an admin, a member or a read-only user.",
+    },
+    // Stock Management Commands
+    CommandSpec {
+        command: Command::StockC(StockCommand::Create), name: "create-stock", aliases: &["cs"],
+        args: &[required("TICKER", Completion::Nothing)],
+        flags: &[
+            option("name", FlagKind::Text, "NAME", "The company's name"),
+            option("value", FlagKind::Decimal, "VALUE", "The value of one share"),
+        ],
+        mutating: true, access: Access::Admin,
+        description: "Create a stock, asking for any details not given when run from a terminal.",
+    },
+    CommandSpec {
+        command: Command::StockC(StockCommand::Delete), name: "delete-stock", aliases: &["ds"],
+        args: &[required("TICKER", Completion::Ticker)], flags: &[], mutating: true, access: Access::Admin,
+        description: "Delete a stock after confirmation.",
+    },
+    CommandSpec {
+        command: Command::StockC(StockCommand::Edit), name: "edit-stock", aliases: &["es"],
+        args: &[required("TICKER", Completion::Ticker), required("PROPERTY", Completion::StockProperty), required("VALUE", Completion::Nothing)], flags: &[],
+        mutating: true, access: Access::Admin,
+        description: "Set PROPERTY (ticker, company-name or value) of a stock to VALUE.",
+    },
+    CommandSpec {
+        command: Command::StockC(StockCommand::List), name: "list-stocks", aliases: &["ls"],
+        args: &[], flags: &[], mutating: false, access: Access::Anyone,
+        description: "List every stock.",
+    },
+    // Portfolio Management Commands
+    CommandSpec {
+        command: Command::PortfolioC(PortfolioCommand::Buy), name: "buy-stock", aliases: &["bs"],
+        args: &[required("TICKER", Completion::Ticker), required("QUANTITY", Completion::Nothing)],
+        flags: &[option("account", FlagKind::Text, "NAME", "Buy in account NAME instead of the current account")],
+        mutating: true, access: Access::Member,
+        description: "Add QUANTITY shares of a stock to the logged in user's current account.",
+    },
+    CommandSpec {
+        command: Command::PortfolioC(PortfolioCommand::List), name: "list-portfolio", aliases: &["lp"],
+        args: &[],
+        flags: &[
+            option("account", FlagKind::Text, "NAME", "List account NAME instead of the current account"),
+            switch("all", "List the holdings of every account combined"),
+        ],
+        mutating: false, access: Access::User,
+        description: "List the logged in user's holdings in the current account, or in every account with --all.",
+    },
+    // Account Commands
+    CommandSpec {
+        command: Command::AccountC(AccountCommand::Create), name: "account create", aliases: &["account new"],
+        args: &[required("NAME", Completion::Nothing)],
+        flags: &[option("cash", FlagKind::Decimal, "AMOUNT", "The cash the account starts with, 0 if not given")],
+        mutating: true, access: Access::Member,
+        description: "Open an account named NAME for the logged in user, e.g. brokerage or ira.",
+    },
+    CommandSpec {
+        command: Command::AccountC(AccountCommand::List), name: "account list", aliases: &["account ls"],
+        args: &[], flags: &[], mutating: false, access: Access::User,
+        description: "List the logged in user's accounts with their cash and value, and the total of them all.",
+    },
+    CommandSpec {
+        command: Command::AccountC(AccountCommand::Switch), name: "account switch", aliases: &["account use"],
+        args: &[required("NAME", Completion::Account)], flags: &[], mutating: false, access: Access::User,
+        description: "Make NAME the account buy-stock and list-portfolio apply to until logging out.",
+    },
+    // Group Commands
+    CommandSpec {
+        command: Command::GroupC(GroupCommand::Create), name: "group create", aliases: &["group new"],
+        args: &[required("NAME", Completion::Nothing), repeated("USERNAME", Completion::Username)], flags: &[],
+        mutating: true, access: Access::Admin,
+        description: "Create a group of users named NAME, e.g. a household, with the users given as its members.",
+    },
+    CommandSpec {
+        command: Command::GroupC(GroupCommand::Delete), name: "group delete", aliases: &["group rm"],
+        args: &[required("NAME", Completion::Group)], flags: &[], mutating: true, access: Access::Admin,
+        description: "Delete a group after confirmation, leaving its members as they are.",
+    },
+    CommandSpec {
+        command: Command::GroupC(GroupCommand::Add), name: "group add", aliases: &[],
+        args: &[required("NAME", Completion::Group), required("USERNAME", Completion::Username), repeated("USERNAME", Completion::Username)],
+        flags: &[], mutating: true, access: Access::Admin,
+        description: "Add users to a group.",
+    },
+    CommandSpec {
+        command: Command::GroupC(GroupCommand::Remove), name: "group remove", aliases: &[],
+        args: &[required("NAME", Completion::Group), required("USERNAME", Completion::Username), repeated("USERNAME", Completion::Username)],
+        flags: &[], mutating: true, access: Access::Admin,
+        description: "Remove users from a group.",
+    },
+    CommandSpec {
+        command: Command::GroupC(GroupCommand::List), name: "group list", aliases: &["group ls"],
+        args: &[], flags: &[], mutating: false, access: Access::User,
+        description: "List every group and its members.",
+    },
+    CommandSpec {
+        command: Command::GroupC(GroupCommand::Portfolio), name: "group-portfolio", aliases: &["gp", "group portfolio"],
+        args: &[required("GROUP", Completion::Group)], flags: &[], mutating: false, access: Access::GroupMember,
+        description: "List the holdings of every member of GROUP combined by ticker, with each member's share, and the \
+            value and cash of each member. Only members and administrators may view a group.",
+    },
+    // History Commands
+    CommandSpec {
+        command: Command::HistoryC(HistoryCommand::Undo), name: "undo", aliases: &["un"],
+        args: &[optional("COUNT", Completion::Nothing)], flags: &[], mutating: false, access: Access::Member,
+        description: "Undo the last mutating command, or the last COUNT. Members may only undo their own commands.",
+    },
+    CommandSpec {
+        command: Command::HistoryC(HistoryCommand::Redo), name: "redo", aliases: &["re"],
+        args: &[optional("COUNT", Completion::Nothing)], flags: &[], mutating: false, access: Access::Member,
+        description: "Redo the last undone command, or the last COUNT. Members may only redo their own commands.",
+    },
+    CommandSpec {
+        command: Command::HistoryC(HistoryCommand::Audit), name: "audit", aliases: &["au"],
+        args: &[],
+        flags: &[
+            option("user", FlagKind::Text, "USERNAME", "Only show changes made by USERNAME"),
+            option("since", FlagKind::Text, "WHEN", "Only show changes since a date (YYYY-MM-DD, UTC) or period (e.g. 7d, 12h)"),
+            option("command", FlagKind::Text, "COMMAND", "Only show changes made by COMMAND"),
+        ],
+        mutating: false, access: Access::Admin,
+        description: "Show the audit log of every change to the data: when, by whom, the command and what it changed.",
+    },
+    // Encryption Commands
+    CommandSpec {
+        command: Command::EncryptionC(EncryptionCommand::Encrypt), name: "encrypt", aliases: &[],
+        args: &[], flags: &[], mutating: false, access: Access::Admin,
+        description: "Encrypt all data with a new passphrase.",
+    },
+    CommandSpec {
+        command: Command::EncryptionC(EncryptionCommand::Decrypt), name: "decrypt", aliases: &[],
+        args: &[], flags: &[], mutating: false, access: Access::Admin,
+        description: "Remove encryption from all data.",
+    },
+    CommandSpec {
+        command: Command::EncryptionC(EncryptionCommand::ChangePassphrase), name: "change-passphrase", aliases: &["cp"],
+        args: &[], flags: &[], mutating: false, access: Access::Admin,
+        description: "Re-encrypt all data with a new passphrase.",
+    },
+    // Profile Commands
+    CommandSpec {
+        command: Command::ProfileC(ProfileCommand::List), name: "profile list", aliases: &["profile ls"],
+        args: &[], flags: &[], mutating: false, access: Access::Anyone,
+        description: "List every profile, marking the active profile.",
+    },
+    CommandSpec {
+        command: Command::ProfileC(ProfileCommand::Create), name: "profile create", aliases: &["profile new"],
+        args: &[required("NAME", Completion::Nothing), optional("DIRECTORY", Completion::Nothing)], flags: &[],
+        mutating: false, access: Access::Admin,
+        description: "Create a profile, keeping its data in DIRECTORY if given.",
+    },
+    CommandSpec {
+        command: Command::ProfileC(ProfileCommand::Switch), name: "profile switch", aliases: &["profile use"],
+        args: &[required("NAME", Completion::Profile)], flags: &[], mutating: false, access: Access::Admin,
+        description: "Make NAME the active profile.",
+    },
+    CommandSpec {
+        command: Command::ProfileC(ProfileCommand::Delete), name: "profile delete", aliases: &["profile rm"],
+        args: &[required("NAME", Completion::Profile)], flags: &[], mutating: false, access: Access::Admin,
+        description: "Delete a profile and its data after confirmation.",
+    },
+    // Settings Commands
+    CommandSpec {
+        command: Command::SettingsC(SettingsCommand::Get), name: "config get", aliases: &[],
+        args: &[required("KEY", Completion::Setting)], flags: &[], mutating: false, access: Access::Anyone,
+        description: "Show the value of setting KEY.",
+    },
+    CommandSpec {
+        command: Command::SettingsC(SettingsCommand::Set), name: "config set", aliases: &[],
+        args: &[required("KEY", Completion::Setting), required("VALUE", Completion::SettingValue)], flags: &[],
+        mutating: false, access: Access::Admin,
+        description: "Change setting KEY to VALUE in the settings file. The lot-matching and quote-provider settings \
+            are reserved: they are kept but not yet used.",
+    },
+    CommandSpec {
+        command: Command::SettingsC(SettingsCommand::List), name: "config list", aliases: &["config ls"],
+        args: &[], flags: &[], mutating: false, access: Access::Anyone,
+        description: "List every setting and its value.",
+    },
+];
 
 impl Command {
 
-    /// Constructor for the `Command` enum to parse a `String` input
+    /// Constructor for the `Command` enum to parse a `String` input, which may be a name or an alias
     pub fn new(s: &str) -> Result<Command, ProjectError> {
-        Ok(match String::from(s).to_lowercase().as_str() {
-            // Special Commands
-            "i" | "init"                => Command::Init,
-            "co" | "console"            => Command::Console,
-            "q" | "quit" | "exit"       => Command::Exit,
-            // State Management Commands
-            "li" | "login"              => Command::StateC(StateCommand::Login),
-            "lo" | "logout"             => Command::StateC(StateCommand::Logout),
-            // User Management Commands
-            "cu" | "create-user"        => Command::UserC(UserCommand::Create),
-            "du" | "delete-user"        => Command::UserC(UserCommand::Delete),
-            "eu" | "edit-user"          => Command::UserC(UserCommand::Edit),
-            "lu" | "list-users"         => Command::UserC(UserCommand::List),
-            // Stock Management Commands
-            "cs" | "create-stock"       => Command::StockC(StockCommand::Create),
-            "ds" | "delete-stock"       => Command::StockC(StockCommand::Delete),
-            "es" | "edit-stock"         => Command::StockC(StockCommand::Edit),
-            "ls" | "list-stocks"        => Command::StockC(StockCommand::List), 
-            // Portfolio Management Commands
-            "bs" | "buy-stock"          => Command::PortfolioC(PortfolioCommand::Buy),
-            "lp" | "list-portfolio"     => Command::PortfolioC(PortfolioCommand::List),
-            _ => return Err(CommandInvalidError),
-        })
-    }
-
-    /// Returns the number of arguments expected after the `Command`
-    pub fn num_args(&self) -> i32 {
-        match self {
-            // Special Commands
-            Command::Init                                   => 0,
-            Command::Console                                => 0,
-            Command::Exit                                   => 0,
-            // State Management Commands
-            Command::StateC(StateCommand::Login)            => 1,
-            Command::StateC(StateCommand::Logout)           => 0,
-            // User Management Commands
-            Command::UserC(UserCommand::Create)             => 1,
-            Command::UserC(UserCommand::Delete)             => 1,
-            Command::UserC(UserCommand::Edit)               => 3,
-            Command::UserC(UserCommand::List)               => 0,
-            // Stock Management Commands
-            Command::StockC(StockCommand::Create)           => 1,
-            Command::StockC(StockCommand::Delete)           => 1,
-            Command::StockC(StockCommand::Edit)             => 3,
-            Command::StockC(StockCommand::List)             => 0,
-            // Portfolio Management Commands
-            Command::PortfolioC(PortfolioCommand::Buy)      => 2,
-            Command::PortfolioC(PortfolioCommand::List)     => 0,
+        CommandSpec::find(s).map(|spec| spec.command.clone()).ok_or(CommandInvalidError)
+    }
+
+    /// Returns the canonical name of the command group `s` belongs to, if it is the first word of a two-word command
+    /// such as "profile list"
+    pub fn group(s: &str) -> Option<&'static str> {
+        let s = s.to_lowercase();
+        GROUPS.iter()
+            .find(|(name, aliases)| *name == s || aliases.contains(&s.as_str()))
+            .map(|(name, _)| *name)
+    }
+
+    /// Returns the `CommandSpec` declaring this `Command`
+    pub fn spec(&self) -> &'static CommandSpec {
+        COMMANDS.iter().find(|spec| spec.command == *self).expect("every command is declared in COMMANDS")
+    }
+
+    /// Returns `true` if the `Command` may modify the user map or stock map, in which case its effects are recorded in
+    /// the `Journal`
+    pub fn is_mutating(&self) -> bool {
+        self.spec().mutating
+    }
+
+    /// Returns `true` if the `Command` may change the data, settings or profiles, which a read-only session may not do.
+    /// These are the mutating commands along with those whose changes are not recorded in the `Journal`. Like `login`
+    /// and `logout`, `account switch` only changes the session kept in the `State`, so it is deliberately left out:
+    /// a read-only session may look at each of its accounts.
+    pub fn changes_data(&self) -> bool {
+        self.is_mutating() || matches!(self,
+            Command::HistoryC(HistoryCommand::Undo | HistoryCommand::Redo) |
+            Command::EncryptionC(_) |
+            Command::ProfileC(ProfileCommand::Create | ProfileCommand::Switch | ProfileCommand::Delete) |
+            Command::SettingsC(SettingsCommand::Set))
+    }
+
+    /// Returns who may run the `Command`
+    pub fn access(&self) -> Access {
+        self.spec().access
+    }
+}
+
+impl CommandSpec {
+
+    /// Returns the `CommandSpec` named `s`, by name or alias
+    pub fn find(s: &str) -> Option<&'static CommandSpec> {
+        let s = s.to_lowercase();
+        COMMANDS.iter().find(|spec| spec.name == s || spec.aliases.contains(&s.as_str()))
+    }
+
+    /// Returns the flag of this command or the global flag named `name`
+    pub fn flag(&self, name: &str) -> Option<&'static FlagSpec> {
+        self.flags.iter().chain(GLOBAL_FLAGS.iter()).find(|flag| flag.name == name)
+    }
+
+    /// Returns the number of positional arguments which must be provided
+    pub fn min_args(&self) -> usize {
+        self.args.iter().filter(|arg| arg.required).count()
+    }
+
+    /// Returns the number of positional arguments which may be provided, if limited
+    pub fn max_args(&self) -> Option<usize> {
+        match self.args.last() {
+            Some(arg) if arg.repeats => None,
+            _ => Some(self.args.len()),
+        }
+    }
+
+    /// Returns the `ArgSpec` of the positional argument at `index`
+    pub fn arg(&self, index: usize) -> Option<&'static ArgSpec> {
+        match self.args.get(index) {
+            Some(arg) => Some(arg),
+            None => self.args.last().filter(|arg| arg.repeats),
+        }
+    }
+
+    /// Returns `true` if the command is only meant to be run by other programs, and so is left out of `help`
+    pub fn is_hidden(&self) -> bool {
+        self.name.starts_with("__")
+    }
+
+    /// Generates the usage text of the command, e.g. "check [--repair]"
+    pub fn usage(&self) -> String {
+        let mut usage = String::from(self.name);
+        for arg in self.args {
+            match (arg.required, arg.repeats) {
+                (_, true) => usage.push_str(&format!(" [{}...]", arg.name)),
+                (true, false) => usage.push_str(&format!(" {}", arg.name)),
+                (false, false) => usage.push_str(&format!(" [{}]", arg.name)),
+            }
+        }
+        for flag in self.flags {
+            usage.push_str(&format!(" [{}]", flag.usage()));
+        }
+        usage
+    }
+}
+
+impl CommandSpec {
+
+    /// Generates the detailed help of the command shown by `help COMMAND`
+    pub fn help_text(&self) -> String {
+        let mut text = format!("Usage: {}\n\n{}\n", self.usage(), self.description);
+        if !self.aliases.is_empty() {
+            text.push_str(&format!("\nAliases: {}\n", self.aliases.join(", ")));
+        }
+        if !self.flags.is_empty() {
+            text.push_str("\nOptions:\n");
+            for flag in self.flags {
+                text.push_str(&format!("  {:<28}{}\n", flag.usage(), flag.description));
+            }
+        }
+        text
+    }
+}
+
+/// Generates the list of every command shown by `help`
+pub fn help_text() -> String {
+    let mut text = String::from("Usage: stock_tracker [OPTIONS] COMMAND [ARGS]\n\nCommands:\n");
+    for spec in COMMANDS.iter().filter(|spec| !spec.is_hidden()) {
+        text.push_str(&format!("  {:<44}{}\n", spec.usage(), spec.description));
+    }
+    text.push_str("\nOptions:\n");
+    for flag in GLOBAL_FLAGS {
+        text.push_str(&format!("  {:<44}{}\n", flag.usage(), flag.description));
+    }
+    text.push_str("\nEnter \"help COMMAND\" for more about a command.\n");
+    text
+}
+
+impl FlagSpec {
+
+    /// Generates the usage text of the flag, e.g. "--profile NAME"
+    pub fn usage(&self) -> String {
+        match self.kind {
+            FlagKind::Switch => format!("--{}", self.name),
+            _ => format!("--{} {}", self.name, self.value_name),
         }
     }
 }
 
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", match self{
-            // Special Commands
-            Command::Init                                   => "init",
-            Command::Console                                => "console",
-            Command::Exit                                   => "exit",
-            // State Management Commands
-            Command::StateC(StateCommand::Login)            => "login",
-            Command::StateC(StateCommand::Logout)           => "logout",
-            // User Management Commands
-            Command::UserC(UserCommand::Create)             => "create-user",
-            Command::UserC(UserCommand::Delete)             => "delete-user",
-            Command::UserC(UserCommand::Edit)               => "edit-user",
-            Command::UserC(UserCommand::List)               => "list-users",
-            // Stock Management Commands
-            Command::StockC(StockCommand::Create)           => "create-stock",
-            Command::StockC(StockCommand::Delete)           => "delete-stock",
-            Command::StockC(StockCommand::Edit)             => "edit-stock",
-            Command::StockC(StockCommand::List)             => "list-stocks",
-            // Portfolio Management Commands
-            Command::PortfolioC(PortfolioCommand::Buy)      => "buy-stock",
-            Command::PortfolioC(PortfolioCommand::List)     => "list-portfolio",
-        })
-    }
-}
\ No newline at end of file
+        write!(f, "{}", self.spec().name)
+    }
+}
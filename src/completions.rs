@@ -0,0 +1,291 @@
+//! #completions
+//!
+//! This holds the `Shell` type and the functions which generate shell completion scripts and the completions offered
+//! by them
+
+// std
+use std::str::FromStr;
+
+// internal crates
+use crate::command::*;
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::settings;
+
+/// The name the program is installed as, which the completion scripts complete and call
+const PROGRAM: &str = env!("CARGO_PKG_NAME");
+
+/// The `Shell` enum represents the shells completion scripts may be generated for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = ProjectError;
+
+    fn from_str(s: &str) -> Result<Shell, ProjectError> {
+        match s.to_lowercase().as_str() {
+            "bash"  => Ok(Shell::Bash),
+            "zsh"   => Ok(Shell::Zsh),
+            "fish"  => Ok(Shell::Fish),
+            _       => Err(InputParseError(String::from(s), String::from("Shell"))),
+        }
+    }
+}
+
+/// The `CompletionData` struct holds the names read from the configuration directory which arguments may be completed
+/// with
+#[derive(Debug, Default)]
+pub struct CompletionData {
+    pub usernames: Vec<String>,
+    pub tickers: Vec<String>,
+    pub profiles: Vec<String>,
+    /// The accounts of the logged in user
+    pub accounts: Vec<String>,
+    pub groups: Vec<String>,
+}
+
+/// The `script()` function generates the completion script for `shell`. The first word is completed from a list of
+/// every command and alias written into the script, while later words are completed by calling `__complete`.
+pub fn script(shell: Shell) -> String {
+    let words = first_words().join(" ");
+
+    match shell {
+        Shell::Bash => format!(r#"# bash completion for {program}, generated by "{program} completions bash"
+_{program}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local candidates
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        candidates="{words}"
+    else
+        candidates="$({program} __complete -- "${{COMP_WORDS[@]:1:COMP_CWORD-1}}" 2>/dev/null)"
+    fi
+    COMPREPLY=($(compgen -W "$candidates" -- "$cur"))
+}}
+complete -F _{program} {program}
+"#, program = PROGRAM, words = words),
+
+        Shell::Zsh => format!(r#"#compdef {program}
+# zsh completion for {program}, generated by "{program} completions zsh"
+_{program}() {{
+    local -a candidates
+    if (( CURRENT == 2 )); then
+        candidates=({words})
+    else
+        candidates=(${{(f)"$({program} __complete -- ${{words[2,CURRENT-1]}} 2>/dev/null)"}})
+    fi
+    compadd -a candidates
+}}
+if [ "$funcstack[1]" = "_{program}" ]; then
+    _{program} "$@"
+else
+    compdef _{program} {program}
+fi
+"#, program = PROGRAM, words = words),
+
+        Shell::Fish => {
+            let mut script = format!(r#"# fish completion for {program}, generated by "{program} completions fish"
+function __{program}_complete
+    set -l words (commandline -opc)
+    {program} __complete -- $words[2..-1] 2>/dev/null
+end
+function __{program}_first_word
+    test (count (commandline -opc)) -eq 1
+end
+complete -c {program} -f
+complete -c {program} -n 'not __{program}_first_word' -a '(__{program}_complete)'
+"#, program = PROGRAM);
+            for spec in COMMANDS.iter().filter(|spec| !spec.is_hidden()) {
+                for name in std::iter::once(&spec.name).chain(spec.aliases.iter()) {
+                    let word = name.split(' ').next().unwrap_or_default();
+                    let description = match Command::group(word) {
+                        Some(group) => format!("{} commands", group),
+                        None => String::from(spec.description),
+                    };
+                    let line = format!("complete -c {} -n '__{}_first_word' -a '{}' -d '{}'\n",
+                        PROGRAM, PROGRAM, word, description.replace('\'', "\\'"));
+                    if !script.contains(&line) {
+                        script.push_str(&line);
+                    }
+                }
+            }
+            for flag in GLOBAL_FLAGS {
+                script.push_str(&format!("complete -c {} -n '__{}_first_word' -a '--{}' -d '{}'\n",
+                    PROGRAM, PROGRAM, flag.name, flag.description));
+            }
+            script
+        },
+    }
+}
+
+/// Returns every word a command line may begin with: the first word of every command name and alias, and every global
+/// flag
+pub fn first_words() -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for spec in COMMANDS.iter().filter(|spec| !spec.is_hidden()) {
+        for name in std::iter::once(&spec.name).chain(spec.aliases.iter()) {
+            let word = String::from(name.split(' ').next().unwrap_or_default());
+            if !words.contains(&word) {
+                words.push(word);
+            }
+        }
+    }
+    for (_, aliases) in GROUPS {
+        words.extend(aliases.iter().map(|x| String::from(*x)));
+    }
+    words.extend(GLOBAL_FLAGS.iter().map(|flag| format!("--{}", flag.name)));
+    words
+}
+
+/// The `candidates()` function returns the completions of the word following `words`, which are the words already
+/// entered after the program name.
+pub fn candidates(words: &[String], data: &CompletionData) -> Vec<String> {
+    let mut spec: Option<&'static CommandSpec> = None;
+    let mut group: Option<&'static str> = None;
+    let mut args: Vec<&str> = Vec::new();
+    let mut pending_flag: Option<&'static FlagSpec> = None;
+
+    for word in words {
+        // The value of a flag
+        if pending_flag.take().is_some() {
+            continue
+        }
+        // A flag
+        if let Some(name) = word.strip_prefix("--") {
+            let flag = match spec {
+                Some(x) => x.flag(name),
+                None => GLOBAL_FLAGS.iter().find(|flag| flag.name == name),
+            };
+            pending_flag = flag.filter(|flag| flag.kind != FlagKind::Switch);
+            continue
+        }
+        // The command, possibly named by two words
+        if spec.is_none() {
+            match group.take() {
+                Some(x) => match CommandSpec::find(&format!("{} {}", x, word)) {
+                    Some(found) => spec = Some(found),
+                    None => return Vec::new(),
+                },
+                None => match Command::group(word) {
+                    Some(x) => group = Some(x),
+                    None => match CommandSpec::find(word) {
+                        Some(found) => spec = Some(found),
+                        None => return Vec::new(),
+                    },
+                },
+            }
+            continue
+        }
+        args.push(word);
+    }
+
+    if let Some(flag) = pending_flag {
+        return flag_values(flag, data)
+    }
+
+    let spec = match (spec, group) {
+        (Some(x), _) => x,
+        (None, Some(x)) => return subcommands(x),
+        (None, None) => return first_words(),
+    };
+
+    let mut candidates = match spec.arg(args.len()) {
+        Some(arg) => arg_values(arg.completion, args.last().copied(), data),
+        None => Vec::new(),
+    };
+    candidates.extend(spec.flags.iter().map(|flag| format!("--{}", flag.name)));
+    candidates
+}
+
+//
+// Assistive functions
+//
+
+/// Returns the second words of the commands of `group`
+fn subcommands(group: &str) -> Vec<String> {
+    let prefix = format!("{} ", group);
+    COMMANDS.iter()
+        .flat_map(|spec| std::iter::once(&spec.name).chain(spec.aliases.iter()))
+        .filter_map(|name| name.strip_prefix(&prefix))
+        .map(String::from)
+        .collect()
+}
+
+/// Returns the values an argument of kind `completion` may take. `previous` is the argument before it, if any.
+fn arg_values(completion: Completion, previous: Option<&str>, data: &CompletionData) -> Vec<String> {
+    let strings = |values: &[&str]| values.iter().map(|x| String::from(*x)).collect();
+
+    match completion {
+        Completion::Nothing         => Vec::new(),
+        Completion::Command         => first_words().into_iter().filter(|x| !x.starts_with("--")).collect(),
+        Completion::Subcommand      => previous.and_then(Command::group).map(subcommands).unwrap_or_default(),
+        Completion::Username        => data.usernames.clone(),
+        Completion::Ticker          => data.tickers.clone(),
+        Completion::Profile         => data.profiles.clone(),
+        Completion::Account         => data.accounts.clone(),
+        Completion::Group           => data.groups.clone(),
+        Completion::Setting         => strings(&settings::KEYS),
+        Completion::SettingValue    => strings(settings::choices(previous.unwrap_or_default())),
+        Completion::UserProperty    => strings(&[
+            "username", "first-name", "last-name", "middle-initial", "email", "base-currency", "risk-tolerance",
+            "tax-jurisdiction",
+        ]),
+        Completion::StockProperty   => strings(&["ticker", "company-name", "value"]),
+        Completion::Role            => strings(&["admin", "member", "read-only"]),
+        Completion::Shell           => strings(&["bash", "zsh", "fish"]),
+    }
+}
+
+/// Returns the values the flag may take
+fn flag_values(flag: &FlagSpec, data: &CompletionData) -> Vec<String> {
+    match flag.name {
+        "profile" => data.profiles.clone(),
+        "user" => data.usernames.clone(),
+        name => settings::choices(name).iter().map(|x| String::from(*x)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(line: &str) -> Vec<String> {
+        let data = CompletionData {
+            usernames: vec![String::from("alice")],
+            tickers: vec![String::from("FOO")],
+            profiles: vec![String::from("default"), String::from("paper")],
+            accounts: vec![String::from("ira"), String::from("main")],
+            groups: vec![String::from("home")],
+        };
+        candidates(&line.split_whitespace().map(String::from).collect::<Vec<_>>(), &data)
+    }
+
+    #[test]
+    fn arguments_complete_from_data() {
+        assert_eq!(complete("bs"), vec!["FOO", "--account"]);
+        assert_eq!(complete("account use"), vec!["ira", "main"]);
+        assert_eq!(complete("grp add home"), vec!["alice"]);
+        assert_eq!(complete("gp"), vec!["home"]);
+        assert_eq!(complete("--profile paper login"), vec!["alice", "--read-only"]);
+        assert_eq!(complete("--profile"), vec!["default", "paper"]);
+        assert_eq!(complete("pf switch"), vec!["default", "paper"]);
+        assert_eq!(complete("es FOO"), vec!["ticker", "company-name", "value"]);
+        assert_eq!(complete("config set lot-matching"), vec!["fifo", "lifo", "average-cost"]);
+        assert_eq!(complete("check"), vec!["--repair"]);
+        assert_eq!(complete("login alice"), vec!["--read-only"]);
+        assert!(complete("show-user alice").is_empty());
+    }
+
+    #[test]
+    fn commands_and_aliases_complete() {
+        let words = complete("");
+        for word in ["init", "cs", "profile", "pf", "--profile"] {
+            assert!(words.contains(&String::from(word)), "{} missing", word);
+        }
+        assert!(!words.contains(&String::from("__complete")));
+        assert_eq!(complete("profile"), vec!["list", "ls", "create", "new", "switch", "use", "delete", "rm"]);
+    }
+}
@@ -0,0 +1,135 @@
+//! #console
+//!
+//! This holds the `ConsoleHelper` type, which gives console mode tab completion, and the functions which keep the
+//! console history in the configuration directory
+
+// std
+use std::fs;
+
+// external crates
+use rustyline::completion::Completer;
+use rustyline::history::DefaultHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+
+// internal crates
+use crate::completions::{self, CompletionData};
+use crate::crypto;
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::tokenize;
+use crate::Config;
+
+/// The number of commands kept in the console history
+pub const HISTORY_SIZE: usize = 500;
+
+/// The `ConsoleHelper` struct completes console input from the same candidates as the shell completion scripts
+#[derive(Helper, Hinter, Highlighter, Validator)]
+pub struct ConsoleHelper {
+    /// The usernames, tickers and profiles arguments are completed with
+    pub data: CompletionData,
+}
+
+impl Completer for ConsoleHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete_line(&line[..pos], &self.data))
+    }
+}
+
+/// The `complete_line()` function completes the last word of `line`, returning where that word starts and the
+/// candidates which begin with it.
+pub fn complete_line(line: &str, data: &CompletionData) -> (usize, Vec<String>) {
+    let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let partial = &line[start..];
+    // Nothing is completed within quotes
+    let words = match tokenize::split(&line[..start]) {
+        Ok(x) => x,
+        Err(_) => return (start, Vec::new()),
+    };
+
+    let mut candidates: Vec<String> = completions::candidates(&words, data).into_iter()
+        .filter(|x| x.starts_with(partial))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+/// Returns a line editor with the history of `config`'s configuration directory loaded
+pub fn editor(config: &Config) -> Result<Editor<ConsoleHelper, DefaultHistory>, ProjectError> {
+    let editor_config = rustyline::Config::builder()
+        .max_history_size(HISTORY_SIZE).map_err(|_| ImpossibleStateError)?
+        .auto_add_history(false)
+        .build();
+    let mut editor = Editor::with_config(editor_config).map_err(|_| InvalidInputError)?;
+
+    for line in read_history(config)? {
+        editor.add_history_entry(line).map_err(|_| InvalidInputError)?;
+    }
+    Ok(editor)
+}
+
+/// Reads the commands saved by earlier console sessions, oldest first
+pub fn read_history(config: &Config) -> Result<Vec<String>, ProjectError> {
+    let path = config.history_path();
+    if !path.exists() {
+        return Ok(Vec::new())
+    }
+
+    let contents = fs::read(&path).map_err(|_| IOHistoryOpenError(path.clone()))?;
+    let contents = crypto::open(&path, contents)?;
+    let contents = String::from_utf8(contents).map_err(|_| IOHistoryOpenError(path.clone()))?;
+    Ok(contents.lines().map(String::from).collect())
+}
+
+/// Saves the history of `editor` for later console sessions, encrypted like the other data files
+pub fn write_history(config: &Config, editor: &Editor<ConsoleHelper, DefaultHistory>) -> Result<(), ProjectError> {
+    let path = config.history_path();
+    let lines: Vec<&str> = editor.history().iter().map(String::as_str).collect();
+
+    let contents = crypto::seal(&path, lines.join("\n").into_bytes())?;
+    fs::write(&path, contents).map_err(|_| IOHistoryWriteError(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> CompletionData {
+        CompletionData {
+            usernames: vec![String::from("alice"), String::from("bob")],
+            tickers: vec![String::from("FOO")],
+            profiles: Vec::new(),
+            accounts: Vec::new(),
+            groups: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn completes_the_word_at_the_cursor() {
+        let (start, candidates) = complete_line("buy-stock F", &data());
+        assert_eq!(start, 10);
+        assert_eq!(candidates, vec!["FOO"]);
+
+        let (start, candidates) = complete_line("login ", &data());
+        assert_eq!(start, 6);
+        assert_eq!(candidates, vec!["--read-only", "alice", "bob"]);
+
+        assert!(complete_line("create-st", &data()).1.contains(&String::from("create-stock")));
+        assert!(complete_line("edit-stock FOO company-name \"Foo ", &data()).1.is_empty());
+    }
+
+    #[test]
+    fn history_persists_between_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+
+        let mut editor = editor(&config).unwrap();
+        editor.add_history_entry("list-stocks").unwrap();
+        editor.add_history_entry("login bob").unwrap();
+        write_history(&config, &editor).unwrap();
+
+        assert_eq!(read_history(&config).unwrap(), vec!["list-stocks", "login bob"]);
+    }
+}
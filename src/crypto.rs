@@ -0,0 +1,441 @@
+//! #crypto
+//!
+//! This holds the functions which encrypt the data files of a configuration directory at rest with a passphrase
+
+// std
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// external crates
+use argon2::{Algorithm, Argon2, Params, Version}; // For memory-hard key derivation
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng}; // For authenticated encryption
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Serialize, Deserialize}; // So we may write the header to a file
+
+// internal crates
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::{temporary_path, Config};
+
+/// Every encrypted file begins with these bytes so that it may be told apart from a plain JSON file
+const MAGIC: &[u8] = b"RSTENC01";
+/// The length of an XChaCha20-Poly1305 nonce in bytes
+const NONCE_LENGTH: usize = 24;
+/// The plaintext sealed into the header, used to check a passphrase before any data file is touched
+const CHECK_PLAINTEXT: &[u8] = b"rust_stock_tracker";
+
+/// The name of the header file within a configuration directory
+pub const ENCRYPTION_FILE_NAME: &str = "Encryption.JSON";
+/// The environment variable which, when set, is used as the passphrase instead of prompting for it
+pub const PASSPHRASE_VARIABLE: &str = "RUST_STOCK_TRACKER_PASSPHRASE";
+/// The environment variable which, when set, is used as the new passphrase by `change-passphrase`
+pub const NEW_PASSPHRASE_VARIABLE: &str = "RUST_STOCK_TRACKER_NEW_PASSPHRASE";
+
+/// The keys of every configuration directory unlocked by this process. A directory stays unlocked until the process
+/// exits, so a passphrase is asked for at most once per session.
+static KEYS: Mutex<BTreeMap<PathBuf, Key>> = Mutex::new(BTreeMap::new());
+
+/// The `Header` records how the key of an encrypted configuration directory is derived from its passphrase. Its
+/// presence in the configuration directory is what marks the directory as encrypted.
+#[derive(Serialize, Deserialize, Debug)]
+struct Header {
+    /// The random salt given to Argon2id
+    salt: Vec<u8>,
+    /// Argon2id memory cost in KiB
+    memory_cost: u32,
+    /// Argon2id number of passes
+    time_cost: u32,
+    /// Argon2id degree of parallelism
+    parallelism: u32,
+    /// `CHECK_PLAINTEXT` sealed with the derived key
+    check: Vec<u8>,
+}
+
+impl Header {
+    fn read(config: &Config) -> Result<Header, ProjectError> {
+        let path = config.encryption_path();
+        let bytes = fs::read(&path).map_err(|_| IOEncryptionOpenError(path.clone()))?;
+        serde_json::from_slice(&bytes).map_err(|_| DeserializeJSONError(path))
+    }
+
+    /// Derives the key for `passphrase` using the parameters of this `Header`
+    fn derive_key(&self, passphrase: &str) -> Result<Key, ProjectError> {
+        let params = Params::new(self.memory_cost, self.time_cost, self.parallelism, Some(32))
+            .map_err(|_| EncryptionError)?;
+        let mut key = Key::default();
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &self.salt, &mut key)
+            .map_err(|_| EncryptionError)?;
+        Ok(key)
+    }
+
+    /// Creates a `Header` with a fresh salt for `passphrase`, returning it along with the derived key
+    fn generate(passphrase: &str) -> Result<(Header, Key), ProjectError> {
+        if passphrase.is_empty() {
+            return Err(InvalidInputError)
+        }
+
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+
+        let mut header = Header {
+            salt,
+            memory_cost: Params::DEFAULT_M_COST,
+            time_cost: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+            check: Vec::new(),
+        };
+        let key = header.derive_key(passphrase)?;
+        header.check = seal_with(&key, CHECK_PLAINTEXT)?;
+
+        Ok((header, key))
+    }
+}
+
+/// Returns `true` if the configuration directory of `config` is encrypted
+pub fn is_encrypted(config: &Config) -> bool {
+    config.encryption_path().exists()
+}
+
+/// The `unlock()` function makes the data files of an encrypted configuration directory readable for the rest of the
+/// process. The passphrase is taken from `RUST_STOCK_TRACKER_PASSPHRASE` if set, otherwise it is prompted for. This
+/// does nothing if the directory is not encrypted or is already unlocked. A rewrite of the data files interrupted by
+/// an earlier process is finished or undone first.
+pub fn unlock(config: &Config) -> Result<(), ProjectError> {
+    recover(config)?;
+    if !is_encrypted(config) || key_for(&config.configuration_directory).is_some() {
+        return Ok(())
+    }
+
+    let passphrase = match env::var(PASSPHRASE_VARIABLE) {
+        Ok(x) => x,
+        Err(_) => read_passphrase(config, "Passphrase: ")?,
+    };
+
+    unlock_with(config, &passphrase)
+}
+
+/// Like `unlock()`, but with the passphrase provided. Returns a `WrongPassphraseError` if it is not the passphrase of
+/// the configuration directory.
+pub fn unlock_with(config: &Config, passphrase: &str) -> Result<(), ProjectError> {
+    let header = Header::read(config)?;
+    let key = header.derive_key(passphrase)?;
+
+    if open_with(&key, &header.check).ok().as_deref() != Some(CHECK_PLAINTEXT) {
+        return Err(WrongPassphraseError)
+    }
+
+    KEYS.lock().unwrap().insert(config.configuration_directory.clone(), key);
+    Ok(())
+}
+
+/// The `enable()` function encrypts every data file of the configuration directory with `passphrase`. The directory
+/// must not already be encrypted.
+pub fn enable(config: &Config, passphrase: &str) -> Result<(), ProjectError> {
+    if is_encrypted(config) {
+        return Err(AlreadyEncryptedError)
+    }
+
+    let (header, key) = Header::generate(passphrase)?;
+    rewrite_data_files(config, Some((&header, &key)), |from, to| fs::rename(from, to))?;
+    KEYS.lock().unwrap().insert(config.configuration_directory.clone(), key);
+    Ok(())
+}
+
+/// The `disable()` function decrypts every data file of an unlocked configuration directory and removes its header.
+pub fn disable(config: &Config) -> Result<(), ProjectError> {
+    require_unlocked(config)?;
+
+    rewrite_data_files(config, None, |from, to| fs::rename(from, to))?;
+    KEYS.lock().unwrap().remove(&config.configuration_directory);
+    Ok(())
+}
+
+/// The `change_passphrase()` function re-encrypts every data file of an unlocked configuration directory with a key
+/// derived from `passphrase`.
+pub fn change_passphrase(config: &Config, passphrase: &str) -> Result<(), ProjectError> {
+    require_unlocked(config)?;
+
+    let (header, key) = Header::generate(passphrase)?;
+    rewrite_data_files(config, Some((&header, &key)), |from, to| fs::rename(from, to))?;
+    KEYS.lock().unwrap().insert(config.configuration_directory.clone(), key);
+    Ok(())
+}
+
+/// The `seal()` function prepares the contents of a data file at `path` to be written. If the configuration directory
+/// holding `path` is encrypted, the contents are encrypted, otherwise they are returned as they are.
+pub fn seal(path: &Path, plaintext: Vec<u8>) -> Result<Vec<u8>, ProjectError> {
+    seal_in(&directory_of(path), plaintext)
+}
+
+/// Like `seal()`, for a file kept in a subdirectory of the configuration `directory`, such as an export
+pub fn seal_in(directory: &Path, plaintext: Vec<u8>) -> Result<Vec<u8>, ProjectError> {
+    match key_for(directory) {
+        Some(key) => seal_file(&key, &plaintext),
+        // Never silently write plaintext into an encrypted directory
+        None if directory.join(ENCRYPTION_FILE_NAME).exists() => Err(EncryptionLockedError(PathBuf::from(directory))),
+        None => Ok(plaintext),
+    }
+}
+
+/// The `open()` function reverses `seal()` for the contents of a data file read from `path`. Plain files are returned
+/// as they are, unless the configuration directory is encrypted: a plain file there has been replaced by someone
+/// without the passphrase, so it is refused like any other tampering.
+pub fn open(path: &Path, contents: Vec<u8>) -> Result<Vec<u8>, ProjectError> {
+    open_in(&directory_of(path), path, contents)
+}
+
+/// Like `open()`, for a file at `path` kept in a subdirectory of the configuration `directory`, such as an export
+pub fn open_in(directory: &Path, path: &Path, contents: Vec<u8>) -> Result<Vec<u8>, ProjectError> {
+    if !contents.starts_with(MAGIC) {
+        if directory.join(ENCRYPTION_FILE_NAME).exists() {
+            return Err(DecryptionError(PathBuf::from(path)))
+        }
+        return Ok(contents)
+    }
+
+    let key = key_for(directory).ok_or_else(|| EncryptionLockedError(PathBuf::from(directory)))?;
+    open_with(&key, &contents[MAGIC.len()..]).map_err(|_| DecryptionError(PathBuf::from(path)))
+}
+
+//
+// Assistive functions
+//
+
+/// Prompts for a passphrase through the `Config`'s prompt without echoing it
+pub fn read_passphrase(config: &Config, prompt: &str) -> Result<String, ProjectError> {
+    config.prompt.borrow_mut().ask_secret(prompt)
+}
+
+/// Prompts for a new passphrase twice, returning a `PassphraseMismatchError` if the two differ
+pub fn read_new_passphrase(config: &Config) -> Result<String, ProjectError> {
+    let passphrase = read_passphrase(config, "New passphrase: ")?;
+    if read_passphrase(config, "Confirm passphrase: ")? != passphrase {
+        return Err(PassphraseMismatchError)
+    }
+    Ok(passphrase)
+}
+
+fn require_unlocked(config: &Config) -> Result<(), ProjectError> {
+    if !is_encrypted(config) {
+        return Err(NotEncryptedError)
+    }
+    match key_for(&config.configuration_directory) {
+        Some(_) => Ok(()),
+        None => Err(EncryptionLockedError(config.configuration_directory.clone())),
+    }
+}
+
+/// Reads every existing data file and writes it back sealed with the key of `sealing`, or as plaintext if it is
+/// `None`, then commits the header of that key in place of the old one. The new header, each new file and a backup of
+/// each original are all staged beside the originals before any is replaced with `rename`, and the header is renamed
+/// into place last, so until then the old header still opens every file. Should a step fail, `recover()` puts the
+/// originals back; should the process stop instead, the next `unlock()` does.
+fn rewrite_data_files<R>(config: &Config, sealing: Option<(&Header, &Key)>, mut rename: R) -> Result<(), ProjectError>
+    where R: FnMut(&Path, &Path) -> io::Result<()> {
+    let mut staged = Vec::new();
+    for path in config.data_file_paths() {
+        if path.exists() {
+            let bytes = fs::read(&path).map_err(|_| IOHashMapOpenError(path.clone()))?;
+            // Exports written in the clear by earlier versions are sealed now rather than refused
+            let plaintext = if directory_of(&path) == config.configuration_directory || bytes.starts_with(MAGIC) {
+                open_in(&config.configuration_directory, &path, bytes.clone())?
+            } else {
+                bytes.clone()
+            };
+            let contents = match sealing {
+                Some((_, key)) => seal_file(key, &plaintext)?,
+                None => plaintext,
+            };
+            staged.push((path, bytes, contents));
+        }
+    }
+    // A header of `null` records that the data files were decrypted, and is removed once committed
+    let serialized_header = serde_json::to_vec(&sealing.map(|(header, _)| header)).map_err(|_| SerializeJSONError)?;
+
+    let header_path = config.encryption_path();
+    let result = fs::write(temporary_path(&header_path), serialized_header)
+        .map_err(|_| IOEncryptionWriteError(header_path.clone()))
+        .and_then(|_| {
+            for (path, original, contents) in &staged {
+                fs::write(backup_path(path), original).map_err(|_| IOHashMapWriteError(path.clone()))?;
+                fs::write(temporary_path(path), contents).map_err(|_| IOHashMapWriteError(path.clone()))?;
+            }
+            for (path, _, _) in &staged {
+                rename(&temporary_path(path), path).map_err(|_| IOHashMapWriteError(path.clone()))?;
+            }
+            rename(&temporary_path(&header_path), &header_path).map_err(|_| IOEncryptionWriteError(header_path.clone()))
+        });
+
+    recover(config)?;
+    result
+}
+
+/// Finishes a rewrite of the data files by `rewrite_data_files()`, or undoes it if its header was never committed. An
+/// uncommitted rewrite has its originals restored from their backups; a committed one only has its leftover files
+/// removed, along with the header if it records a decryption. This does nothing if no rewrite was under way.
+fn recover(config: &Config) -> Result<(), ProjectError> {
+    let header_path = config.encryption_path();
+    let staged_header = temporary_path(&header_path);
+    let committed = !staged_header.exists();
+
+    for path in config.data_file_paths() {
+        let backup = backup_path(&path);
+        if !committed && backup.exists() {
+            fs::rename(&backup, &path).map_err(|_| IOHashMapWriteError(path.clone()))?;
+        }
+        remove_if_exists(&temporary_path(&path)).map_err(|_| IOHashMapWriteError(path.clone()))?;
+        remove_if_exists(&backup).map_err(|_| IOHashMapWriteError(path.clone()))?;
+    }
+
+    if committed && fs::read(&header_path).is_ok_and(|x| x == b"null") {
+        remove_if_exists(&header_path).map_err(|_| IOEncryptionWriteError(header_path.clone()))?;
+    }
+    remove_if_exists(&staged_header).map_err(|_| IOEncryptionWriteError(header_path))
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(x) if x.kind() == io::ErrorKind::NotFound => Ok(()),
+        x => x,
+    }
+}
+
+/// The location the original of a data file at `path` is kept while it is being replaced
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|x| x.to_os_string()).unwrap_or_default();
+    name.push(".old");
+    path.with_file_name(name)
+}
+
+fn directory_of(path: &Path) -> PathBuf {
+    path.parent().map(PathBuf::from).unwrap_or_default()
+}
+
+fn key_for(directory: &Path) -> Option<Key> {
+    KEYS.lock().unwrap().get(directory).cloned()
+}
+
+/// Encrypts the contents of a data file, returning `MAGIC` followed by the output of `seal_with()`
+fn seal_file(key: &Key, plaintext: &[u8]) -> Result<Vec<u8>, ProjectError> {
+    let mut sealed = MAGIC.to_vec();
+    sealed.extend(seal_with(key, plaintext)?);
+    Ok(sealed)
+}
+
+/// Encrypts `plaintext`, returning the random nonce followed by the ciphertext
+fn seal_with(key: &Key, plaintext: &[u8]) -> Result<Vec<u8>, ProjectError> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = XChaCha20Poly1305::new(key).encrypt(&nonce, plaintext).map_err(|_| EncryptionError)?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypts the output of `seal_with()`, failing if the key is wrong or the data was tampered with
+fn open_with(key: &Key, sealed: &[u8]) -> Result<Vec<u8>, chacha20poly1305::aead::Error> {
+    if sealed.len() < NONCE_LENGTH {
+        return Err(chacha20poly1305::aead::Error)
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+    XChaCha20Poly1305::new(key).decrypt(XNonce::from_slice(nonce), ciphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_files_round_trip_and_reject_wrong_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+        let path = config.user_map_path();
+        fs::write(&path, b"{}").unwrap();
+
+        enable(&config, "hunter2").unwrap();
+        let sealed = fs::read(&path).unwrap();
+        assert!(sealed.starts_with(MAGIC));
+        assert_eq!(open(&path, sealed.clone()).unwrap(), b"{}");
+
+        // A fresh process would have no key cached
+        KEYS.lock().unwrap().remove(&config.configuration_directory);
+        assert!(matches!(open(&path, sealed.clone()), Err(EncryptionLockedError(_))));
+        assert!(matches!(unlock_with(&config, "hunter3"), Err(WrongPassphraseError)));
+        unlock_with(&config, "hunter2").unwrap();
+
+        // Plaintext swapped in for an encrypted file is refused
+        assert!(matches!(open(&path, b"{}".to_vec()), Err(DecryptionError(_))));
+
+        change_passphrase(&config, "hunter3").unwrap();
+        KEYS.lock().unwrap().remove(&config.configuration_directory);
+        unlock_with(&config, "hunter3").unwrap();
+        assert!(!temporary_path(&path).exists());
+
+        disable(&config).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"{}");
+        assert!(!is_encrypted(&config));
+    }
+
+    #[test]
+    fn interrupted_rewrites_leave_the_old_passphrase_working() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+        fs::write(config.user_map_path(), b"{}").unwrap();
+        fs::write(config.stock_map_path(), b"[]").unwrap();
+        enable(&config, "hunter2").unwrap();
+        let opens_with_old_passphrase = || {
+            KEYS.lock().unwrap().remove(&config.configuration_directory);
+            recover(&config).unwrap();
+            unlock_with(&config, "hunter2").unwrap();
+            let read = |path: PathBuf| open(&path, fs::read(&path).unwrap()).unwrap();
+            assert_eq!((read(config.user_map_path()), read(config.stock_map_path())), (b"{}".to_vec(), b"[]".to_vec()));
+            assert!(fs::read_dir(dir.path()).unwrap().all(|x| x.unwrap().path().extension().unwrap() == "JSON"));
+        };
+
+        // The second rename fails, after the user map has been replaced
+        let (header, key) = Header::generate("hunter3").unwrap();
+        let mut renames = 0;
+        let result = rewrite_data_files(&config, Some((&header, &key)), |from, to| {
+            renames += 1;
+            if renames == 2 { return Err(io::Error::other("disk full")) }
+            fs::rename(from, to)
+        });
+        assert!(matches!(result, Err(IOHashMapWriteError(_))));
+        opens_with_old_passphrase();
+
+        // The process stops after every data file has been replaced, before the header is
+        let header_path = config.encryption_path();
+        let stopped = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            rewrite_data_files(&config, None, |from, to| match to == header_path {
+                true => panic!("stopped"),
+                false => fs::rename(from, to),
+            })
+        }));
+        assert!(stopped.is_err());
+        opens_with_old_passphrase();
+    }
+
+    #[test]
+    fn exports_are_sealed_with_the_configuration_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+        fs::create_dir_all(config.exports_path()).unwrap();
+        let export = config.exports_path().join("alice-1.csv");
+        fs::write(&export, b"username\nalice\n").unwrap();
+
+        enable(&config, "hunter2").unwrap();
+        let sealed = fs::read(&export).unwrap();
+        assert!(sealed.starts_with(MAGIC));
+        assert_eq!(open_in(&config.configuration_directory, &export, sealed).unwrap(), b"username\nalice\n");
+        assert!(seal_in(&config.configuration_directory, b"x".to_vec()).unwrap().starts_with(MAGIC));
+
+        disable(&config).unwrap();
+        assert_eq!(fs::read(&export).unwrap(), b"username\nalice\n");
+    }
+}
@@ -0,0 +1,478 @@
+//! #dashboard
+//!
+//! This holds the `Dashboard` type, a full-screen view of the stock map and the logged-in user's portfolio, and the
+//! function which runs it in the terminal
+
+// std
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::io::IsTerminal;
+
+// external crates
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+
+// internal crates
+use crate::account::DEFAULT_ACCOUNT;
+use crate::args::FlagValue;
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::output::HoldingRecord;
+use crate::settings::Settings;
+use crate::stock::Stock;
+use crate::user::User;
+use crate::{Config, State};
+
+/// The keys the dashboard responds to, shown at the bottom of the screen
+const KEYS_HELP: &str = "↑/↓ select  Tab switch pane  b buy  v set value  n set name  q quit";
+
+/// The `Pane` enum represents the lists a ticker may be selected from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pane {
+    Stocks,
+    Portfolio,
+}
+
+/// The `Input` enum represents what text being typed into the dashboard is for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    /// A quantity of the selected stock to buy
+    Buy,
+    /// A new value for the selected stock
+    Value,
+    /// A new company name for the selected stock
+    Name,
+}
+
+/// The `Action` enum represents what the dashboard asks to be done in response to a key
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Quit,
+    /// Run the command line `words`, showing `done` if it succeeds
+    Run { words: Vec<String>, done: String },
+}
+
+/// The `Dashboard` struct represents what the dashboard shows and which row is selected
+#[derive(Debug)]
+pub struct Dashboard {
+    /// The logged-in user, if any
+    username: Option<String>,
+    /// The account of the logged-in user which is shown and bought into
+    account: Option<String>,
+    /// Every stock, sorted by ticker
+    stocks: Vec<Stock>,
+    /// The holdings of the account, sorted by ticker
+    holdings: Vec<HoldingRecord>,
+    settings: Settings,
+    pane: Pane,
+    /// The selected row of each pane
+    selected_stock: usize,
+    selected_holding: usize,
+    /// The text being typed, if any, and what it is for
+    input: Option<(Input, String)>,
+    /// The message shown above the keys, e.g. the outcome of the last action
+    pub status: String,
+}
+
+impl Dashboard {
+    pub fn new(username: Option<String>, stocks: Vec<Stock>, holdings: Vec<HoldingRecord>, settings: Settings) -> Dashboard {
+        Dashboard {
+            username,
+            account: None,
+            stocks,
+            holdings,
+            settings,
+            pane: Pane::Stocks,
+            selected_stock: 0,
+            selected_holding: 0,
+            input: None,
+            status: String::new(),
+        }
+    }
+
+    /// Reads the stock map, user map and `State` of `config` into a new `Dashboard`
+    pub fn load(config: &Config) -> Result<Dashboard, ProjectError> {
+        let stock_map: HashMap<String, Stock> = crate::read_from_hashmap(&config.stock_map_path())?;
+        let user_map: HashMap<String, User> = crate::read_from_hashmap(&config.user_map_path())?;
+        let mut state = State::init(config)?;
+        let username = state.current_user.clone().filter(|x| user_map.contains_key(x));
+
+        let user = username.as_ref().and_then(|x| user_map.get(x));
+        let account = match user {
+            Some(x) => current_account(config, &mut state, x)?,
+            None => None,
+        };
+        let (account, holdings) = match (user, account) {
+            (Some(user), Some(account)) => {
+                let holdings = user.account(&account)?.holdings.iter()
+                    .collect::<BTreeMap<_, _>>()
+                    .into_values()
+                    .map(|x| HoldingRecord::new(user.username(), &account, x, stock_map.get(&x.stock.ticker)))
+                    .collect();
+                (Some(account), holdings)
+            },
+            _ => (None, Vec::new()),
+        };
+        let stocks = stock_map.into_iter().collect::<BTreeMap<_, _>>().into_values().collect();
+
+        let mut dashboard = Dashboard::new(username, stocks, holdings, config.settings.clone());
+        dashboard.account = account;
+        Ok(dashboard)
+    }
+
+    /// Reloads the data of `config`, keeping the selection where possible
+    pub fn reload(&mut self, config: &Config) -> Result<(), ProjectError> {
+        let fresh = Dashboard::load(config)?;
+        self.username = fresh.username;
+        self.account = fresh.account;
+        self.stocks = fresh.stocks;
+        self.holdings = fresh.holdings;
+        self.selected_stock = self.selected_stock.min(self.stocks.len().saturating_sub(1));
+        self.selected_holding = self.selected_holding.min(self.holdings.len().saturating_sub(1));
+        Ok(())
+    }
+
+    /// Returns the ticker selected in the focused pane, if any
+    pub fn selected_ticker(&self) -> Option<&str> {
+        match self.pane {
+            Pane::Stocks => self.stocks.get(self.selected_stock).map(|x| x.ticker.as_str()),
+            Pane::Portfolio => self.holdings.get(self.selected_holding).map(|x| x.ticker.as_str()),
+        }
+    }
+
+    /// The `handle_key()` method updates the dashboard for a key press, returning the `Action` it calls for, if any.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Some(Action::Quit)
+        }
+
+        // Typing
+        if let Some((input, text)) = &mut self.input {
+            match key.code {
+                KeyCode::Esc => {
+                    self.input = None;
+                    self.status = String::from("Cancelled.");
+                },
+                KeyCode::Enter => {
+                    let (input, text) = (*input, text.trim().to_string());
+                    self.input = None;
+                    let ticker = String::from(self.selected_ticker()?);
+                    return Some(match input {
+                        Input::Buy => Action::Run {
+                            done: format!("Bought {} shares of {}.", text, ticker),
+                            words: vec![String::from("buy-stock"), ticker, text],
+                        },
+                        Input::Value => Action::Run {
+                            done: format!("Set the value of {} to {}.", ticker, text),
+                            words: vec![String::from("edit-stock"), ticker, String::from("value"), text],
+                        },
+                        Input::Name => Action::Run {
+                            done: format!("Set the company name of {} to {}.", ticker, text),
+                            words: vec![String::from("edit-stock"), ticker, String::from("company-name"), text],
+                        },
+                    })
+                },
+                KeyCode::Backspace => { text.pop(); },
+                KeyCode::Char(c) => text.push(c),
+                _ => {},
+            }
+            return None
+        }
+
+        // Browsing
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.pane = match self.pane {
+                    Pane::Stocks => Pane::Portfolio,
+                    Pane::Portfolio => Pane::Stocks,
+                };
+            },
+            KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.select(1),
+            KeyCode::Char('b') if self.username.is_none() => self.status = String::from("Log in to buy stocks."),
+            KeyCode::Char(c @ ('b' | 'v' | 'n')) => {
+                if self.selected_ticker().is_none() {
+                    self.status = String::from("No stock selected.");
+                } else {
+                    let input = match c { 'b' => Input::Buy, 'v' => Input::Value, _ => Input::Name };
+                    self.input = Some((input, String::new()));
+                }
+            },
+            _ => {},
+        }
+        None
+    }
+
+    /// Moves the selection of the focused pane by `step` rows, staying within it
+    fn select(&mut self, step: isize) {
+        let (selected, len) = match self.pane {
+            Pane::Stocks => (&mut self.selected_stock, self.stocks.len()),
+            Pane::Portfolio => (&mut self.selected_holding, self.holdings.len()),
+        };
+        *selected = selected.saturating_add_signed(step).min(len.saturating_sub(1));
+    }
+
+    /// The `render()` method draws the dashboard: the stocks on the left, the portfolio and the details of the selected
+    /// ticker on the right, and the status and keys along the bottom.
+    pub fn render(&self, frame: &mut Frame<'_>) {
+        let [main, footer] = Layout::vertical([Constraint::Min(0), Constraint::Length(3)]).areas(frame.area());
+        let [left, right] = Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(main);
+        let [top_right, bottom_right] = Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(right);
+
+        // Stocks
+        let rows = self.stocks.iter().map(|stock| Row::new(vec![
+            Line::from(stock.ticker.clone()),
+            Line::from(stock.company_name.clone()),
+            Line::from(self.settings.format_money(stock.value)).right_aligned(),
+        ]));
+        let stocks = Table::new(rows, [Constraint::Length(8), Constraint::Min(10), Constraint::Length(12)])
+            .header(Row::new(vec!["Ticker", "Company", "Value"]).style(Style::new().add_modifier(Modifier::BOLD)))
+            .block(self.block("Stocks", Pane::Stocks))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state = TableState::default().with_selected(self.focused(Pane::Stocks, self.selected_stock));
+        frame.render_stateful_widget(stocks, left, &mut state);
+
+        // Portfolio
+        let title = match (&self.username, &self.account) {
+            (Some(x), Some(y)) => format!("Portfolio of {} ({})", x, y),
+            (Some(x), None) => format!("Portfolio of {}", x),
+            (None, _) => String::from("Portfolio (not logged in)"),
+        };
+        let rows = self.holdings.iter().map(|holding| Row::new(vec![
+            Line::from(holding.ticker.clone()),
+            Line::from(holding.quantity.to_string()).right_aligned(),
+            Line::from(self.settings.format_money(holding.market_value)).right_aligned(),
+            Line::from(self.settings.format_money(holding.gain)).right_aligned().style(gain_style(holding.gain)),
+        ]));
+        let portfolio = Table::new(rows, [Constraint::Length(8), Constraint::Length(8), Constraint::Length(12), Constraint::Length(12)])
+            .header(Row::new(vec!["Ticker", "Shares", "Value", "Gain/Loss"]).style(Style::new().add_modifier(Modifier::BOLD)))
+            .block(self.block(&title, Pane::Portfolio))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state = TableState::default().with_selected(self.focused(Pane::Portfolio, self.selected_holding));
+        frame.render_stateful_widget(portfolio, top_right, &mut state);
+
+        // Details
+        frame.render_widget(Paragraph::new(self.details()).block(Block::new().borders(Borders::ALL).title("Details")), bottom_right);
+
+        // Status, or the text being typed
+        let status = match &self.input {
+            Some((Input::Buy, text)) => format!("Shares to buy: {}_", text),
+            Some((Input::Value, text)) => format!("New value: {}_", text),
+            Some((Input::Name, text)) => format!("New company name: {}_", text),
+            None => self.status.clone(),
+        };
+        frame.render_widget(Paragraph::new(vec![Line::from(status), Line::from(KEYS_HELP)]).block(Block::new().borders(Borders::TOP)), footer);
+    }
+
+    /// Returns the lines describing the selected ticker
+    fn details(&self) -> Vec<Line<'static>> {
+        let ticker = match self.selected_ticker() {
+            Some(x) => x,
+            None => return vec![Line::from("No stock selected.")],
+        };
+
+        let mut lines = Vec::new();
+        if let Some(stock) = self.stocks.iter().find(|x| x.ticker == ticker) {
+            lines.push(Line::from(format!("{} ({})", stock.company_name, stock.ticker)));
+            lines.push(Line::from(format!("Value per share: {}", self.settings.format_money(stock.value))));
+        } else {
+            lines.push(Line::from(format!("{} is no longer in the stock list.", ticker)));
+        }
+        if let Some(holding) = self.holdings.iter().find(|x| x.ticker == ticker) {
+            lines.push(Line::from(format!("Shares held: {}", holding.quantity)));
+            lines.push(Line::from(format!("Market value: {}", self.settings.format_money(holding.market_value))));
+            lines.push(Line::from(format!("Cost basis: {}", self.settings.format_money(holding.cost_basis))));
+            lines.push(Line::from(format!("Gain/loss: {}", self.settings.format_money(holding.gain))).style(gain_style(holding.gain)));
+        }
+        lines
+    }
+
+    /// A bordered block titled `title`, highlighted if `pane` is focused
+    fn block(&self, title: &str, pane: Pane) -> Block<'static> {
+        let style = if self.pane == pane { Style::new().fg(Color::Cyan) } else { Style::new() };
+        Block::new().borders(Borders::ALL).border_style(style).title(String::from(title))
+    }
+
+    /// Only the focused pane shows its selection
+    fn focused(&self, pane: Pane, selected: usize) -> Option<usize> {
+        if self.pane == pane { Some(selected) } else { None }
+    }
+}
+
+/// Gains are green and losses red
+fn gain_style(gain: f64) -> Style {
+    if gain > 0.0 {
+        Style::new().fg(Color::Green)
+    } else if gain < 0.0 {
+        Style::new().fg(Color::Red)
+    } else {
+        Style::new()
+    }
+}
+
+/// The `run()` function shows the dashboard until it is quit. Actions are carried out by `dispatch` like any other
+/// command, so they are checked and journaled in the same way.
+pub fn run<F>(config: &Config, mut dispatch: F) -> Result<(), ProjectError> where
+    F: FnMut(&Config) -> Result<(), ProjectError>, {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(TerminalRequiredError)
+    }
+
+    let mut dashboard = Dashboard::load(config)?;
+    let mut terminal = ratatui::try_init().map_err(|_| TerminalError)?;
+    let result = event_loop(config, &mut terminal, &mut dashboard, &mut dispatch);
+    ratatui::restore();
+    result
+}
+
+fn event_loop<F>(config: &Config, terminal: &mut DefaultTerminal, dashboard: &mut Dashboard, dispatch: &mut F) -> Result<(), ProjectError> where
+    F: FnMut(&Config) -> Result<(), ProjectError>, {
+    loop {
+        terminal.draw(|frame| dashboard.render(frame)).map_err(|_| TerminalError)?;
+
+        let key = match event::read().map_err(|_| TerminalError)? {
+            Event::Key(x) if x.kind == KeyEventKind::Press => x,
+            _ => continue,
+        };
+
+        match dashboard.handle_key(key) {
+            Some(Action::Quit) => return Ok(()),
+            Some(Action::Run { words, done }) => {
+                // Notifications would be drawn over the dashboard, so the outcome is shown in the status line instead
+                let result = config.for_line(words).and_then(|mut action_config| {
+                    action_config.flags.insert(String::from("quiet"), FlagValue::Switch);
+                    dispatch(&action_config)
+                });
+                dashboard.status = match result {
+                    Ok(()) => done,
+                    Err(x) => x.to_string(),
+                };
+                dashboard.reload(config)?;
+            },
+            None => {},
+        }
+    }
+}
+
+// Assistive functions
+
+/// Returns the account of `user` to show: the one given by `--account`, else the one chosen by `account switch`, else
+/// the default account, else the first by name. A chosen account which no longer exists is cleared from `state`, so that
+/// the dashboard still opens after it is deleted or renamed.
+fn current_account(config: &Config, state: &mut State, user: &User) -> Result<Option<String>, ProjectError> {
+    if config.flag("account").is_some() {
+        return crate::account_name(config, state, user).map(Some)
+    }
+    match &state.current_account {
+        Some(x) if user.accounts.contains_key(x) => return Ok(Some(x.clone())),
+        Some(_) => {
+            state.current_account = None;
+            state.write(config)?;
+        },
+        None => {},
+    }
+    match user.accounts.contains_key(DEFAULT_ACCOUNT) {
+        true => Ok(Some(String::from(DEFAULT_ACCOUNT))),
+        false => Ok(user.accounts.keys().next().cloned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stock::StockUnit;
+    use crate::write_to_hashmap;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn dashboard() -> Dashboard {
+        let foo = Stock::new_from_details("FOO", "Foo Holdings Inc", 12.0).unwrap();
+        let bar = Stock::new_from_details("BAR", "Bar Co", 4.0).unwrap();
+        let mut bought = StockUnit::new(foo.clone(), 3).unwrap();
+        bought.cost = Some(30.0);
+        let holdings = vec![HoldingRecord::new("bob", "main", &bought, Some(&foo))];
+        Dashboard::new(Some(String::from("bob")), vec![bar, foo], holdings, Settings::default())
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn screen(dashboard: &Dashboard) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 24)).unwrap();
+        terminal.draw(|frame| dashboard.render(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer.content().chunks(usize::from(buffer.area.width))
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn renders_stocks_portfolio_and_details() {
+        let mut dashboard = dashboard();
+        let rendered = screen(&dashboard);
+        assert!(rendered.contains("Portfolio of bob"));
+        assert!(rendered.contains("Foo Holdings Inc"));
+        assert!(rendered.contains("Bar Co (BAR)"));
+
+        dashboard.handle_key(key(KeyCode::Down));
+        let rendered = screen(&dashboard);
+        assert!(rendered.contains("Foo Holdings Inc (FOO)"));
+        assert!(rendered.contains("Gain/loss: $6.00"));
+    }
+
+    #[test]
+    fn keys_select_and_ask_for_actions() {
+        let mut dashboard = dashboard();
+        dashboard.handle_key(key(KeyCode::Tab));
+        assert_eq!(dashboard.selected_ticker(), Some("FOO"));
+
+        for code in [KeyCode::Char('b'), KeyCode::Char('2'), KeyCode::Char('x'), KeyCode::Backspace] {
+            assert_eq!(dashboard.handle_key(key(code)), None);
+        }
+        assert!(screen(&dashboard).contains("Shares to buy: 2_"));
+        assert_eq!(dashboard.handle_key(key(KeyCode::Enter)), Some(Action::Run {
+            words: vec![String::from("buy-stock"), String::from("FOO"), String::from("2")],
+            done: String::from("Bought 2 shares of FOO."),
+        }));
+
+        dashboard.handle_key(key(KeyCode::Char('v')));
+        dashboard.handle_key(key(KeyCode::Esc));
+        assert_eq!(dashboard.status, "Cancelled.");
+        assert_eq!(dashboard.handle_key(key(KeyCode::Char('q'))), Some(Action::Quit));
+    }
+
+    #[test]
+    fn stale_accounts_fall_back_to_the_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+        let foo = Stock::new_from_details("FOO", "Foo Holdings Inc", 12.0).unwrap();
+        write_to_hashmap(&config.stock_map_path(), &HashMap::from([(String::from("FOO"), foo.clone())])).unwrap();
+        let mut bob = User::new_from_username("bob").unwrap();
+        bob.add_stock(DEFAULT_ACCOUNT, &foo, 2).unwrap();
+        bob.create_account("ira", 0.0).unwrap();
+        write_to_hashmap(&config.user_map_path(), &HashMap::from([(String::from("bob"), bob.clone())])).unwrap();
+        let mut state = State::init(&config).unwrap();
+        state.set_user(&config, "bob").unwrap();
+        state.current_account = Some(String::from("college"));
+        state.write(&config).unwrap();
+
+        let dashboard = Dashboard::load(&config).unwrap();
+        assert_eq!(dashboard.account.as_deref(), Some(DEFAULT_ACCOUNT));
+        assert_eq!(dashboard.holdings.len(), 1);
+        assert_eq!(State::init(&config).unwrap().current_account, None);
+
+        // Without a default account, the first by name is shown
+        bob.accounts.remove(DEFAULT_ACCOUNT);
+        write_to_hashmap(&config.user_map_path(), &HashMap::from([(String::from("bob"), bob)])).unwrap();
+        let dashboard = Dashboard::load(&config).unwrap();
+        assert_eq!(dashboard.account.as_deref(), Some("ira"));
+        assert!(dashboard.holdings.is_empty());
+    }
+}
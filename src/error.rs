@@ -5,8 +5,10 @@
 use std::path::PathBuf;
 use thiserror::Error; // For more structured definition of errors
 
-/// The `ProjectError` enum represents the variants of `Error`s expected in `stock_tracker`
+use crate::validate::Field;
 
+/// The `ProjectError` enum represents the variants of `Error`s expected in `stock_tracker`
+#[allow(clippy::enum_variant_names)]
 #[derive(Error, Debug)]
 pub enum ProjectError {
     #[error("Read from HashMap file {} unsuccessful.", .0.display())]
@@ -17,10 +19,42 @@ pub enum ProjectError {
     IOStateOpenError(PathBuf),
     #[error("Write to State file at {} unsuccessful.", .0.display())]
     IOStateWriteError(PathBuf),
+    #[error("Read from Journal file {} unsuccessful.", .0.display())]
+    IOJournalOpenError(PathBuf),
+    #[error("Write to Journal file at {} unsuccessful.", .0.display())]
+    IOJournalWriteError(PathBuf),
+    #[error("Read from audit log {} unsuccessful.", .0.display())]
+    IOAuditOpenError(PathBuf),
+    #[error("Write to audit log at {} unsuccessful.", .0.display())]
+    IOAuditWriteError(PathBuf),
+    #[error("Write to export file at {} unsuccessful.", .0.display())]
+    IOExportWriteError(PathBuf),
+    #[error("Read from console history file {} unsuccessful.", .0.display())]
+    IOHistoryOpenError(PathBuf),
+    #[error("Write to console history file at {} unsuccessful.", .0.display())]
+    IOHistoryWriteError(PathBuf),
+    #[error("Read from encryption header {} unsuccessful.", .0.display())]
+    IOEncryptionOpenError(PathBuf),
+    #[error("Write to encryption header at {} unsuccessful.", .0.display())]
+    IOEncryptionWriteError(PathBuf),
+    #[error("Read from Profiles file {} unsuccessful.", .0.display())]
+    IOProfilesOpenError(PathBuf),
+    #[error("Write to Profiles file at {} unsuccessful.", .0.display())]
+    IOProfilesWriteError(PathBuf),
+    #[error("Read from Settings file {} unsuccessful.", .0.display())]
+    IOSettingsOpenError(PathBuf),
+    #[error("Write to Settings file at {} unsuccessful.", .0.display())]
+    IOSettingsWriteError(PathBuf),
     #[error("Serialization unsuccessful.")]
     SerializeJSONError,
     #[error("Deserialization of JSON file {} unsuccessful.", .0.display())]
     DeserializeJSONError(PathBuf),
+    #[error("Serialization of settings unsuccessful.")]
+    SerializeTOMLError,
+    #[error("Deserialization of TOML file {} unsuccessful.", .0.display())]
+    DeserializeTOMLError(PathBuf),
+    #[error("Serialization of CSV output unsuccessful.")]
+    SerializeCSVError,
     #[error("Insertion to HashMap failed: key {0} is already occupied.")]
     HashMapInsertError(String),
     #[error("Remove from HashMap at key {0} unsuccessful.")]
@@ -35,10 +69,16 @@ pub enum ProjectError {
     InputParseError(String, String),
     #[error("Internal error parsing {0} as {1}, check that inputs are formatted properly.")]
     InternalParseError(String, String),
-    #[error("No command string provided.")]
+    #[error("No command string provided, run \"help\" for a list of commands.")]
     ConfigNoCommandError,
-    #[error("Too few arguments provided for {0}")]
+    #[error("Too few arguments provided, usage: {0}")]
     ConfigArgumentsError(String),
+    #[error("Too many arguments provided, usage: {0}")]
+    ConfigExtraArgumentsError(String),
+    #[error("Option {0} not recognized.")]
+    ConfigUnknownFlagError(String),
+    #[error("Option {0} requires a value.")]
+    ConfigFlagValueError(String),
     #[error("Creation of directories to {} unsuccessful", .0.display())]
     ConfigCreateDirectoryError(PathBuf),
     #[error("Unexpected error: home directory not found. Consider specifying a configuration directory by setting \"RUST_STOCK_TRACKER_CONFIGURATION_DIRECTORY\"")]
@@ -49,8 +89,128 @@ pub enum ProjectError {
     StateInvalidUserError(String),
     #[error("Command attempted without logging in.")]
     StateNoUserError,
+    #[error("Permission denied: {0}.")]
+    PermissionDeniedError(String),
+    #[error("{0} changes data, which a read-only session may not do; log in without --read-only to make changes.")]
+    ReadOnlySessionError(String),
+    #[error("User {0} is the last administrator and must stay one while there are other users.")]
+    LastAdminError(String),
+    #[error("An archived user named {0} already exists, restore it first.")]
+    ArchivedUserExistsError(String),
+    #[error("The session of user {0} expired after a period of inactivity, log in again to continue.")]
+    SessionExpiredError(String),
+    #[error("Nothing to undo.")]
+    JournalUndoError,
+    #[error("Nothing to redo.")]
+    JournalRedoError,
+    #[error("Integrity check found {0} problem(s), run \"check --repair\" to fix them.")]
+    IntegrityCheckError(usize),
+    #[error("Configuration directory {} is encrypted and has not been unlocked.", .0.display())]
+    EncryptionLockedError(PathBuf),
+    #[error("Incorrect passphrase.")]
+    WrongPassphraseError,
+    #[error("Passphrases do not match.")]
+    PassphraseMismatchError,
+    #[error("Incorrect password for user {0}, {1} attempt(s) left before the user is locked out.")]
+    IncorrectPasswordError(String, u32),
+    #[error("User {0} is locked out after too many failed logins, try again in {1} second(s).")]
+    LoginLockedError(String, u64),
+    #[error("Passwords do not match.")]
+    PasswordMismatchError,
+    #[error("User {0} already has a password, use change-password to change it.")]
+    PasswordAlreadySetError(String),
+    #[error("User {0} has no password, use set-password to set one.")]
+    NoPasswordError(String),
+    #[error("Password hashing unsuccessful.")]
+    PasswordHashError,
+    #[error("Data is already encrypted.")]
+    AlreadyEncryptedError,
+    #[error("Data is not encrypted.")]
+    NotEncryptedError,
+    #[error("Encryption unsuccessful.")]
+    EncryptionError,
+    #[error("Decryption of file {} unsuccessful, it may have been corrupted or tampered with.", .0.display())]
+    DecryptionError(PathBuf),
+    #[error("Profile {0} not found.")]
+    ProfileNotFoundError(String),
+    #[error("Profile name {0} is invalid: only letters, digits, - and _ are allowed.")]
+    ProfileNameError(String),
+    #[error("Profile {0} is the default or active profile and cannot be deleted.")]
+    ProfileInUseError(String),
+    #[error("User {0} has no account named {1}.")]
+    AccountNotFoundError(String, String),
+    #[error("User {0} already has an account named {1}.")]
+    AccountExistsError(String, String),
+    #[error("Account name {0} is invalid: only letters, digits, - and _ are allowed.")]
+    AccountNameError(String),
+    #[error("Group {0} not found.")]
+    GroupNotFoundError(String),
+    #[error("Group {0} already exists.")]
+    GroupExistsError(String),
+    #[error("Group name {0} is invalid: only letters, digits, - and _ are allowed.")]
+    GroupNameError(String),
+    #[error("User {1} is already a member of group {0}.")]
+    GroupMemberExistsError(String, String),
+    #[error("User {1} is not a member of group {0}.")]
+    GroupMemberNotFoundError(String, String),
+    #[error("Setting {0} not recognized.")]
+    SettingsKeyError(String),
+    #[error("Read from script {} unsuccessful.", .0.display())]
+    IOScriptOpenError(PathBuf),
+    #[error("{0} line(s) of the script failed.")]
+    ScriptFailedError(usize),
+    #[error("Unterminated quote: a closing {0} is missing.")]
+    UnterminatedQuoteError(char),
+    #[error("Unterminated escape: the input ends with \\.")]
+    UnterminatedEscapeError,
+    #[error("Cannot ask \"{0}\" as input is disabled, because of --no-input or because standard input is not a terminal.")]
+    InputRequiredError(String),
+    #[error("The dashboard needs a terminal for both standard input and standard output.")]
+    TerminalRequiredError,
+    #[error("Drawing to or reading from the terminal unsuccessful.")]
+    TerminalError,
+    #[error("{0} must not be empty.")]
+    EmptyFieldError(Field),
+    #[error("{0} must be at most {1} character(s) long.")]
+    FieldTooLongError(Field, usize),
+    #[error("{0} may not contain {1:?}: {}.", .0.allowed())]
+    InvalidCharacterError(Field, char),
+    #[error("{0} {1:?} is not valid, it should look like {}.", .0.example())]
+    InvalidFormatError(Field, String),
+    #[error("{0} must be a number of at least 0, not {1}.")]
+    InvalidNumberError(Field, f64),
     #[error("Invalid input.")]
     InvalidInputError,
     #[error("Unexpected error: impossible state.")]
     ImpossibleStateError,
-}
\ No newline at end of file
+}
+
+impl ProjectError {
+    /// Returns the name of the variant, e.g. "HashMapKeyNotFoundError", which identifies the kind of error in
+    /// structured output
+    pub fn kind(&self) -> String {
+        let debug = format!("{:?}", self);
+        match debug.find('(') {
+            Some(i) => String::from(&debug[..i]),
+            None => debug,
+        }
+    }
+
+    /// Returns whether the error leaves the data files unreadable or unwritten, so that console mode should exit rather
+    /// than carry on. Only IO and (de)serialization errors are fatal; a script that cannot be opened is a mistyped
+    /// path, not a failure of the data files.
+    pub fn is_fatal(&self) -> bool {
+        use ProjectError::*;
+        match self {
+            IOScriptOpenError(_) => false,
+            IOHashMapOpenError(_) | IOHashMapWriteError(_) | IOStateOpenError(_) | IOStateWriteError(_) |
+            IOJournalOpenError(_) | IOJournalWriteError(_) | IOAuditOpenError(_) | IOAuditWriteError(_) |
+            IOExportWriteError(_) | IOHistoryOpenError(_) | IOHistoryWriteError(_) | IOEncryptionOpenError(_) |
+            IOEncryptionWriteError(_) | IOProfilesOpenError(_) | IOProfilesWriteError(_) | IOSettingsOpenError(_) |
+            IOSettingsWriteError(_) | ConfigCreateDirectoryError(_) => true,
+            SerializeJSONError | DeserializeJSONError(_) | SerializeTOMLError | DeserializeTOMLError(_) |
+            SerializeCSVError => true,
+            _ => false,
+        }
+    }
+}
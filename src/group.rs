@@ -0,0 +1,157 @@
+//! #group
+//!
+//! This holds the `Group` type, a named set of users such as a household, whose portfolios may be viewed together
+
+// std
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// external crates
+use serde::{Serialize, Deserialize}; // So groups may be written to the group map
+
+// internal crates
+use crate::account;
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::stock::StockUnit;
+use crate::user::User;
+
+/// The `Group` struct represents users whose holdings are combined by `group-portfolio`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Group {
+    /// The usernames of the members, kept sorted
+    #[serde(default)]
+    pub members: BTreeSet<String>,
+}
+
+impl Group {
+    /// The `add()` method adds `username` to the group `name`, returning a `HashMapKeyNotFoundError` if there is no such
+    /// user in `user_map` or a `GroupMemberExistsError` if they are already a member.
+    pub fn add(&mut self, name: &str, username: &str, user_map: &HashMap<String, User>) -> Result<(), ProjectError> {
+        if !user_map.contains_key(username) {
+            return Err(HashMapKeyNotFoundError(String::from(username)))
+        }
+        if !self.members.insert(String::from(username)) {
+            return Err(GroupMemberExistsError(String::from(name), String::from(username)))
+        }
+        Ok(())
+    }
+
+    /// The `remove()` method removes `username` from the group `name`, returning a `GroupMemberNotFoundError` if they
+    /// are not a member.
+    pub fn remove(&mut self, name: &str, username: &str) -> Result<(), ProjectError> {
+        if !self.members.remove(username) {
+            return Err(GroupMemberNotFoundError(String::from(name), String::from(username)))
+        }
+        Ok(())
+    }
+
+    /// The `holdings()` method combines the holdings of every account of each member by ticker, giving each member's
+    /// share of a ticker under their username. Members missing from `user_map` hold nothing.
+    pub fn holdings(&self, user_map: &HashMap<String, User>) -> BTreeMap<String, BTreeMap<String, StockUnit>> {
+        let mut holdings: BTreeMap<String, BTreeMap<String, StockUnit>> = BTreeMap::new();
+        for (username, user) in self.members.iter().filter_map(|x| user_map.get(x).map(|user| (x, user))) {
+            for (ticker, stock_unit) in account::consolidate(user.accounts.values()) {
+                holdings.entry(ticker).or_default().insert(username.clone(), stock_unit);
+            }
+        }
+        holdings
+    }
+
+    /// Returns the cash of every account of each member, by username
+    pub fn cash(&self, user_map: &HashMap<String, User>) -> BTreeMap<String, f64> {
+        self.members.iter()
+            .filter_map(|x| user_map.get(x).map(|user| (x.clone(), user.accounts.values().map(|y| y.cash).sum())))
+            .collect()
+    }
+}
+
+/// The `total()` function combines the holdings of one ticker by each member, as given by `Group::holdings()`, adding
+/// up their quantities and costs.
+pub fn total(holdings: &BTreeMap<String, StockUnit>) -> Option<StockUnit> {
+    let mut units = holdings.values();
+    let mut total = units.next()?.clone();
+    for stock_unit in units {
+        total.cost = Some(total.cost_basis() + stock_unit.cost_basis());
+        total.quantity += stock_unit.quantity;
+    }
+    Some(total)
+}
+
+/// The `rename_member()` function replaces `old` with `new` in every group of `group_map`, for when a user is renamed.
+pub fn rename_member(group_map: &mut HashMap<String, Group>, old: &str, new: &str) {
+    for group in group_map.values_mut() {
+        if group.members.remove(old) {
+            group.members.insert(String::from(new));
+        }
+    }
+}
+
+/// The `remove_member()` function removes `username` from every group of `group_map`, for when a user is deleted.
+pub fn remove_member(group_map: &mut HashMap<String, Group>, username: &str) {
+    for group in group_map.values_mut() {
+        group.members.remove(username);
+    }
+}
+
+/// Group names are typed on the command line, so only letters, digits, `-` and `_` are allowed
+pub fn validate_name(name: &str) -> Result<(), ProjectError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(GroupNameError(String::from(name)))
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::DEFAULT_ACCOUNT;
+    use crate::stock::Stock;
+
+    #[test]
+    fn holdings_are_combined_by_ticker_and_member() {
+        let foo = Stock::new_from_details("FOO", "Foo Inc", 10.0).unwrap();
+        let bar = Stock::new_from_details("BAR", "Bar Inc", 5.0).unwrap();
+        let mut alice = User::new_from_username("alice").unwrap();
+        alice.add_stock(DEFAULT_ACCOUNT, &foo, 3).unwrap();
+        alice.create_account("ira", 100.0).unwrap();
+        alice.add_stock("ira", &foo, 1).unwrap();
+        let mut bob = User::new_from_username("bob").unwrap();
+        bob.add_stock(DEFAULT_ACCOUNT, &foo, 2).unwrap();
+        bob.add_stock(DEFAULT_ACCOUNT, &bar, 4).unwrap();
+        let user_map = HashMap::from([(String::from("alice"), alice), (String::from("bob"), bob)]);
+
+        let mut group = Group::default();
+        group.members.extend([String::from("alice"), String::from("bob"), String::from("carol")]);
+        let holdings = group.holdings(&user_map);
+        assert_eq!(holdings.keys().collect::<Vec<_>>(), ["BAR", "FOO"]);
+        assert_eq!((holdings["FOO"]["alice"].quantity, holdings["FOO"]["bob"].quantity), (4, 2));
+        assert!(!holdings["BAR"].contains_key("alice"));
+        let foo_total = total(&holdings["FOO"]).unwrap();
+        assert_eq!((foo_total.quantity, foo_total.cost_basis()), (6, 60.0));
+        assert_eq!(group.cash(&user_map)["alice"], 100.0);
+
+        let mut group_map = HashMap::from([(String::from("home"), group)]);
+        rename_member(&mut group_map, "bob", "robert");
+        remove_member(&mut group_map, "carol");
+        assert_eq!(group_map["home"].members.iter().collect::<Vec<_>>(), ["alice", "robert"]);
+        assert!(matches!(validate_name("my home"), Err(GroupNameError(_))));
+    }
+
+    #[test]
+    fn members_must_be_users_and_are_added_once() {
+        let user_map = HashMap::from([
+            (String::from("alice"), User::new_from_username("alice").unwrap()),
+            (String::from("bob"), User::new_from_username("bob").unwrap()),
+        ]);
+        let mut group = Group::default();
+        group.add("home", "bob", &user_map).unwrap();
+        group.add("home", "alice", &user_map).unwrap();
+        assert!(matches!(group.add("home", "alice", &user_map), Err(GroupMemberExistsError(_, _))));
+        assert!(matches!(group.add("home", "dave", &user_map), Err(HashMapKeyNotFoundError(_))));
+        assert_eq!(group.members.iter().collect::<Vec<_>>(), ["alice", "bob"]);
+
+        group.remove("home", "alice").unwrap();
+        assert!(matches!(group.remove("home", "alice"), Err(GroupMemberNotFoundError(_, _))));
+        assert_eq!(group.members.iter().collect::<Vec<_>>(), ["bob"]);
+    }
+}
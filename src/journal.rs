@@ -0,0 +1,309 @@
+//! #journal
+//!
+//! This holds the `Journal` type and related methods, which record the effects of mutating commands so that they may
+//! be undone and redone
+
+// std
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+// external crates
+use serde::{Serialize, Deserialize}; // So we may write the journal to a file
+
+// internal crates
+use crate::crypto;
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::group::Group;
+use crate::stock::Stock;
+use crate::user::User;
+use crate::{Config, State};
+
+/// The maximum number of entries kept in the journal. When exceeded, the oldest entries are discarded first.
+const MAX_JOURNAL_ENTRIES: usize = 50;
+
+/// A `Snapshot` is a copy of all data a command may modify: the user map, the stock map, the archived users, the groups
+/// and the `State`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub user_map: HashMap<String, User>,
+    pub stock_map: HashMap<String, Stock>,
+    /// Users deleted with `delete-user --archive`, which may be restored. Entries journaled before archiving existed
+    /// have none.
+    #[serde(default)]
+    pub archive_map: HashMap<String, User>,
+    /// Groups of users, which entries journaled before groups existed have none
+    #[serde(default)]
+    pub group_map: HashMap<String, Group>,
+    pub state: State,
+}
+
+impl Snapshot {
+    /// `capture()` reads the current data from the configuration directory. A map which has not been initialized yet
+    /// is captured as empty.
+    pub fn capture(config: &Config) -> Result<Snapshot, ProjectError> {
+        let user_map = match config.user_map_path() {
+            path if path.exists() => crate::read_from_hashmap(&path)?,
+            _ => HashMap::new(),
+        };
+        let stock_map = match config.stock_map_path() {
+            path if path.exists() => crate::read_from_hashmap(&path)?,
+            _ => HashMap::new(),
+        };
+        let archive_map = match config.archive_map_path() {
+            path if path.exists() => crate::read_from_hashmap(&path)?,
+            _ => HashMap::new(),
+        };
+        let group_map = crate::read_group_map(config)?;
+        let state = State::init(config)?;
+
+        Ok(Snapshot { user_map, stock_map, archive_map, group_map, state })
+    }
+
+    /// `write()` writes the maps and `State` of the `Snapshot` to the configuration directory.
+    pub fn write(&self, config: &Config) -> Result<(), ProjectError> {
+        self.restore(config, &self.state)
+    }
+
+    /// `restore()` writes the maps of the `Snapshot` back to the configuration directory along with the `State`
+    /// provided. Every file is staged before any is replaced, so a failure leaves the data as it was.
+    fn restore(&self, config: &Config, state: &State) -> Result<(), ProjectError> {
+        let files = [
+            (config.user_map_path(), crate::serialize_hashmap(&config.user_map_path(), &self.user_map)?),
+            (config.stock_map_path(), crate::serialize_hashmap(&config.stock_map_path(), &self.stock_map)?),
+            (config.archive_map_path(), crate::serialize_hashmap(&config.archive_map_path(), &self.archive_map)?),
+            (config.group_map_path(), crate::serialize_hashmap(&config.group_map_path(), &self.group_map)?),
+            (config.state_path(), state.serialize(config)?),
+        ];
+        crate::replace_files(&files)
+    }
+}
+
+/// A `JournalEntry` records a single mutating command along with the data before and after it was run.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JournalEntry {
+    /// The command as it was entered, e.g. "edit-stock FOO value 12.5"
+    pub command: String,
+    /// The data before the command was run
+    pub before: Snapshot,
+    /// The data after the command was run
+    pub after: Snapshot,
+}
+
+/// The `Journal` is the history of mutating commands. Entries before `position` may be undone and entries at or after
+/// `position` may be redone.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+    position: usize,
+}
+
+impl Journal {
+
+    /// `open()` reads the journal from the configuration directory, or returns an empty journal if none exists yet.
+    pub fn open(config: &Config) -> Result<Journal, ProjectError> {
+        let path = config.journal_path();
+
+        if !path.exists() {
+            return Ok(Journal::default())
+        }
+
+        let contents = fs::read(&path).map_err(|_| IOJournalOpenError(path.clone()))?;
+        let contents = crypto::open(&path, contents)?;
+
+        serde_json::from_slice(&contents).map_err(|_| DeserializeJSONError(path))
+    }
+
+    pub fn write(&self, config: &Config) -> Result<(), ProjectError> {
+        let path = config.journal_path();
+
+        let serialized_journal = serde_json::to_string(self).map_err(|_| SerializeJSONError)?;
+        let serialized_journal = crypto::seal(&path, serialized_journal.into_bytes())?;
+
+        let mut file = match fs::File::create(&path) {
+            Ok(x) => x,
+            Err(_) => return Err(IOJournalOpenError(path)),
+        };
+
+        file.write_all(&serialized_journal).map_err(|_| IOJournalWriteError(PathBuf::from(&path)))
+    }
+
+    /// `record()` adds a new entry to the journal and writes it. Any entries which could have been redone are
+    /// discarded, as they no longer follow from the current data.
+    pub fn record(&mut self, config: &Config, command: &str, before: Snapshot, after: Snapshot) -> Result<(), ProjectError> {
+        self.entries.truncate(self.position);
+        self.entries.push(JournalEntry { command: String::from(command), before, after });
+
+        if self.entries.len() > MAX_JOURNAL_ENTRIES {
+            let excess = self.entries.len() - MAX_JOURNAL_ENTRIES;
+            self.entries.drain(..excess);
+        }
+
+        self.position = self.entries.len();
+        self.write(config)
+    }
+
+    /// `undo()` restores the data from before the last `n` commands, returning the commands undone (most recent
+    /// first). Returns a `JournalUndoError` if there is nothing to undo. If `only_by` is given, nothing is undone and a
+    /// `PermissionDeniedError` is returned unless every command was run by that user.
+    pub fn undo(&mut self, config: &Config, n: usize, only_by: Option<&str>) -> Result<Vec<String>, ProjectError> {
+        if self.position == 0 {
+            return Err(JournalUndoError)
+        }
+        check_run_by(&self.entries[self.position.saturating_sub(n)..self.position], only_by, "undo")?;
+
+        let mut undone = Vec::new();
+        while undone.len() < n && self.position > 0 {
+            self.position -= 1;
+            let entry = &self.entries[self.position];
+            let state = restored_state(config, &entry.after, &entry.before)?;
+            entry.before.restore(config, &state)?;
+            undone.push(entry.command.clone());
+        }
+
+        self.write(config)?;
+        Ok(undone)
+    }
+
+    /// `redo()` restores the data from after the next `n` undone commands, returning the commands redone. Returns a
+    /// `JournalRedoError` if there is nothing to redo, and is limited by `only_by` as `undo()` is.
+    pub fn redo(&mut self, config: &Config, n: usize, only_by: Option<&str>) -> Result<Vec<String>, ProjectError> {
+        if self.position == self.entries.len() {
+            return Err(JournalRedoError)
+        }
+        check_run_by(&self.entries[self.position..self.entries.len().min(self.position.saturating_add(n))], only_by, "redo")?;
+
+        let mut redone = Vec::new();
+        while redone.len() < n && self.position < self.entries.len() {
+            let entry = &self.entries[self.position];
+            let state = restored_state(config, &entry.before, &entry.after)?;
+            entry.after.restore(config, &state)?;
+            redone.push(entry.command.clone());
+            self.position += 1;
+        }
+
+        self.write(config)?;
+        Ok(redone)
+    }
+}
+
+/// Returns a `PermissionDeniedError` for `action` unless every one of `entries` was run by the user `only_by`, when
+/// given. The user who ran a command is the one logged in before it.
+fn check_run_by(entries: &[JournalEntry], only_by: Option<&str>, action: &str) -> Result<(), ProjectError> {
+    match only_by {
+        Some(username) if entries.iter().any(|x| x.before.state.current_user.as_deref() != Some(username)) => {
+            Err(PermissionDeniedError(format!("{} of a command run by another user requires an administrator", action)))
+        },
+        _ => Ok(()),
+    }
+}
+
+/// The `restored_state()` function decides which `State` should be in effect when moving from snapshot `from` to
+/// snapshot `to`. The current `State` is always kept, so that undoing or redoing never logs anyone in: the only change
+/// made is to follow the logged in user through a rename made by the command, or to log them out if they do not
+/// exist in the restored user map.
+fn restored_state(config: &Config, from: &Snapshot, to: &Snapshot) -> Result<State, ProjectError> {
+    let mut state = State::init(config)?;
+
+    if let (Some(current), Some(old), Some(new)) = (&state.current_user, &from.state.current_user, &to.state.current_user) {
+        let renamed = current == old && old != new
+            && from.user_map.contains_key(old) && !to.user_map.contains_key(old)
+            && !from.user_map.contains_key(new) && to.user_map.contains_key(new);
+        if renamed {
+            state.current_user = Some(new.clone());
+        }
+    }
+
+    if state.current_user.is_some() && !state.valid_state(to.user_map.clone()) {
+        state.logged_in = false;
+        state.current_user = None;
+        state.session = None;
+        state.current_account = None;
+        state.read_only = false;
+    }
+
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_and_redo_restore_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+        let mut journal = Journal::open(&config).unwrap();
+
+        let before = Snapshot::capture(&config).unwrap();
+        let mut stock_map = HashMap::new();
+        stock_map.insert(String::from("FOO"), Stock::new_from_ticker("FOO").unwrap());
+        crate::write_to_hashmap(&config.stock_map_path(), &stock_map).unwrap();
+        let after = Snapshot::capture(&config).unwrap();
+        journal.record(&config, "create-stock FOO", before.clone(), after.clone()).unwrap();
+
+        assert_eq!(journal.undo(&config, 1, None).unwrap(), vec![String::from("create-stock FOO")]);
+        assert_eq!(Snapshot::capture(&config).unwrap(), before);
+        assert!(matches!(journal.undo(&config, 1, None), Err(JournalUndoError)));
+
+        assert_eq!(journal.redo(&config, 3, None).unwrap(), vec![String::from("create-stock FOO")]);
+        assert_eq!(Snapshot::capture(&config).unwrap(), after);
+        assert!(matches!(journal.redo(&config, 1, None), Err(JournalRedoError)));
+    }
+
+    #[test]
+    fn undo_keeps_the_current_login_unless_renamed() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+        let mut journal = Journal::open(&config).unwrap();
+        let user_map = |usernames: &[&str]| -> HashMap<String, User> {
+            usernames.iter().map(|x| (String::from(*x), User::new_from_username(x).unwrap())).collect()
+        };
+
+        // alice deletes herself, then carol logs in and undoes it
+        crate::write_to_hashmap(&config.user_map_path(), &user_map(&["alice", "carol"])).unwrap();
+        State::init(&config).unwrap().set_user(&config, "alice").unwrap();
+        let before = Snapshot::capture(&config).unwrap();
+        crate::write_to_hashmap(&config.user_map_path(), &user_map(&["carol"])).unwrap();
+        State::init(&config).unwrap().clear_user(&config).unwrap();
+        journal.record(&config, "delete-user alice", before, Snapshot::capture(&config).unwrap()).unwrap();
+        State::init(&config).unwrap().set_user(&config, "carol").unwrap();
+        journal.undo(&config, 1, None).unwrap();
+        assert_eq!(State::init(&config).unwrap().current_user.as_deref(), Some("carol"));
+
+        // carol renames herself to caz, which undoing and redoing follow
+        let before = Snapshot::capture(&config).unwrap();
+        crate::write_to_hashmap(&config.user_map_path(), &user_map(&["alice", "caz"])).unwrap();
+        State::init(&config).unwrap().set_user(&config, "caz").unwrap();
+        journal.record(&config, "edit-user carol username caz", before, Snapshot::capture(&config).unwrap()).unwrap();
+        journal.undo(&config, 1, None).unwrap();
+        assert_eq!(State::init(&config).unwrap().current_user.as_deref(), Some("carol"));
+        journal.redo(&config, 1, None).unwrap();
+        assert_eq!(State::init(&config).unwrap().current_user.as_deref(), Some("caz"));
+    }
+
+    #[test]
+    fn members_may_only_undo_their_own_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+        let mut journal = Journal::open(&config).unwrap();
+
+        // bob creates a stock
+        State::init(&config).unwrap().set_user(&config, "bob").unwrap();
+        let before = Snapshot::capture(&config).unwrap();
+        let stock_map = HashMap::from([(String::from("FOO"), Stock::new_from_ticker("FOO").unwrap())]);
+        crate::write_to_hashmap(&config.stock_map_path(), &stock_map).unwrap();
+        let after = Snapshot::capture(&config).unwrap();
+        journal.record(&config, "create-stock FOO", before.clone(), after.clone()).unwrap();
+
+        assert!(matches!(journal.undo(&config, 1, Some("carol")), Err(PermissionDeniedError(_))));
+        assert_eq!(Snapshot::capture(&config).unwrap().stock_map, after.stock_map);
+        assert_eq!(journal.undo(&config, 1, Some("bob")).unwrap(), vec![String::from("create-stock FOO")]);
+        assert_eq!(Snapshot::capture(&config).unwrap().stock_map, before.stock_map);
+        assert!(matches!(journal.redo(&config, 1, Some("carol")), Err(PermissionDeniedError(_))));
+        journal.redo(&config, 1, None).unwrap();
+        assert!(!crate::temporary_path(&config.stock_map_path()).exists());
+    }
+}
@@ -6,16 +6,50 @@
 #![feature(map_try_insert)]
 
 // modules
+mod account;
+mod args;
+mod audit;
+mod check;
 mod command;
+mod completions;
+mod console;
+mod crypto;
+mod dashboard;
 mod error;
+mod group;
+mod journal;
+mod output;
+mod permission;
+mod profile;
+mod prompt;
+mod script;
+mod settings;
 mod stock;
+mod table;
+mod tokenize;
 mod user;
+mod validate;
 
+use crate::account::DEFAULT_ACCOUNT;
+use crate::args::{FlagValue, ParsedArgs};
+use crate::audit::{AuditEntry, AuditFilter};
 use crate::command::*;
+use crate::completions::{CompletionData, Shell};
 use crate::error::ProjectError;
 use crate::error::ProjectError::*;
-use crate::stock::Stock;
-use crate::user::User;
+use crate::group::Group;
+use crate::journal::{Journal, Snapshot};
+use crate::output::{
+    AccountRecord, AuditRecord, GroupHoldingRecord, GroupRecord, HoldingRecord, ProfileRecord, SessionRecord, SettingRecord,
+    StockRecord, UserDetailRecord, UserRecord, ViolationRecord,
+};
+use crate::profile::Profiles;
+use crate::prompt::PromptHandle;
+use crate::settings::Settings;
+use crate::stock::{Stock, StockUnit};
+use crate::table::{Cell, Table};
+use crate::user::{RiskTolerance, Role, User};
+use crate::validate::Field;
 
 // std
 use std::collections::HashMap; // So we may construct HashMaps
@@ -23,14 +57,14 @@ use std::collections::BTreeMap; // So we may construct BTreeMaps
 use std::env; // So we can set the configuration path by environment variables
 use std::fs; // So we may read/write to files.
 use std::io;
-use std::io::Write;
+use std::io::IsTerminal;
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH}; // So failed logins may expire
 
 // external crates
-use dirs;
+use rustyline::error::ReadlineError; // So console mode may tell Ctrl-C from the end of input
 use serde::{Serialize, Deserialize}; // So we may prepare the HashMap to be written to a file
-use serde_json; // So we may write and read the HashMap to JSON
 
 /// The `Config` struct represents the CLI input state of a call to this program.
 #[derive(Debug, Clone)]
@@ -39,8 +73,20 @@ pub struct Config {
     pub command: Command,
     /// The remainder of arguments which may be processed differently depending on the command.
     pub remainder: Vec<String>,
+    /// The flags provided, keyed by name without the leading `--`
+    pub flags: BTreeMap<String, FlagValue>,
+    /// The global flags provided (e.g. `--profile NAME`), kept so console mode may apply them to every command.
+    pub global_args: Vec<String>,
+    /// The directory holding the profile registry and the data of the default profile
+    pub root_directory: PathBuf,
+    /// The name of the profile in use
+    pub profile: String,
     /// The location of the program's configuration files
     pub configuration_directory: PathBuf, 
+    /// The settings of the configuration directory, with any given on the command line applied
+    pub settings: Settings,
+    /// Where the answers to questions such as delete confirmations come from
+    pub prompt: PromptHandle,
 }
 
 impl Config {
@@ -48,38 +94,91 @@ impl Config {
     I: Iterator<Item = T>,
     T: Into<String>, {
         // Convert to `T` to `String`
-        let mut args = args.map(Into::into);
-
-        args.next(); // Discard the first argument
-
-        // command
-        let command = match args.next() {
-            Some(arg) => Command::new(&arg)?, // Return Err if invalid
-            None => return Err(ConfigNoCommandError),
-        };
-        // remainder
-        let remainder: Vec<String> = args.collect();
-        // configuration_directory
-        let configuration_directory = match env::var("RUST_STOCK_TRACKER_CONFIGURATION_DIRECTORY") {
-            Ok(x) if x != "" => PathBuf::from(x),
-            _ => PathBuf::from( match dirs::home_dir() {
+        let args: Vec<String> = args.map(Into::into).skip(1).collect(); // Discard the first argument
+
+        // command, remainder & flags
+        let parsed = args::parse(args)?;
+        // root_directory
+        let root_directory = match env::var("RUST_STOCK_TRACKER_CONFIGURATION_DIRECTORY") {
+            Ok(x) if !x.is_empty() => PathBuf::from(x),
+            _ => match dirs::home_dir() {
                 Some(p) => p.join(".rust_stock_tracker"),
                 None => return Err(ConfigHomeDirectoryNotFoundError),
-            }),
+            },
         };
 
-        // Checking validity
-        //  remainder
-        if (remainder.len() as i32) < command.num_args() { // Check if valid # of args have been provided
-            return Err(ConfigArgumentsError(format!("{}",command)));
+        Config::from_parsed(parsed, root_directory)
+    }
+
+    /// Like `new()`, but for the arguments the program was called with: when no command is given and commands are
+    /// piped to standard input, they are run as a script.
+    pub fn from_args(args: Vec<String>) -> Result<Config, ProjectError> {
+        match Config::new(args.iter()) {
+            Err(ConfigNoCommandError) if !io::stdin().is_terminal() => {
+                Config::new(args.into_iter().chain(vec![String::from("run-script"), String::from("-")]))
+            },
+            x => x,
         }
+    }
+
+    /// Constructs the `Config` for a line of console or script input, split into `words`. The global flags of `self`
+    /// apply to it unless the line gives them again.
+    pub fn for_line(&self, words: Vec<String>) -> Result<Config, ProjectError> {
+        let args = self.global_args.iter().cloned().chain(words).collect();
+        Config::from_parsed(args::parse(args)?, self.root_directory.clone())
+    }
+
+    fn from_parsed(parsed: ParsedArgs, root_directory: PathBuf) -> Result<Config, ProjectError> {
+        let ParsedArgs { command, remainder, flags, global_args } = parsed;
+
+        // profile & configuration_directory
+        let profiles = Profiles::open(&root_directory)?;
+        let profile = match flags.get("profile").and_then(FlagValue::as_str) {
+            Some(x) => String::from(x),
+            None => String::from(profiles.active()),
+        };
+        let configuration_directory = profiles.directory(&root_directory, &profile)?;
+
+        // Checking validity
         //  configuration_directory
         if !configuration_directory.exists() {
             let configuration_directory_c = configuration_directory.clone();
             fs::create_dir_all(&configuration_directory).map_err(|_| ConfigCreateDirectoryError(configuration_directory_c))?;
         }
 
-        Ok(Config { command, remainder, configuration_directory})
+        // settings, any of which may be overridden by a global flag of the same name
+        let mut settings = Settings::load(&configuration_directory)?;
+        for key in settings::KEYS.iter() {
+            if let Some(x) = flags.get(*key) {
+                settings.set(key, &x.to_string())?;
+            }
+        }
+
+        // prompt, which never waits for an answer when input is disabled or nobody is at a terminal to give one
+        let prompt = prompt::select(flags.contains_key("no-input"));
+
+        Ok(Config { command, remainder, flags, global_args, root_directory, profile, configuration_directory, settings, prompt })
+    }
+
+    /// Returns the value of flag `name`, if it was given
+    pub fn flag(&self, name: &str) -> Option<&FlagValue> {
+        self.flags.get(name)
+    }
+
+    /// Constructs a `Config` for the default profile of `directory`, for use in tests
+    #[cfg(test)]
+    pub fn test(directory: &Path) -> Config {
+        Config {
+            command: Command::Init,
+            remainder: Vec::new(),
+            flags: BTreeMap::new(),
+            global_args: Vec::new(),
+            root_directory: PathBuf::from(directory),
+            profile: String::from(profile::DEFAULT_PROFILE),
+            configuration_directory: PathBuf::from(directory),
+            settings: Settings::default(),
+            prompt: std::rc::Rc::new(std::cell::RefCell::new(prompt::NonInteractivePrompt)),
+        }
     }
 
     /// Simple method to return the location of the UserMap 
@@ -91,30 +190,125 @@ impl Config {
     pub fn stock_map_path(&self) -> PathBuf {
         self.configuration_directory.join("StockMap.JSON")
     }
+
+    /// Simple method to return the location of the State
+    pub fn state_path(&self) -> PathBuf {
+        self.configuration_directory.join("State.JSON")
+    }
+
+    /// Simple method to return the location of the archived users
+    pub fn archive_map_path(&self) -> PathBuf {
+        self.configuration_directory.join("ArchiveMap.JSON")
+    }
+
+    /// Simple method to return the location of the GroupMap
+    pub fn group_map_path(&self) -> PathBuf {
+        self.configuration_directory.join("GroupMap.JSON")
+    }
+
+    /// Simple method to return the location of the directory holdings are exported to when a user is deleted
+    pub fn exports_path(&self) -> PathBuf {
+        self.configuration_directory.join("Exports")
+    }
+
+    /// Simple method to return the location of the Journal
+    pub fn journal_path(&self) -> PathBuf {
+        self.configuration_directory.join("Journal.JSON")
+    }
+
+    /// Simple method to return the location of the audit log
+    pub fn audit_path(&self) -> PathBuf {
+        self.configuration_directory.join("Audit.JSONL")
+    }
+
+    /// Simple method to return the location of the console history
+    pub fn history_path(&self) -> PathBuf {
+        self.configuration_directory.join("History.txt")
+    }
+
+    /// Simple method to return the location of the encryption header, which only exists if the configuration directory
+    /// is encrypted
+    pub fn encryption_path(&self) -> PathBuf {
+        self.configuration_directory.join(crypto::ENCRYPTION_FILE_NAME)
+    }
+
+    /// Returns the locations of every file holding user data, all of which are encrypted when encryption is enabled.
+    /// These include the holdings exported when users were deleted.
+    pub fn data_file_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.user_map_path(), self.stock_map_path(), self.archive_map_path(), self.group_map_path(),
+             self.state_path(), self.journal_path(), self.audit_path(), self.history_path()];
+        if let Ok(entries) = fs::read_dir(self.exports_path()) {
+            let mut exports: Vec<PathBuf> = entries.filter_map(|x| x.ok()).map(|x| x.path())
+                .filter(|x| x.extension().is_some_and(|y| y == "csv"))
+                .collect();
+            exports.sort();
+            paths.extend(exports);
+        }
+        paths
+    }
 }
 
+/// The number of wrong passwords in a row after which a user is locked out
+const MAX_LOGIN_ATTEMPTS: u32 = 5;
+/// How long a user stays locked out, in seconds
+const LOCKOUT_SECONDS: u64 = 300;
+
 /// The `State` struct represents all persistency between calls to this program, such as logged-in states
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
 pub struct State {
     /// A `bool` which is `true` if a user is logged in and `false` if no user is logged in.
     logged_in: bool,
     /// A `String` which, when `Some(x)`, `x` should always be a key of the HashMap in `UserMap.JSON`. When `logged_in` is
     /// `false`, `current_user` should be `None`.
     current_user: Option<String>,
+    /// When the logged-in user logged in and was last active, `None` when no user is logged in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session: Option<Session>,
+    /// The account chosen by `account switch`, `None` for the default account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    current_account: Option<String>,
+    /// Whether commands which change data are refused, for a login with `--read-only`. With nobody logged in, this is a
+    /// guest session.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    read_only: bool,
+    /// The wrong passwords given for each username since its last successful login
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    failed_logins: HashMap<String, FailedLogins>,
+}
+
+/// The `Session` struct represents the times of a login, in seconds since the Unix epoch
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Session {
+    pub login_time: u64,
+    pub last_activity: u64,
+}
+
+impl Session {
+    fn start() -> Session {
+        Session { login_time: now(), last_activity: now() }
+    }
+}
+
+/// The `FailedLogins` struct represents the wrong passwords given in a row for one user
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct FailedLogins {
+    count: u32,
+    /// When the last wrong password was given, in seconds since the Unix epoch
+    last: u64,
 }
 
 impl State {
 
     /// `new()` is more flexible than `init()` and can be used to create a `State` from any existing file.
     pub fn new<P: AsRef<Path>>(path: &P) -> Result<State, ProjectError> {
-        let file = match fs::File::open(path) {
+        let contents = match fs::read(path) {
             Ok(x) => x,
             Err(_) => return Err(IOStateOpenError(PathBuf::from(path.as_ref())))
         };
 
-        let reader = io::BufReader::new(&file);
+        let contents = crypto::open(path.as_ref(), contents)?;
 
-        serde_json::from_reader(reader).map_err(|_| DeserializeJSONError(PathBuf::from(path.as_ref())))
+        serde_json::from_slice(&contents).map_err(|_| DeserializeJSONError(PathBuf::from(path.as_ref())))
     }
 
     /// This function is like `new()`, but it checks if the path is initialized first and
@@ -122,22 +316,14 @@ impl State {
     /// a `Config`.
     pub fn init(config: &Config) -> Result<State, ProjectError> {
         
-        let path = &config.configuration_directory.join("State.JSON");
+        let path = &config.state_path();
 
         if path.exists() {
             State::new(path)
         }
         else {
-            let state = State { logged_in: false, current_user: None, };
-            let serialized_state = serde_json::to_string(&state).map_err(|_| SerializeJSONError)?;
-
-            let mut file = match fs::File::create(path) {
-                Ok(x) => x,
-                Err(_) => return Err(IOStateOpenError(PathBuf::from(path)))
-            };
-
-            file.write_all(serialized_state.as_bytes()).map_err(|_| IOStateWriteError(PathBuf::from(path)))?;
-
+            let state = State::default();
+            state.write(config)?;
             Ok(state)
         }
     }
@@ -147,6 +333,7 @@ impl State {
     pub fn set_user(&mut self, config: &Config, username: &str) -> Result<(), ProjectError> {
         self.logged_in = true;
         self.current_user = Some(String::from(username));
+        self.session = self.session.or_else(|| Some(Session::start()));
         self.write(config)
     }
 
@@ -154,34 +341,110 @@ impl State {
     /// valid before returning. Like `set_user()`, this method returns a result.
     pub fn try_set_user(&mut self, config: &Config, username: &str, hashmap: HashMap<String, User>) -> Result<(), ProjectError> {
         if !self.valid_user(username, hashmap) {
-            return Err(StateInvalidUserError(String::from(username)))
+            Err(StateInvalidUserError(String::from(username)))
         } else {
             self.logged_in = true;
             self.current_user = Some(String::from(username));
+            self.session = Some(Session::start());
+            self.current_account = None;
+            self.failed_logins.remove(username);
             self.write(config)
         }
     }
 
+    /// Returns a `LoginLockedError` if `username` gave too many wrong passwords in a row and the lockout has not
+    /// expired yet.
+    pub fn check_lockout(&self, username: &str) -> Result<(), ProjectError> {
+        match self.failed_logins.get(username) {
+            Some(x) if x.count >= MAX_LOGIN_ATTEMPTS && now() < x.last + LOCKOUT_SECONDS => {
+                Err(LoginLockedError(String::from(username), x.last + LOCKOUT_SECONDS - now()))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// `record_failed_login()` counts a wrong password for `username` and writes the state, returning the error to
+    /// report: an `IncorrectPasswordError`, or a `LoginLockedError` once the attempts are used up. The count starts
+    /// again after a lockout expires.
+    pub fn record_failed_login(&mut self, config: &Config, username: &str) -> Result<ProjectError, ProjectError> {
+        let failed = self.failed_logins.entry(String::from(username)).or_insert(FailedLogins { count: 0, last: 0 });
+        if failed.count >= MAX_LOGIN_ATTEMPTS {
+            failed.count = 0;
+        }
+        failed.count += 1;
+        failed.last = now();
+
+        let error = match MAX_LOGIN_ATTEMPTS - failed.count {
+            0 => LoginLockedError(String::from(username), LOCKOUT_SECONDS),
+            left => IncorrectPasswordError(String::from(username), left),
+        };
+        self.write(config)?;
+        Ok(error)
+    }
+
     /// Returns to a "logged out" state
     pub fn clear_user(&mut self, config: &Config) -> Result<(), ProjectError> {
         self.logged_in = false;
         self.current_user = None;
+        self.session = None;
+        self.current_account = None;
+        self.read_only = false;
         self.write(config)
     }
 
-    pub fn write(&self, config: &Config) -> Result<(), ProjectError> {
-        let path = &config.configuration_directory.join("State.JSON");
+    /// `start_guest()` logs out any user and starts a read-only session with nobody logged in.
+    pub fn start_guest(&mut self, config: &Config) -> Result<(), ProjectError> {
+        self.clear_user(config)?;
+        self.read_only = true;
+        self.write(config)
+    }
 
-        let mut file = match fs::File::create(path) {
-            Ok(x) => x,
-            Err(_) => return Err(IOStateOpenError(PathBuf::from(path)))
+    /// Returns `true` if commands which change data are refused, for a login with `--read-only`
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// `refresh()` records activity in the session of the logged-in user. A session idle for longer than the
+    /// session-timeout setting is logged out instead, returning the username which was logged in. A state which has
+    /// not been initialized is left alone.
+    pub fn refresh(config: &Config) -> Result<Option<String>, ProjectError> {
+        if !config.state_path().exists() {
+            return Ok(None)
+        }
+        let mut state = State::init(config)?;
+        let username = match &state.current_user {
+            Some(x) => x.clone(),
+            None => return Ok(None),
         };
 
-        let serialized_state = serde_json::to_string(self).map_err(|_| SerializeJSONError)?;
+        // A login from before sessions were recorded starts its session now
+        let session = state.session.get_or_insert_with(Session::start);
+        if let Some(timeout) = session_timeout(config)? {
+            if now().saturating_sub(session.last_activity) > timeout {
+                state.clear_user(config)?;
+                return Ok(Some(username))
+            }
+        }
 
-        file.write_all(serialized_state.as_bytes()).map_err(|_| IOStateWriteError(PathBuf::from(path)))?;
+        session.last_activity = now();
+        state.write(config)?;
+        Ok(None)
+    }
 
-        Ok(())
+    /// Returns the session of the logged-in user, if any
+    pub fn session(&self) -> Option<Session> {
+        self.session
+    }
+
+    pub fn write(&self, config: &Config) -> Result<(), ProjectError> {
+        let path = config.state_path();
+        replace_file(&path, &self.serialize(config)?).map_err(|_| IOStateWriteError(path))
+    }
+
+    /// Returns the contents of the state file for this `State`, sealed if the configuration directory is encrypted
+    pub fn serialize(&self, config: &Config) -> Result<Vec<u8>, ProjectError> {
+        let serialized_state = serde_json::to_string(self).map_err(|_| SerializeJSONError)?;
+        crypto::seal(&config.state_path(), serialized_state.into_bytes())
     }
 
     /// Simple function that reports to the user if the `current_user` field is valid
@@ -200,30 +463,130 @@ impl State {
 
 /// The `run` function represents the runtime logic of the program
 pub fn run(config: &Config) -> Result<(), ProjectError> {
+    match config.command {
+        Command::Console => console_mode(config),
+        Command::Exit => Err(InvalidInputError), // should only be accessible from within console_mode
+        Command::Complete => complete(config), // never asks for a passphrase, as it is run by the shell
+        _ => dispatch(config),
+    }
+}
+
+/// The `report_error` function reports an error returned by `run`, as JSON if JSON output was selected.
+pub fn report_error(config: &Config, error: &ProjectError) {
+    output::print_error(config.settings.format, Some("Application error"), error);
+}
+
+/// The `report_parse_error` function reports an error returned by `Config::new`, as JSON if the arguments ask for JSON
+/// output.
+pub fn report_parse_error(args: &[String], error: &ProjectError) {
+    output::print_error(output::requested_format(args), Some("Problem parsing arguments"), error);
+}
+
+/// The `dispatch` function carries out any command other than those which enter or exit console mode. The effects of
+/// mutating commands are recorded in the `Journal` so that they may be undone, and every change to the data (including
+/// undoing and redoing) is recorded in the audit log.
+fn dispatch(config: &Config) -> Result<(), ProjectError> {
+    // Ask for the passphrase if the data files are encrypted and this is the first command to read them
+    crypto::unlock(config)?;
+
+    // A session left idle for too long is logged out so that the user must log in again, which login and help allow
+    if let Some(username) = State::refresh(config)? {
+        if !matches!(config.command, Command::StateC(_) | Command::Help) {
+            return Err(SessionExpiredError(username))
+        }
+    }
+
+    // The role of the logged-in user must allow the command
+    permission::authorize(config)?;
+
+    if !config.command.changes_data() {
+        return execute(config)
+    }
+
+    let before = Snapshot::capture(config)?;
+    execute(config)?;
+    let after = Snapshot::capture(config)?;
+
+    // Every command which may change the data is audited, even when the data files are unchanged, as settings, profiles
+    // and encryption are not part of the snapshot
+    let entry = AuditEntry {
+        timestamp: now(),
+        user: before.state.current_user.clone(),
+        command: config.command.to_string(),
+        arguments: audit_arguments(config),
+        changes: audit::summarize(&before, &after),
+    };
+    // Commands which were declined or changed nothing are not worth undoing
+    if config.command.is_mutating() && before != after {
+        let command = format!("{} {}", config.command, config.remainder.join(" "));
+        Journal::open(config)?.record(config, command.trim_end(), before, after)?;
+    }
+    audit::append(config, &entry)
+}
+
+/// The `execute` function matches each `Command` to the function which carries it out.
+fn execute(config: &Config) -> Result<(), ProjectError> {
     match config.command {
         // Special Commands
-        Command::Init => init(config)?,
-        Command::Console => console_mode(config)?,
-        Command::Exit => return Err(InvalidInputError), // should only be accessible from within console_mode
+        Command::Init                                   => init(config),
+        Command::Check                                  => check(config),
+        Command::Help                                   => help(config),
+        Command::Completions                            => completions(config),
+        Command::Complete                               => complete(config),
+        Command::Console | Command::Exit                => Err(InvalidInputError), // handled by `run` and `console_mode`
+        Command::RunScript                              => run_script(config),
+        Command::Dashboard                              => dashboard::run(config, dispatch),
         // State Commands
-        Command::StateC(StateCommand::Login)            => login(config)?,
-        Command::StateC(StateCommand::Logout)           => logout(config)?,
+        Command::StateC(StateCommand::Login)            => login(config),
+        Command::StateC(StateCommand::Logout)           => logout(config),
+        Command::StateC(StateCommand::WhoAmI)           => whoami(config),
         // User Commands
-        Command::UserC(UserCommand::Create)             => create_user(config)?,
-        Command::UserC(UserCommand::Delete)             => delete_user(config)?,
-        Command::UserC(UserCommand::Edit)               => edit_user(config)?,
-        Command::UserC(UserCommand::List)               => list_users(config)?,
+        Command::UserC(UserCommand::Create)             => create_user(config),
+        Command::UserC(UserCommand::Delete)             => delete_user(config),
+        Command::UserC(UserCommand::Restore)            => restore_user(config),
+        Command::UserC(UserCommand::Edit)               => edit_user(config),
+        Command::UserC(UserCommand::List)               => list_users(config),
+        Command::UserC(UserCommand::Show)               => show_user(config),
+        Command::UserC(UserCommand::SetPassword)        => set_password(config),
+        Command::UserC(UserCommand::ChangePassword)     => change_password(config),
+        Command::UserC(UserCommand::SetRole)            => set_role(config),
         // Stock Commands
-        Command::StockC(StockCommand::Create)           => create_stock(config)?,
-        Command::StockC(StockCommand::Delete)           => delete_stock(config)?,
-        Command::StockC(StockCommand::Edit)             => edit_stock(config)?,
-        Command::StockC(StockCommand::List)             => list_stocks(config)?,
-        // Portfolio Command
-        Command::PortfolioC(PortfolioCommand::Buy)      => buy_stock(config)?,
-        Command::PortfolioC(PortfolioCommand::List)     => list_portfolio(config)?,
-    };
-
-    Ok(())
+        Command::StockC(StockCommand::Create)           => create_stock(config),
+        Command::StockC(StockCommand::Delete)           => delete_stock(config),
+        Command::StockC(StockCommand::Edit)             => edit_stock(config),
+        Command::StockC(StockCommand::List)             => list_stocks(config),
+        // Portfolio Commands
+        Command::PortfolioC(PortfolioCommand::Buy)      => buy_stock(config),
+        Command::PortfolioC(PortfolioCommand::List)     => list_portfolio(config),
+        // Account Commands
+        Command::AccountC(AccountCommand::Create)       => create_account(config),
+        Command::AccountC(AccountCommand::List)         => list_accounts(config),
+        Command::AccountC(AccountCommand::Switch)       => switch_account(config),
+        // Group Commands
+        Command::GroupC(GroupCommand::Create)           => create_group(config),
+        Command::GroupC(GroupCommand::Delete)           => delete_group(config),
+        Command::GroupC(GroupCommand::Add)              => add_to_group(config),
+        Command::GroupC(GroupCommand::Remove)           => remove_from_group(config),
+        Command::GroupC(GroupCommand::List)             => list_groups(config),
+        Command::GroupC(GroupCommand::Portfolio)        => group_portfolio(config),
+        // History Commands
+        Command::HistoryC(HistoryCommand::Undo)         => undo(config),
+        Command::HistoryC(HistoryCommand::Redo)         => redo(config),
+        Command::HistoryC(HistoryCommand::Audit)        => audit(config),
+        // Encryption Commands
+        Command::EncryptionC(EncryptionCommand::Encrypt)            => encrypt(config),
+        Command::EncryptionC(EncryptionCommand::Decrypt)            => decrypt(config),
+        Command::EncryptionC(EncryptionCommand::ChangePassphrase)   => change_passphrase(config),
+        // Profile Commands
+        Command::ProfileC(ProfileCommand::List)         => list_profiles(config),
+        Command::ProfileC(ProfileCommand::Create)       => create_profile(config),
+        Command::ProfileC(ProfileCommand::Switch)       => switch_profile(config),
+        Command::ProfileC(ProfileCommand::Delete)       => delete_profile(config),
+        // Settings Commands
+        Command::SettingsC(SettingsCommand::Get)        => get_setting(config),
+        Command::SettingsC(SettingsCommand::Set)        => set_setting(config),
+        Command::SettingsC(SettingsCommand::List)       => list_settings(config),
+    }
 }
 
 //
@@ -238,80 +601,344 @@ fn init(config: &Config) -> Result<(), ProjectError> {
     // Generate new stock hashmap and write to file
     let stock_map = HashMap::<String, Stock>::new();
     write_to_hashmap(&config.stock_map_path(), &stock_map)?;
+    // Forget any archived users
+    write_to_hashmap(&config.archive_map_path(), &HashMap::<String, User>::new())?;
+    // Forget any groups
+    write_to_hashmap(&config.group_map_path(), &HashMap::<String, Group>::new())?;
     // Log any users out of state so there are no impossible users logged in
     let mut state = State::init(config)?;
     state.clear_user(config)?;
 
-    notify("All user/stock data reset/initialized.");
+    notify(config, "All user/stock data reset/initialized.");
     Ok(())
 }
 
-fn console_mode(_config: &Config) -> Result<(), ProjectError> {
+/// The `check` function reports every violation of the invariants between the user map, the stock map and the `State`.
+/// If `--repair` is given, the violations are fixed and a summary of the repairs is shown instead.
+fn check(config: &Config) -> Result<(), ProjectError> {
+    let repair = config.flag("repair").is_some();
+
+    let mut snapshot = Snapshot::capture(config)?;
+
+    let violations = if repair { check::repair(&mut snapshot) } else { check::find_violations(&snapshot) };
+    if repair && !violations.is_empty() {
+        snapshot.write(config)?;
+    }
+
+    let records: Vec<ViolationRecord> = violations.iter().map(ViolationRecord::from).collect();
+    output::print_records(config, &records, || {
+        if violations.is_empty() {
+            println!("No problems found.");
+            return
+        }
+        if repair {
+            println!("Repaired {} problem(s):", violations.len());
+        }
+        for violation in &violations {
+            println!("{}", violation);
+        }
+    })?;
+
+    if repair || violations.is_empty() {
+        Ok(())
+    } else {
+        Err(IntegrityCheckError(violations.len()))
+    }
+}
+
+/// The `help` function lists every command, or describes the command named by the remainder in detail.
+fn help(config: &Config) -> Result<(), ProjectError> {
+    if config.remainder.is_empty() {
+        print!("{}", command::help_text());
+        return Ok(())
+    }
+
+    // The first word may be the alias of a group, e.g. "help pf list"
+    let mut words = config.remainder.clone();
+    if let Some(group) = Command::group(&words[0]) {
+        words[0] = String::from(group);
+    }
+
+    match CommandSpec::find(&words.join(" ")) {
+        Some(spec) => print!("{}", spec.help_text()),
+        None => return Err(CommandInvalidError),
+    }
+    Ok(())
+}
+
+/// The `completions` function prints the completion script for the shell given.
+fn completions(config: &Config) -> Result<(), ProjectError> {
+    let shell = config.remainder[0].parse::<Shell>()?;
+    print!("{}", completions::script(shell));
+    Ok(())
+}
+
+/// The `complete` function prints the completions of the word following the remainder, one per line.
+fn complete(config: &Config) -> Result<(), ProjectError> {
+    for candidate in completions::candidates(&config.remainder, &completion_data(config)) {
+        println!("{}", candidate);
+    }
+    Ok(())
+}
+
+/// The `completion_data` function reads the names arguments may be completed with. Usernames and tickers are left out
+/// if the data cannot be read, e.g. because it is encrypted.
+fn completion_data(config: &Config) -> CompletionData {
+    let mut data = CompletionData::default();
+    if let Ok(user_map) = read_from_hashmap::<PathBuf, User>(&config.user_map_path()) {
+        data.usernames = user_map.into_keys().collect();
+        data.usernames.sort();
+    }
+    if let Ok(stock_map) = read_from_hashmap::<PathBuf, Stock>(&config.stock_map_path()) {
+        data.tickers = stock_map.into_keys().collect();
+        data.tickers.sort();
+    }
+    if let Ok(profiles) = Profiles::open(&config.root_directory) {
+        data.profiles = profiles.list(&config.root_directory).into_iter().map(|(name, _)| name).collect();
+    }
+    if let Some(Ok((user, _))) = config.state_path().exists().then(|| logged_in_user(config)) {
+        data.accounts = user.accounts.into_keys().collect();
+    }
+    if let Ok(group_map) = read_group_map(config) {
+        data.groups = group_map.into_keys().collect();
+        data.groups.sort();
+    }
+    data
+}
+
+/// The `console_mode` function reads commands line by line, with line editing, tab completion and a history kept in
+/// the configuration directory, until `exit` or end of input.
+fn console_mode(config: &Config) -> Result<(), ProjectError> {
     // Notify the user that they have entered console mode
-    notify("Entering console mode...");
+    notify(config, "Entering console mode...");
+
+    // The history is encrypted along with the data, so ask for the passphrase up front
+    crypto::unlock(config)?;
+    let mut editor = console::editor(config)?;
     
     loop { // Loop until exited
-        print!(">");
-        io::stdout().flush().unwrap();
+        // Refreshed every line, so names created by the last command may be completed
+        editor.set_helper(Some(console::ConsoleHelper { data: completion_data(config) }));
+
+        let args_string = match editor.readline(">") {
+            Ok(x) => x,
+            Err(ReadlineError::Interrupted) => continue, // Ctrl-C cancels the current line
+            Err(ReadlineError::Eof) => { notify(config, "Exiting..."); return Ok(()) },
+            Err(_) => return Err(InvalidInputError),
+        };
 
-        let mut args_string = String::new();
-        io::stdin()
-            .read_line(&mut args_string)
-            .map_err(|_| InvalidInputError)?;
+        if !args_string.trim().is_empty() {
+            editor.add_history_entry(args_string.as_str()).map_err(|_| InvalidInputError)?;
+            console::write_history(config, &editor)?;
+        }
+
+        // Split the line as a shell would, so quoted arguments may hold spaces
+        let words = match tokenize::split(&args_string) {
+            Ok(x) => x,
+            Err(x) => { notify(config, &x.to_string()); continue },
+        };
 
-        // The config constructor expects the first argument to be the call to the program
-        let args = format!("filler {}", args_string); // To satisfy macro requirements this is on its own line
-        let args = args.split_ascii_whitespace();
         // Construct a new config for the console-mode loop instance
-        let this_config = match Config::new(args) {
+        // Global options given when entering console mode apply to every command
+        let this_config = match config.for_line(words) {
             Ok(x) => x,
-            Err(_) => { notify("Command not recognized."); continue},
+            Err(CommandInvalidError) => { notify(config, "Command not recognized, enter \"help\" for a list of commands."); continue },
+            Err(ConfigNoCommandError) => continue, // Blank line
+            Err(x) => { notify(config, &x.to_string()); continue },
         };
 
         // Accept command inputs
         let result = match this_config.command {
-            // Special Commands
-            Command::Init                                   => init(&this_config),
-            Command::Console                                => { notify("Already in console mode."); continue },
-            Command::Exit                                   => { notify("Exiting..."); return Ok(()) }, // should only be accessible from within console_mode
-            // State Commands
-            Command::StateC(StateCommand::Login)            => login(&this_config),
-            Command::StateC(StateCommand::Logout)           => logout(&this_config),
-            // User Commands
-            Command::UserC(UserCommand::Create)             => create_user(&this_config),
-            Command::UserC(UserCommand::Delete)             => delete_user(&this_config),
-            Command::UserC(UserCommand::Edit)               => edit_user(&this_config),
-            Command::UserC(UserCommand::List)               => list_users(&this_config),
-            // State Commands
-            Command::StockC(StockCommand::Create)           => create_stock(&this_config),
-            Command::StockC(StockCommand::Delete)           => delete_stock(&this_config),
-            Command::StockC(StockCommand::Edit)             => edit_stock(&this_config),
-            Command::StockC(StockCommand::List)             => list_stocks(&this_config),
-            // Portfolio Commands
-            Command::PortfolioC(PortfolioCommand::Buy)      => buy_stock(&this_config),
-            Command::PortfolioC(PortfolioCommand::List)     => list_portfolio(&this_config),
+            Command::Console                                => { notify(config, "Already in console mode."); continue },
+            Command::Exit                                   => { notify(config, "Exiting..."); return Ok(()) }, // should only be accessible from within console_mode
+            _                                               => dispatch(&this_config),
         };
-        // Check if Error command should throw exit console mode or not
+        // Only errors which leave the data files unreadable or unwritten exit console mode
         match result {
             Ok(_) => continue,
-            Err(x @ InputParseError(_,_)) |
-            Err(x @ HashMapKeyNotFoundError(_)) |
-            Err(x @ StateNoUserError) | 
-            Err(x @ StateInvalidUserError(_)) => println!("{}", x),
-            Err(x) => return Err(x),
+            Err(x) if x.is_fatal() => return Err(x),
+            Err(x) => output::print_error(this_config.settings.format, None, &x),
         };
     }
 }
 
+/// The `run_script` function carries out the commands of a file, or of standard input if the file is `-`, one per
+/// line through the same dispatcher as console mode. Each command is journaled on its own. With `--on-error continue`
+/// every line is attempted; otherwise the script stops at the first failure. Failed lines are summarized at the end.
+fn run_script(config: &Config) -> Result<(), ProjectError> {
+    let file = &config.remainder[0];
+    let on_error = match config.flag("on-error").and_then(FlagValue::as_str) {
+        Some(x) => x.parse()?,
+        None => script::OnError::Stop,
+    };
+
+    let source = if file == "-" {
+        let mut source = String::new();
+        io::Read::read_to_string(&mut io::stdin(), &mut source).map_err(|_| IOScriptOpenError(PathBuf::from(file)))?;
+        source
+    } else {
+        fs::read_to_string(file).map_err(|_| IOScriptOpenError(PathBuf::from(file)))?
+    };
+
+    let summary = script::run(config, &source, on_error, dispatch);
+    if summary.failures.is_empty() {
+        return Ok(())
+    }
+
+    notify(config, &format!("{} of {} command(s) failed:", summary.failures.len(), summary.commands));
+    for failure in &summary.failures {
+        notify(config, &failure.to_string());
+    }
+    Err(ScriptFailedError(summary.failures.len()))
+}
+
 /// The `login` function opens the HashMap, and activates a state where certain commmands will be applied on the user in question.
+/// With `--read-only` every command which changes data is refused until logging out, and without a username a guest
+/// session is started instead, with nobody logged in.
 fn login(config: &Config) -> Result<(), ProjectError>{
     // Setup
-    let username = &config.remainder[0];
+    let read_only = config.flag("read-only").is_some();
     let mut state = State::init(config)?;
-    let hashmap = read_from_hashmap(&config.user_map_path())?;
+    let username = match config.remainder.first() {
+        Some(x) => x,
+        // Without a username, only a guest may log in
+        None if read_only => {
+            state.start_guest(config)?;
+            notify(config, "Viewing as a read-only guest, log in as a user to make changes.");
+            return Ok(())
+        },
+        None => return Err(ConfigArgumentsError(config.command.spec().usage())),
+    };
+    let hashmap: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    // Password, for users who have set one
+    if let Some(user) = hashmap.get(username).filter(|x| x.has_password()) {
+        state.check_lockout(username)?;
+        let password = config.prompt.borrow_mut().ask_secret(&format!("Password for {}: ", username))?;
+        if !user.verify_password(&password) {
+            return Err(state.record_failed_login(config, username)?)
+        }
+    }
     // Login
-    state.try_set_user(config, &username, hashmap)?;
-    notify(&format!("Logged in as {} successfully.", username));
+    state.read_only = read_only;
+    state.try_set_user(config, username, hashmap)?;
+    if read_only {
+        notify(config, &format!("Logged in as {} successfully, read-only.", username));
+    } else {
+        notify(config, &format!("Logged in as {} successfully.", username));
+    }
+    Ok(())
+}
+
+/// The `set_role` function takes a username and a role (in that order) and gives the user that role. The last
+/// administrator cannot be given another role while there are other users.
+fn set_role(config: &Config) -> Result<(), ProjectError> {
+    let username = &config.remainder[0];
+    let role: Role = config.remainder[1].parse()?;
+
+    let mut user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    if !user_map.contains_key(username) {
+        return Err(HashMapKeyNotFoundError(String::from(username)))
+    }
+    if role != Role::Admin && permission::is_last_admin(&user_map, username) {
+        return Err(LastAdminError(String::from(username)))
+    }
+
+    let user = user_map.get_mut(username).unwrap(); // We can be confident this will be Some()
+    user.set_role(role);
+    user.mark_updated(now());
+    write_to_hashmap(&config.user_map_path(), &user_map)?;
+
+    notify(config, &format!("User {} is now {}.", username, role));
+    Ok(())
+}
+
+/// The `whoami` function shows the logged-in user, how long ago they logged in, when their session expires and whether
+/// it is read-only. A guest session is only noted, as it has no user.
+fn whoami(config: &Config) -> Result<(), ProjectError> {
+    let state = State::init(config)?;
+    let read_only = state.is_read_only();
+    let (username, session) = match (state.current_user, state.session) {
+        (Some(x), Some(y)) => (x, y),
+        _ if read_only => {
+            notify(config, "Nobody is logged in, viewing as a read-only guest.");
+            return Ok(())
+        },
+        _ => return Err(StateNoUserError),
+    };
+
+    let timeout = session_timeout(config)?;
+    let record = SessionRecord {
+        username,
+        login_time: session.login_time,
+        last_activity: session.last_activity,
+        session_age: now().saturating_sub(session.login_time),
+        expires_at: timeout.map(|x| session.last_activity.saturating_add(x)),
+        read_only,
+    };
+
+    output::print_record(config, &record, || {
+        let mode = if record.read_only { ", read-only" } else { "" };
+        println!("Logged in as {} for {}{}.", record.username, format_duration(record.session_age), mode);
+        match timeout {
+            Some(x) => println!("The session expires after {} without activity.", format_duration(x)),
+            None => println!("The session does not expire."),
+        }
+    })
+}
+
+/// The `set_password` function sets a password for the logged-in user, who must not have one yet. The new password
+/// is asked for twice without echo.
+fn set_password(config: &Config) -> Result<(), ProjectError> {
+    let username = match State::init(config)?.current_user {
+        Some(x) => x,
+        None => return Err(StateNoUserError),
+    };
+
+    let mut user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    let user = user_map.get_mut(&username).ok_or_else(|| HashMapKeyNotFoundError(username.clone()))?;
+    if user.has_password() {
+        return Err(PasswordAlreadySetError(username))
+    }
+
+    user.set_password(&read_new_password(config)?)?;
+    user.mark_updated(now());
+    write_to_hashmap(&config.user_map_path(), &user_map)?;
+
+    notify(config, &format!("Password set for {}, it will be asked for at login.", username));
+    Ok(())
+}
+
+/// The `change_password` function changes the password of the logged-in user, or removes it with `--remove`. The
+/// current password is asked for first, and wrong answers count towards a lockout as they do for `login`.
+fn change_password(config: &Config) -> Result<(), ProjectError> {
+    let mut state = State::init(config)?;
+    let username = match state.current_user.clone() {
+        Some(x) => x,
+        None => return Err(StateNoUserError),
+    };
+
+    let mut user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    let user = user_map.get_mut(&username).ok_or_else(|| HashMapKeyNotFoundError(username.clone()))?;
+    if !user.has_password() {
+        return Err(NoPasswordError(username))
+    }
+
+    state.check_lockout(&username)?;
+    let current = config.prompt.borrow_mut().ask_secret("Current password: ")?;
+    if !user.verify_password(&current) {
+        return Err(state.record_failed_login(config, &username)?)
+    }
+
+    user.mark_updated(now());
+    if config.flag("remove").is_some() {
+        user.clear_password();
+        write_to_hashmap(&config.user_map_path(), &user_map)?;
+        notify(config, &format!("Password removed for {}.", username));
+    } else {
+        user.set_password(&read_new_password(config)?)?;
+        write_to_hashmap(&config.user_map_path(), &user_map)?;
+        notify(config, &format!("Password changed for {}.", username));
+    }
     Ok(())
 }
 
@@ -319,60 +946,144 @@ fn login(config: &Config) -> Result<(), ProjectError>{
 fn logout(config: &Config) -> Result<(), ProjectError>{
     let mut state = State::init(config)?;
     state.clear_user(config)?;
-    notify("Logged out successfully.");
+    notify(config, "Logged out successfully.");
     Ok(())
 }
 
-/// The `create_user` function opens the HashMap and inserts a new user. 
+/// The `create_user` function opens the HashMap and inserts a new user with the names given by `--first`, `--last`
+/// and `--middle`. Any not given are asked for when run from a terminal.
 fn create_user(config: &Config) -> Result<(), ProjectError> {
 
     let username = &config.remainder[0];
+    validate::username(username)?;
+
+    // Preliminary check if username is free, so details are not asked for in vain
+    let user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    if user_map.contains_key(username) {
+        return Err(HashMapInsertError(String::from(username)))
+    }
+
+    // The first user, or one created while there is no administrator, becomes an administrator to manage the others
+    let role = match config.flag("role") {
+        _ if !permission::has_admin(&user_map) => Role::Admin,
+        Some(x) => x.to_string().parse()?,
+        None => Role::Member,
+    };
+
+    // Details
+    let placeholder = User::new_from_username(username).map_err(|_| UserNewError)?;
+    let first_name: String = detail(config, "first", &format!("First name of {}", username), placeholder.first_name())?;
+    let last_name: String = detail(config, "last", &format!("Last name of {}", username), placeholder.last_name())?;
+    // Users without a middle name have no middle initial
+    let middle_initial: String = detail(config, "middle", &format!("Middle initial of {}", username), "")?;
+    validate::name(Field::FirstName, &first_name)?;
+    validate::name(Field::LastName, &last_name)?;
+    validate::middle_initial(&middle_initial)?;
+    let mut user = User::new_from_details(username, &first_name, &last_name, &middle_initial).map_err(|_| UserNewError)?;
+    user.set_role(role);
+    user.mark_created(now());
 
     let f = |hashmap: &mut HashMap<String, User>| {
-        hashmap.try_insert(String::from(username), User::new_from_username(username).map_err(|_| UserNewError)?)
+        hashmap.try_insert(String::from(username), user.clone())
         .map_or_else(|_| Err(HashMapInsertError(String::from(username))), |_| Ok(()))
     };
 
     modify_hashmap(&config.user_map_path(), f)?;
 
-    notify(&format!("User {} has been added.", username));
+    notify(config, &format!("User {} has been added.", username));
     Ok(())
 }
 
-/// The `delete_user` function queries the user for a confirmation, opens the HashMap, and deletes a user.
+/// The `delete_user` function queries the user for a confirmation, opens the HashMap, and deletes a user. With
+/// `--archive` the user is moved to the archive instead, from which `restore-user` may restore them. A user who still
+/// has holdings is only deleted permanently after an explicit confirmation, even if the `confirm-delete` setting is
+/// off, and their holdings are first exported to a CSV file. A deleted user who is logged in is logged out, and is
+/// removed from every group.
 fn delete_user(config: &Config) -> Result<(), ProjectError> {
     
     let username = &config.remainder[0];
+    let archive = config.flag("archive").is_some();
 
-    // Preliminary check if username exists in the user map
-    if !read_from_hashmap::<PathBuf, User>(&config.user_map_path())?.contains_key(username) {
-        return Err(HashMapKeyNotFoundError(String::from(username)))
+    // Preliminary check if username exists in the user map, and may be deleted
+    let mut user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    let user = match user_map.get(username) {
+        Some(x) => x,
+        None => return Err(HashMapKeyNotFoundError(String::from(username))),
+    };
+    if permission::is_last_admin(&user_map, username) {
+        return Err(LastAdminError(String::from(username)))
+    }
+    let mut archive_map: HashMap<String, User> = match config.archive_map_path() {
+        path if path.exists() => read_from_hashmap(&path)?,
+        _ => HashMap::new(),
+    };
+    if archive && archive_map.contains_key(username) {
+        return Err(ArchivedUserExistsError(String::from(username)))
     }
 
     // Make sure the user wants to delete
-    println!("Are you sure you want to delete user profile {}", username.to_string());
+    let holdings = user.accounts.values().map(|x| x.holdings.len()).sum::<usize>();
+    let confirmed = if archive {
+        confirm_delete(config, &format!("Are you sure you want to archive user profile {}", username))?
+    } else if holdings > 0 {
+        confirm(config, &format!("User {} still has {} holding(s), which will be exported. Are you sure you want to \
+            permanently delete user profile {}", username, holdings, username))?
+    } else {
+        confirm_delete(config, &format!("Are you sure you want to delete user profile {}", username))?
+    };
+    if !confirmed {
+        return Ok(())
+    }
 
-    let mut ans = String::new();
-    io::stdin().read_line(&mut ans).map_err(|_| UserNewError)?;
+    if !archive && holdings > 0 {
+        let path = export_holdings(config, user)?;
+        notify(config, &format!("Holdings of {} exported to {}.", username, path.display()));
+    }
 
-    // Remove the newline
-    let ans = ans.trim();
+    let user = user_map.remove(username).ok_or_else(|| HashMapRemoveError(String::from(username)))?;
+    write_to_hashmap(&config.user_map_path(), &user_map)?;
+    if archive {
+        archive_map.insert(String::from(username), user);
+        write_to_hashmap(&config.archive_map_path(), &archive_map)?;
+    }
+    let mut group_map = read_group_map(config)?;
+    group::remove_member(&mut group_map, username);
+    write_to_hashmap(&config.group_map_path(), &group_map)?;
 
-    match ans.to_lowercase().as_str() {
-        // In the case where the user is sure
-        "y" | "yes" => {
-            let f = |hashmap: &mut HashMap<String, User>| hashmap
-                .remove(&username.to_string()) // Remove
-                .ok_or_else(|| HashMapRemoveError(String::from(username))).map(|_| ()); // Handle Option -> Result & discarding User
-            modify_hashmap(&config.user_map_path(), f)?
-        },
-        // In the case where the user declines
-        "q" | "quit" | "n" | "no" => return Ok(()),
-        // In the case where the user input is not recognized
-        _ => return Err(InvalidInputError),
+    // The deleted user may no longer be logged in
+    let mut state = State::init(config)?;
+    if state.current_user.as_ref() == Some(username) {
+        state.clear_user(config)?;
+    }
+
+    if archive {
+        notify(config, &format!("User {} archived, restore-user {} will restore them.", username, username));
+    } else {
+        notify(config, &format!("User {} deleted.", username));
+    }
+    Ok(())
+}
+
+/// The `restore_user` function moves a user archived by `delete-user --archive` back into the user map, with the
+/// holdings and accounts they had when archived.
+fn restore_user(config: &Config) -> Result<(), ProjectError> {
+    let username = &config.remainder[0];
+
+    let mut archive_map: HashMap<String, User> = match config.archive_map_path() {
+        path if path.exists() => read_from_hashmap(&path)?,
+        _ => HashMap::new(),
     };
+    let mut user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    if user_map.contains_key(username) {
+        return Err(HashMapInsertError(String::from(username)))
+    }
+    let user = archive_map.remove(username).ok_or_else(|| HashMapKeyNotFoundError(String::from(username)))?;
+
+    user_map.insert(String::from(username), user);
+    write_to_hashmap(&config.user_map_path(), &user_map)?;
+    write_to_hashmap(&config.archive_map_path(), &archive_map)?;
 
-    notify(&format!("User {} deleted.", username));
+    notify(config, &format!("User {} restored.", username));
     Ok(())
 }
 
@@ -404,6 +1115,7 @@ fn edit_user(config: &Config) -> Result<(), ProjectError> {
 
     match user.get_property(&property)? {
         user::Property::Username(x) => { // Must be a `String`
+            validate::username(&value)?;
             let username_property = x;
             *username_property = value.clone(); // We clone here so we don't move `value`
             // We will need to update the username as a key
@@ -415,25 +1127,65 @@ fn edit_user(config: &Config) -> Result<(), ProjectError> {
             note = format!("User {} changed to {}.", username, username_property);
         },
         user::Property::FirstName(x) => { // Must be a `String`
+            validate::name(Field::FirstName, &value)?;
             let first_name = x;
             *first_name = value;
             note = format!("User {}'s first name changed to {}.", username, first_name);
         },
         user::Property::LastName(x) => { // Must be a `String`
+            validate::name(Field::LastName, &value)?;
             let last_name = x;
             *last_name = value;
             note = format!("User {}'s last name changed to {}.", username, last_name);
         },
         user::Property::MiddleInitial(x) => { // Must be a `String`
+            validate::middle_initial(&value)?;
             let middle_initial = x;
             *middle_initial = value;
             note = format!("User {}'s middle initial changed to {}.", username, middle_initial);
         },
+        // Optional details are cleared by an empty value
+        user::Property::Email(x) => { // Must be an email address
+            let email = if value.is_empty() { None } else { validate::email(&value)?; Some(value) };
+            note = format!("User {}'s email changed to {}.", username, email.as_deref().unwrap_or("none"));
+            *x = email;
+        },
+        user::Property::BaseCurrency(x) => { // Must be a currency code
+            let currency = if value.is_empty() { None } else { validate::currency(&value)?; Some(value.to_uppercase()) };
+            note = format!("User {}'s base currency changed to {}.", username, currency.as_deref().unwrap_or("none"));
+            *x = currency;
+        },
+        user::Property::RiskTolerance(x) => { // Must be a `RiskTolerance`
+            let risk_tolerance = if value.is_empty() { None } else { Some(value.parse::<RiskTolerance>()?) };
+            note = format!("User {}'s risk tolerance changed to {}.", username,
+                risk_tolerance.map_or(String::from("none"), |x| x.to_string()));
+            *x = risk_tolerance;
+        },
+        user::Property::TaxJurisdiction(x) => { // Must be a country code and optional subdivision
+            let jurisdiction = if value.is_empty() { None } else { validate::tax_jurisdiction(&value)?; Some(value.to_uppercase()) };
+            note = format!("User {}'s tax jurisdiction changed to {}.", username, jurisdiction.as_deref().unwrap_or("none"));
+            *x = jurisdiction;
+        },
     };
-
-    // Remove old entry from HashMap if necessary
+    user.mark_updated(now());
+
+    // Remove old entry from HashMap if necessary, without replacing another user, nor taking the name of an archived
+    // user who could then not be restored
+    if update_username && new_username_1 != *username {
+        let archive_map: HashMap<String, User> = match config.archive_map_path() {
+            path if path.exists() => read_from_hashmap(&path)?,
+            _ => HashMap::new(),
+        };
+        if user_map.contains_key(&new_username_1) || archive_map.contains_key(&new_username_1) {
+            return Err(HashMapInsertError(new_username_1))
+        }
+    }
     if update_username {
         let user = user_map.remove(username).unwrap(); // We can be confident this is `Some`
+        // Groups refer to their members by username
+        let mut group_map = read_group_map(config)?;
+        group::rename_member(&mut group_map, username, &new_username_1);
+        write_to_hashmap(&config.group_map_path(), &group_map)?;
         user_map.insert(new_username_1, user);
     }
 
@@ -444,50 +1196,107 @@ fn edit_user(config: &Config) -> Result<(), ProjectError> {
     let mut state = State::init(config)?;
     // If the user we changed is the one logged in
     if update_username &&
-        match &state.current_user { Some(x) => *x == new_username_2, None => false, } {
+        match &state.current_user { Some(x) => x == username, None => false, } {
         state.set_user(config, &new_username_2)?;
     }
 
     // Notify success
-    notify(&note);
+    notify(config, &note);
 
     Ok(())
 }
 
-/// The `list_users` function lists all created `User`s in the `UserMap`
+/// The `list_users` function lists all created `User`s in the `UserMap`, or the archived ones with `--archived`
 fn list_users(config: &Config) -> Result<(), ProjectError> {
-    // Read user_map
-    let user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
-
-    // If stock_map is empty, tell the user and end short
-    if user_map.is_empty() {
-        println!("No users created.");
-        return Ok(())
-    }
+    // Read user_map, or the archived users with `--archived`
+    let archived = config.flag("archived").is_some();
+    let user_map: HashMap<String, User> = match config.archive_map_path() {
+        path if archived && path.exists() => read_from_hashmap(&path)?,
+        _ if archived => HashMap::new(),
+        _ => read_from_hashmap(&config.user_map_path())?,
+    };
 
     // Sort the HashMap by key
     let list: BTreeMap<String, User> = user_map.into_iter().collect();
+    let records: Vec<UserRecord> = list.values().map(UserRecord::from).collect();
 
-    println!("List of users:");
-    for (_, user) in list {
-        println!("{}", user);
-    }
+    output::print_records(config, &records, || {
+        // If user_map is empty, tell the user and end short
+        if list.is_empty() {
+            println!("{}", if archived { "No users archived." } else { "No users created." });
+            return
+        }
 
-    Ok(())
+        let mut table = Table::new(&["Username", "First name", "Middle initial", "Last name", "Role"]);
+        for user in &records {
+            table.push(vec![
+                Cell::text(user.username.as_str()),
+                Cell::text(user.first_name.as_str()),
+                Cell::text(user.middle_initial.as_str()),
+                Cell::text(user.last_name.as_str()),
+                Cell::text(user.role.as_str()),
+            ]);
+        }
+        table.print();
+    })
+}
+
+
+/// The `show_user` function shows every detail of a user, including those `list-users` leaves out.
+fn show_user(config: &Config) -> Result<(), ProjectError> {
+    let username = &config.remainder[0];
+    let user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    let user = user_map.get(username).ok_or_else(|| HashMapKeyNotFoundError(String::from(username)))?;
+    let record = UserDetailRecord::from(user);
+
+    output::print_record(config, &record, || {
+        let optional = |x: &Option<String>| x.clone().unwrap_or_else(|| String::from("-"));
+        let time = |x: Option<u64>| x.map_or(String::from("unknown"), |x| format!("{} UTC", audit::format_timestamp(x)));
+        let details = [
+            ("Username", record.username.clone()),
+            ("Name", user.full_name()),
+            ("Role", record.role.clone()),
+            ("Email", optional(&record.email)),
+            ("Base currency", optional(&record.base_currency)),
+            ("Risk tolerance", optional(&record.risk_tolerance)),
+            ("Tax jurisdiction", optional(&record.tax_jurisdiction)),
+            ("Accounts", record.accounts.clone()),
+            ("Password", String::from(if record.has_password { "set" } else { "none" })),
+            ("Created", time(record.created)),
+            ("Updated", time(record.updated)),
+        ];
+        for (label, value) in details {
+            println!("{:<18}{}", format!("{}:", label), value);
+        }
+    })
 }
 
-
-/// The `create_stock` function opens the StockMap and inserts a new stock.
+/// The `create_stock` function opens the StockMap and inserts a new stock with the company name and value given by
+/// `--name` and `--value`. Any not given are asked for when run from a terminal.
 fn create_stock(config: &Config) -> Result<(), ProjectError> {
     let stock_id = &config.remainder[0];
+    validate::ticker(stock_id)?;
+
+    // Preliminary check if stock is free, so details are not asked for in vain
+    if read_from_hashmap::<PathBuf, Stock>(&config.stock_map_path())?.contains_key(stock_id) {
+        return Err(HashMapInsertError(String::from(stock_id)))
+    }
+
+    // Details
+    let placeholder = Stock::new_from_ticker(stock_id).map_err(|_| StockNewError)?;
+    let company_name: String = detail(config, "name", &format!("Company name of {}", stock_id), placeholder.company_name)?;
+    let value: f64 = detail(config, "value", &format!("Value of one share of {}", stock_id), placeholder.value)?;
+    validate::company_name(&company_name)?;
+    validate::value(value)?;
+    let stock = Stock::new_from_details(stock_id, &company_name, value).map_err(|_| StockNewError)?;
 
     let f = |hashmap: &mut HashMap<String, Stock>| {
-        hashmap.try_insert(String::from(stock_id), Stock::new_from_ticker(stock_id).map_err(|_| StockNewError)?)
+        hashmap.try_insert(String::from(stock_id), stock.clone())
         .map_or_else(|_| Err(HashMapInsertError(String::from(stock_id))), |_| Ok(()))
     };
 
     modify_hashmap(&config.stock_map_path(), f)?;
-    notify(&format!("Stock {} has been added.", stock_id));
+    notify(config, &format!("Stock {} has been added.", stock_id));
     Ok(())
 }
 
@@ -503,26 +1312,17 @@ fn delete_stock(config: &Config) -> Result<(), ProjectError>{
 
 
     // Make sure the user wants to delete
-    println!("Are you sure you want to delete stock {}", stock_id.to_string());
-    let mut ans = String::new();
-    io::stdin().read_line(&mut ans).map_err(|_| UserNewError)?;
-    let ans = ans.trim(); // Remove the newline
-    match ans.to_lowercase().as_str() {
-        // In the case where the user is sure
-        "y" | "yes" => {
-            let f = |hashmap: &mut HashMap<String, Stock>| hashmap
-                .remove(&stock_id.to_string()) // Remove
-                .ok_or_else(|| HashMapRemoveError(stock_id.to_string())).map(|_| ()); // Handle Option -> Result & discarding User
-            modify_hashmap(&config.stock_map_path(), f)?;
-        },
-        // In the case where the user declines
-        "q" | "quit" | "n" | "no" => return Ok(()),
-        // In the case where the user input is not recognized
-        _ => return Err(InvalidInputError),
+    if !confirm_delete(config, &format!("Are you sure you want to delete stock {}", stock_id))? {
+        return Ok(())
     }
 
+    let f = |hashmap: &mut HashMap<String, Stock>| hashmap
+        .remove(&stock_id.to_string()) // Remove
+        .ok_or_else(|| HashMapRemoveError(stock_id.to_string())).map(|_| ()); // Handle Option -> Result & discarding User
+    modify_hashmap(&config.stock_map_path(), f)?;
+
     // Closeout
-    notify(&format!("Stock {} has been deleted.", stock_id));
+    notify(config, &format!("Stock {} has been deleted.", stock_id));
     Ok(())
 }
 
@@ -554,6 +1354,7 @@ fn edit_stock(config: &Config) -> Result<(), ProjectError> {
 
     match stock.get_property(&property)? {
         stock::Property::Ticker(x) => { // Must be a `String`
+            validate::ticker(&value)?;
             let ticker = x;
             *ticker = value.clone();
             // We will need to update the stock_id as a key
@@ -563,6 +1364,7 @@ fn edit_stock(config: &Config) -> Result<(), ProjectError> {
             note = format!("Stock {} changed to {}.", stock_id, ticker);
         },
         stock::Property::CompanyName(x) => { // Must be a `String`
+            validate::company_name(&value)?;
             let company_name = x;
             *company_name = value;
             note = format!("Stock {} changed to {}.", stock_id, company_name);
@@ -570,12 +1372,16 @@ fn edit_stock(config: &Config) -> Result<(), ProjectError> {
         stock::Property::Value(x) => { // Must be a `f64`
             let stock_value = x;
             let value = parse_or_err::<f64>(&value)?; // Convert `value` to `f64`
+            validate::value(value)?;
             *stock_value = value;
             note = format!("Stock {} changed to {}.", stock_id, stock_value);
         },
     };
 
-    // Remove old entry from HashMap if necessary
+    // Remove old entry from HashMap if necessary, without replacing another stock
+    if update_stock_id && new_stock_id != *stock_id && stock_map.contains_key(&new_stock_id) {
+        return Err(HashMapInsertError(new_stock_id))
+    }
     if update_stock_id {
         let stock = stock_map.remove(stock_id).unwrap(); // We can be confident this is `Some`
         stock_map.insert(new_stock_id, stock);
@@ -585,7 +1391,7 @@ fn edit_stock(config: &Config) -> Result<(), ProjectError> {
     write_to_hashmap(&config.stock_map_path(), &stock_map)?;
 
     // Notify success
-    notify(&note);
+    notify(config, &note);
 
     Ok(())
 }
@@ -595,21 +1401,27 @@ fn list_stocks(config: &Config) -> Result<(), ProjectError> {
     // Read stock_map
     let stock_map: HashMap<String, Stock> = read_from_hashmap(&config.stock_map_path())?;
 
-    // If stock_map is empty, tell the user and end short
-    if stock_map.is_empty() {
-        println!("No stocks created.");
-        return Ok(())
-    }
-
     // Sort the HashMap by key
     let list: BTreeMap<String, Stock> = stock_map.into_iter().collect();
+    let records: Vec<StockRecord> = list.values().map(StockRecord::from).collect();
 
-    println!("List of stocks:");
-    for (_, stock) in list {
-        println!("{}", stock);
-    }
+    output::print_records(config, &records, || {
+        // If stock_map is empty, tell the user and end short
+        if list.is_empty() {
+            println!("No stocks created.");
+            return
+        }
 
-    Ok(())
+        let mut table = Table::new(&["Ticker", "Company", "Value"]);
+        for stock in list.values() {
+            table.push(vec![
+                Cell::text(stock.ticker.as_str()),
+                Cell::text(stock.company_name.as_str()),
+                Cell::number(config.settings.format_money(stock.value)),
+            ]);
+        }
+        table.print();
+    })
 }
 
 /// The `buy_stock` function takes a stock ticker id and a quantity (in that order) and adds the quantity of purchased stocks
@@ -617,7 +1429,8 @@ fn list_stocks(config: &Config) -> Result<(), ProjectError> {
 fn buy_stock(config: &Config) -> Result<(), ProjectError>{
     
     // Check user is logged in first
-    let username = match State::init(&config)?.current_user {
+    let state = State::init(config)?;
+    let username = match state.current_user.clone() {
         Some(x) => x,
         None => return Err(StateNoUserError),
     };
@@ -637,64 +1450,731 @@ fn buy_stock(config: &Config) -> Result<(), ProjectError>{
     // Check availability of user and retrieve it if available
     let mut user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
     let user = if !user_map.contains_key(&username) {
-        return Err(HashMapKeyNotFoundError(String::from(username)))
+        return Err(HashMapKeyNotFoundError(username))
     } else {
         user_map.get_mut(&username).unwrap() // We can be confident this will be Some()
     };
 
-    // Alter user and write map.
-    user.add_stock(stock, stock_qt)?;
+    // Alter the account and write map.
+    let account = account_name(config, &state, user)?;
+    user.add_stock(&account, stock, stock_qt)?;
     write_to_hashmap(&config.user_map_path(), &user_map)?;
 
     // Closeout
-    notify(&format!("{} shares of stock {} purchased by {}", stock_qt, stock_id, username));
+    notify(config, &format!("{} shares of stock {} purchased by {} in account {}", stock_qt, stock_id, username, account));
     Ok(())
 }
 
 /// The `list_portfolio` function relies on a logged in state and shows the current state of all the logged in user's stocks
 fn list_portfolio(config: &Config) -> Result<(), ProjectError>{
+    let (user, state) = logged_in_user(config)?;
+    let username = user.username();
+
+    // Holdings are valued at the current price in the stock map, and sorted by ticker
+    let stock_map: HashMap<String, Stock> = read_from_hashmap(&config.stock_map_path())?;
+    let all = config.flag("all").is_some();
+    let (account, holdings, cash) = if all {
+        (String::from("all"), account::consolidate(user.accounts.values()), user.accounts.values().map(|x| x.cash).sum())
+    } else {
+        let name = account_name(config, &state, &user)?;
+        let account = user.account(&name)?;
+        let holdings: BTreeMap<String, StockUnit> = account.holdings.clone().into_iter().collect();
+        (name, holdings, account.cash)
+    };
+    let records: Vec<HoldingRecord> = holdings.values()
+        .map(|stock_unit| HoldingRecord::new(username, &account, stock_unit, stock_map.get(&stock_unit.stock.ticker)))
+        .collect();
+
+    output::print_records(config, &records, || {
+        if all {
+            println!("All accounts of {} have:", username);
+        } else {
+            println!("Account {} of {} has:", account, username);
+        }
+
+        if holdings.is_empty() {
+            println!("No holdings");
+        } else {
+            let mut table = Table::new(&["Ticker", "Company", "Shares", "Price", "Value", "Cost", "Gain/Loss"]);
+            for holding in &records {
+                table.push(vec![
+                    Cell::text(holding.ticker.as_str()),
+                    Cell::text(holding.company_name.as_str()),
+                    Cell::number(holding.quantity.to_string()),
+                    Cell::number(config.settings.format_money(holding.price)),
+                    Cell::number(config.settings.format_money(holding.market_value)),
+                    Cell::number(config.settings.format_money(holding.cost_basis)),
+                    Cell::change(config.settings.format_money(holding.gain), holding.gain),
+                ]);
+            }
+            table.print();
+        }
+        println!("Cash: {}", config.settings.format_money(cash));
+    })
+}
+
+/// The `create_account` function opens an account for the logged in user with the name given, starting with the cash
+/// given by `--cash` (or none).
+fn create_account(config: &Config) -> Result<(), ProjectError> {
     let username = match State::init(config)?.current_user {
         Some(x) => x,
         None => return Err(StateNoUserError),
     };
+    let name = &config.remainder[0];
+    let cash = match config.flag("cash") {
+        Some(x) => parse_or_err::<f64>(&x.to_string())?,
+        None => 0.0,
+    };
+
+    let mut user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    let user = user_map.get_mut(&username).ok_or_else(|| HashMapKeyNotFoundError(username.clone()))?;
+    user.create_account(name, cash)?;
+    write_to_hashmap(&config.user_map_path(), &user_map)?;
+
+    notify(config, &format!("Account {} opened for {} with {} in cash.", name, username, config.settings.format_money(cash)));
+    Ok(())
+}
+
+/// The `list_accounts` function shows each account of the logged in user with its cash and the value of its holdings,
+/// followed by the total of every account.
+fn list_accounts(config: &Config) -> Result<(), ProjectError> {
+    let (user, state) = logged_in_user(config)?;
+    let current = account_name(config, &state, &user)?;
+
+    let stock_map: HashMap<String, Stock> = read_from_hashmap(&config.stock_map_path())?;
+    let records: Vec<AccountRecord> = user.accounts.iter()
+        .map(|(name, account)| {
+            let market_value = account.market_value(&stock_map);
+            AccountRecord {
+                username: String::from(user.username()),
+                account: name.clone(),
+                cash: account.cash,
+                market_value,
+                total: account.cash + market_value,
+                current: *name == current,
+            }
+        })
+        .collect();
+
+    output::print_records(config, &records, || {
+        let money = |x: f64| Cell::number(config.settings.format_money(x));
+        let mut table = Table::new(&["Current", "Account", "Cash", "Holdings", "Total"]);
+        for account in &records {
+            let marker = if account.current { "*" } else { "" };
+            table.push(vec![
+                Cell::text(marker),
+                Cell::text(account.account.as_str()),
+                money(account.cash),
+                money(account.market_value),
+                money(account.total),
+            ]);
+        }
+        table.push(vec![
+            Cell::text(""),
+            Cell::text("All accounts"),
+            money(records.iter().map(|x| x.cash).sum()),
+            money(records.iter().map(|x| x.market_value).sum()),
+            money(records.iter().map(|x| x.total).sum()),
+        ]);
+        table.print();
+    })
+}
+
+/// The `switch_account` function makes the account given the one `buy-stock` and `list-portfolio` apply to, until the
+/// user logs out.
+fn switch_account(config: &Config) -> Result<(), ProjectError> {
+    let (user, mut state) = logged_in_user(config)?;
+    let name = &config.remainder[0];
+    user.account(name)?;
+
+    state.current_account = Some(name.clone());
+    state.write(config)?;
+    notify(config, &format!("Switched to account {}.", name));
+    Ok(())
+}
+
+/// The `create_group` function creates a group of users, such as a household, with the users given as its members.
+fn create_group(config: &Config) -> Result<(), ProjectError> {
+    let name = &config.remainder[0];
+    group::validate_name(name)?;
 
     let user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
-    let user = if !user_map.contains_key(&username) {
-        return Err(HashMapKeyNotFoundError(String::from(username)))
-    } else {
-        user_map.get(&username).unwrap() // We can be confident this will be Some()
-    };
+    let mut group = Group::default();
+    for username in &config.remainder[1..] {
+        group.add(name, username, &user_map)?;
+    }
+
+    let mut group_map = read_group_map(config)?;
+    if group_map.contains_key(name) {
+        return Err(GroupExistsError(name.clone()))
+    }
+    let count = group.members.len();
+    group_map.insert(name.clone(), group);
+    write_to_hashmap(&config.group_map_path(), &group_map)?;
+
+    notify(config, &format!("Group {} created with {} member(s).", name, count));
+    Ok(())
+}
+
+/// The `delete_group` function queries the user for a confirmation and deletes a group. Its members are not affected.
+fn delete_group(config: &Config) -> Result<(), ProjectError> {
+    let name = &config.remainder[0];
+
+    let mut group_map = read_group_map(config)?;
+    if !group_map.contains_key(name) {
+        return Err(GroupNotFoundError(name.clone()))
+    }
+    if !confirm_delete(config, &format!("Are you sure you want to delete group {}", name))? {
+        return Ok(())
+    }
 
-    println!("User profile {} has:", username);
+    group_map.remove(name);
+    write_to_hashmap(&config.group_map_path(), &group_map)?;
 
-    for (_, stock_unit) in match &user.portfolio {
-        Some(x) => x.iter(),
-        None => {
+    notify(config, &format!("Group {} deleted.", name));
+    Ok(())
+}
+
+/// The `add_to_group` function adds the users given to a group. Nothing is added if any of them does not exist or is
+/// already a member.
+fn add_to_group(config: &Config) -> Result<(), ProjectError> {
+    let name = &config.remainder[0];
+
+    let user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    let mut group_map = read_group_map(config)?;
+    let group = group_map.get_mut(name).ok_or_else(|| GroupNotFoundError(name.clone()))?;
+    for username in &config.remainder[1..] {
+        group.add(name, username, &user_map)?;
+    }
+    write_to_hashmap(&config.group_map_path(), &group_map)?;
+
+    notify(config, &format!("Added {} to group {}.", config.remainder[1..].join(", "), name));
+    Ok(())
+}
+
+/// The `remove_from_group` function removes the users given from a group. Nothing is removed if any of them is not a
+/// member.
+fn remove_from_group(config: &Config) -> Result<(), ProjectError> {
+    let name = &config.remainder[0];
+
+    let mut group_map = read_group_map(config)?;
+    let group = group_map.get_mut(name).ok_or_else(|| GroupNotFoundError(name.clone()))?;
+    for username in &config.remainder[1..] {
+        group.remove(name, username)?;
+    }
+    write_to_hashmap(&config.group_map_path(), &group_map)?;
+
+    notify(config, &format!("Removed {} from group {}.", config.remainder[1..].join(", "), name));
+    Ok(())
+}
+
+/// The `list_groups` function lists every group and its members.
+fn list_groups(config: &Config) -> Result<(), ProjectError> {
+    let group_map: BTreeMap<String, Group> = read_group_map(config)?.into_iter().collect();
+    let records: Vec<GroupRecord> = group_map.iter()
+        .map(|(name, group)| GroupRecord {
+            group: name.clone(),
+            members: group.members.iter().cloned().collect::<Vec<_>>().join(" "),
+        })
+        .collect();
+
+    output::print_records(config, &records, || {
+        if records.is_empty() {
+            println!("No groups created.");
+            return
+        }
+
+        let mut table = Table::new(&["Group", "Members"]);
+        for (name, group) in &group_map {
+            table.push(vec![
+                Cell::text(name.as_str()),
+                Cell::text(group.members.iter().cloned().collect::<Vec<_>>().join(", ")),
+            ]);
+        }
+        table.print();
+    })
+}
+
+/// The `group_portfolio` function combines the holdings of every account of each member of a group by ticker, valued
+/// at the current prices in the stock map. Each ticker is listed with the holding of each member followed by the
+/// holding of every member combined, and the cash and value of each member are totalled after. Only members of the
+/// group and administrators may view it.
+fn group_portfolio(config: &Config) -> Result<(), ProjectError> {
+    let name = &config.remainder[0];
+
+    let group = read_group_map(config)?.remove(name).ok_or_else(|| GroupNotFoundError(name.clone()))?;
+    let user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+
+    let stock_map: HashMap<String, Stock> = read_from_hashmap(&config.stock_map_path())?;
+    let holdings = group.holdings(&user_map);
+    let total_value: f64 = holdings.values()
+        .filter_map(group::total)
+        .map(|x| HoldingRecord::new(name, "all", &x, stock_map.get(&x.stock.ticker)).market_value)
+        .sum();
+
+    let mut records: Vec<GroupHoldingRecord> = Vec::new();
+    for members in holdings.values() {
+        for (member, stock_unit) in members {
+            records.push(GroupHoldingRecord::new(name, member, stock_unit, stock_map.get(&stock_unit.stock.ticker), total_value));
+        }
+        if let Some(x) = group::total(members) {
+            records.push(GroupHoldingRecord::new(name, "all", &x, stock_map.get(&x.stock.ticker), total_value));
+        }
+    }
+
+    output::print_records(config, &records, || {
+        let money = |x: f64| Cell::number(config.settings.format_money(x));
+        let members: Vec<String> = group.members.iter().cloned().collect();
+        println!("Group {} ({}) holds:", name, members.join(", "));
+
+        if records.is_empty() {
             println!("No holdings");
-            return Ok(())
-        },
-    } {
-        println!("{}: {} shares", stock_unit.stock.ticker, stock_unit.quantity);
+        } else {
+            let mut table = Table::new(&["Ticker", "Company", "Member", "Shares", "Price", "Value", "Cost", "Gain/Loss", "Weight"]);
+            for holding in &records {
+                table.push(vec![
+                    Cell::text(holding.ticker.as_str()),
+                    Cell::text(holding.company_name.as_str()),
+                    Cell::text(holding.member.as_str()),
+                    Cell::number(holding.quantity.to_string()),
+                    money(holding.price),
+                    money(holding.market_value),
+                    money(holding.cost_basis),
+                    Cell::change(config.settings.format_money(holding.gain), holding.gain),
+                    Cell::number(format!("{:.1}%", holding.weight * 100.0)),
+                ]);
+            }
+            table.print();
+        }
+
+        // The cash and value of each member, and of the group
+        let cash = group.cash(&user_map);
+        let values: Vec<f64> = cash.keys()
+            .map(|member| records.iter().filter(|x| x.member == *member).map(|x| x.market_value).sum())
+            .collect();
+        let mut table = Table::new(&["Member", "Cash", "Holdings", "Total"]);
+        for ((member, cash), value) in cash.iter().zip(&values) {
+            table.push(vec![Cell::text(member.as_str()), money(*cash), money(*value), money(cash + value)]);
+        }
+        let total_cash: f64 = cash.values().sum();
+        table.push(vec![Cell::text("All members"), money(total_cash), money(total_value), money(total_cash + total_value)]);
+        table.print();
+    })
+}
+
+/// The `undo` function restores the data from before the last mutating command, or the last `N` if a count is given.
+/// Members may only undo commands they ran themselves.
+fn undo(config: &Config) -> Result<(), ProjectError> {
+    let n = match config.remainder.first() {
+        Some(x) => parse_or_err::<usize>(x)?,
+        None => 1,
+    };
+
+    for command in Journal::open(config)?.undo(config, n, own_commands_only(config)?.as_deref())? {
+        notify(config, &format!("Undid \"{}\".", command));
+    }
+    Ok(())
+}
+
+/// The `redo` function reapplies the last undone mutating command, or the last `N` if a count is given.
+fn redo(config: &Config) -> Result<(), ProjectError> {
+    let n = match config.remainder.first() {
+        Some(x) => parse_or_err::<usize>(x)?,
+        None => 1,
+    };
+
+    for command in Journal::open(config)?.redo(config, n, own_commands_only(config)?.as_deref())? {
+        notify(config, &format!("Redid \"{}\".", command));
+    }
+    Ok(())
+}
+
+/// The `audit` function shows the entries of the audit log, oldest first, limited to those matching the `--user`,
+/// `--since` and `--command` options given.
+fn audit(config: &Config) -> Result<(), ProjectError> {
+    let command = match config.flag("command").and_then(FlagValue::as_str) {
+        Some(x) => Some(CommandSpec::find(x).ok_or(CommandInvalidError)?.name.to_string()),
+        None => None,
+    };
+    let filter = AuditFilter {
+        user: config.flag("user").and_then(FlagValue::as_str).map(String::from),
+        since: config.flag("since").and_then(FlagValue::as_str).map(|x| audit::parse_since(x, now())).transpose()?,
+        command,
+    };
+
+    let records: Vec<AuditRecord> = audit::read(config)?.iter()
+        .filter(|x| filter.matches(x))
+        .map(AuditRecord::from)
+        .collect();
+
+    output::print_records(config, &records, || {
+        let mut table = Table::new(&["Time (UTC)", "User", "Command", "Arguments", "Changes"]);
+        for record in &records {
+            table.push(vec![
+                Cell::text(audit::format_timestamp(record.timestamp)),
+                Cell::text(record.user.as_str()),
+                Cell::text(record.command.as_str()),
+                Cell::text(record.arguments.as_str()),
+                Cell::text(record.changes.as_str()),
+            ]);
+        }
+        table.print();
+    })
+}
+
+/// The `encrypt` function encrypts the user map, stock map, state and journal with a new passphrase. Every command
+/// will then ask for the passphrase (or read it from `RUST_STOCK_TRACKER_PASSPHRASE`) before running.
+fn encrypt(config: &Config) -> Result<(), ProjectError> {
+    if crypto::is_encrypted(config) {
+        return Err(AlreadyEncryptedError)
+    }
+
+    let passphrase = match env::var(crypto::PASSPHRASE_VARIABLE) {
+        Ok(x) => x,
+        Err(_) => crypto::read_new_passphrase(config)?,
+    };
+
+    crypto::enable(config, &passphrase)?;
+    notify(config, "Data encrypted.");
+    Ok(())
+}
+
+/// The `decrypt` function permanently removes encryption from the data files.
+fn decrypt(config: &Config) -> Result<(), ProjectError> {
+    crypto::disable(config)?;
+    notify(config, "Data decrypted.");
+    Ok(())
+}
+
+/// The `change_passphrase` function re-encrypts the data files with a new passphrase, which is read from
+/// `RUST_STOCK_TRACKER_NEW_PASSPHRASE` if set.
+fn change_passphrase(config: &Config) -> Result<(), ProjectError> {
+    if !crypto::is_encrypted(config) {
+        return Err(NotEncryptedError)
+    }
+
+    let passphrase = match env::var(crypto::NEW_PASSPHRASE_VARIABLE) {
+        Ok(x) => x,
+        Err(_) => crypto::read_new_passphrase(config)?,
+    };
+
+    crypto::change_passphrase(config, &passphrase)?;
+    notify(config, "Passphrase changed.");
+    Ok(())
+}
+
+/// The `list_profiles` function lists every profile and its configuration directory, marking the active profile.
+fn list_profiles(config: &Config) -> Result<(), ProjectError> {
+    let profiles = Profiles::open(&config.root_directory)?;
+    let records: Vec<ProfileRecord> = profiles.list(&config.root_directory).into_iter()
+        .map(|(name, directory)| ProfileRecord {
+            active: name == profiles.active(),
+            name,
+            directory: directory.display().to_string(),
+        })
+        .collect();
+
+    output::print_records(config, &records, || {
+        let mut table = Table::new(&["Active", "Profile", "Directory"]);
+        for profile in &records {
+            let marker = if profile.active { "*" } else { "" };
+            table.push(vec![Cell::text(marker), Cell::text(profile.name.as_str()), Cell::text(profile.directory.as_str())]);
+        }
+        table.print();
+    })
+}
+
+/// The `create_profile` function registers a new profile, optionally kept in the directory given after its name.
+fn create_profile(config: &Config) -> Result<(), ProjectError> {
+    let name = &config.remainder[0];
+    // A directory given relative to where the command was run is registered absolute, so it is found from anywhere
+    let directory = match config.remainder.get(1) {
+        Some(x) => Some(std::path::absolute(x).map_err(|_| InputParseError(x.clone(), String::from("a directory")))?),
+        None => None,
+    };
+
+    let mut profiles = Profiles::open(&config.root_directory)?;
+    let directory = profiles.create(&config.root_directory, name, directory)?;
+    fs::create_dir_all(&directory).map_err(|_| ConfigCreateDirectoryError(directory.clone()))?;
+    profiles.write(&config.root_directory)?;
+
+    notify(config, &format!("Profile {} has been created at {}.", name, directory.display()));
+    Ok(())
+}
+
+/// The `switch_profile` function sets the profile used by later calls when no `--profile` option is given.
+fn switch_profile(config: &Config) -> Result<(), ProjectError> {
+    let name = &config.remainder[0];
+
+    let mut profiles = Profiles::open(&config.root_directory)?;
+    profiles.switch(name)?;
+    profiles.write(&config.root_directory)?;
+
+    notify(config, &format!("Switched to profile {}.", name));
+    Ok(())
+}
+
+/// The `delete_profile` function queries the user for a confirmation and unregisters a profile. Its data is removed
+/// too, unless it was created in a directory of the user's choosing.
+fn delete_profile(config: &Config) -> Result<(), ProjectError> {
+    let name = &config.remainder[0];
+
+    let mut profiles = Profiles::open(&config.root_directory)?;
+    let directory = profiles.delete(name)?;
+
+    // Make sure the user wants to delete
+    if !confirm_delete(config, &format!("Are you sure you want to delete profile {} and its data", name))? {
+        return Ok(())
+    }
+    profiles.write(&config.root_directory)?;
+
+    if directory == Profiles::managed_directory(&config.root_directory, name) && directory.exists() {
+        fs::remove_dir_all(&directory).map_err(|_| ConfigCreateDirectoryError(directory.clone()))?;
+        notify(config, &format!("Profile {} has been deleted.", name));
+    } else {
+        notify(config, &format!("Profile {} has been deleted, its data remains at {}.", name, directory.display()));
     }
+    Ok(())
+}
+
+/// The `get_setting` function shows the value of a setting, including any override given on the command line.
+fn get_setting(config: &Config) -> Result<(), ProjectError> {
+    let record = SettingRecord { key: config.remainder[0].clone(), value: config.settings.get(&config.remainder[0])? };
+    output::print_record(config, &record, || println!("{}", record.value))
+}
 
+/// The `set_setting` function changes a setting in the settings file of the configuration directory.
+fn set_setting(config: &Config) -> Result<(), ProjectError> {
+    let key = &config.remainder[0];
+    let value = &config.remainder[1];
+
+    // Start from the file rather than `config.settings` so command-line overrides are not saved
+    let mut settings = Settings::load(&config.configuration_directory)?;
+    settings.set(key, value)?;
+    settings.write(&config.configuration_directory)?;
+
+    notify(config, &format!("Setting {} changed to {}.", key, settings.get(key)?));
     Ok(())
 }
 
+/// The `list_settings` function lists every setting and its value.
+fn list_settings(config: &Config) -> Result<(), ProjectError> {
+    let records: Vec<SettingRecord> = config.settings.list()?.into_iter()
+        .map(|(key, value)| SettingRecord { key: String::from(key), value })
+        .collect();
+
+    output::print_records(config, &records, || {
+        let mut table = Table::new(&["Setting", "Value"]);
+        for setting in &records {
+            table.push(vec![Cell::text(setting.key.as_str()), Cell::text(setting.value.as_str())]);
+        }
+        table.print();
+    })
+}
+
 //
 // Assistive functions
 //
 
+/// The location a file at `path` is written to before it replaces the original
+pub(crate) fn temporary_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|x| x.to_os_string()).unwrap_or_default();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// The `replace_file` function writes `contents` beside the file at `path` and then renames it into place, so that a
+/// failure while writing leaves the original as it was rather than truncated.
+pub(crate) fn replace_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temporary = temporary_path(path);
+    fs::write(&temporary, contents).and_then(|_| fs::rename(&temporary, path)).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary);
+    })
+}
+
+/// The `replace_files` function replaces several files together: every one is written beside its original before any
+/// is renamed into place, so that a failure while writing leaves them all as they were.
+pub(crate) fn replace_files(files: &[(PathBuf, Vec<u8>)]) -> Result<(), ProjectError> {
+    for (path, contents) in files {
+        if fs::write(temporary_path(path), contents).is_err() {
+            for (path, _) in files {
+                let _ = fs::remove_file(temporary_path(path));
+            }
+            return Err(IOHashMapWriteError(path.clone()))
+        }
+    }
+    for (path, _) in files {
+        fs::rename(temporary_path(path), path).map_err(|_| IOHashMapWriteError(path.clone()))?;
+    }
+    Ok(())
+}
+
+/// The `confirm_delete` function asks the user `question` through the `Config`'s prompt and returns whether they
+/// answered yes. When the `confirm-delete` setting is off or `--yes` was given, the question is skipped and the answer
+/// is taken to be yes.
+fn confirm_delete(config: &Config, question: &str) -> Result<bool, ProjectError> {
+    if !config.settings.confirm_delete {
+        return Ok(true)
+    }
+    confirm(config, question)
+}
+
+/// The `confirm` function is like `confirm_delete`, but asks whatever the `confirm-delete` setting, for deletions
+/// which lose data that cannot be recovered.
+fn confirm(config: &Config, question: &str) -> Result<bool, ProjectError> {
+    if config.flag("yes").is_some() {
+        return Ok(true)
+    }
+
+    prompt::confirm(&mut *config.prompt.borrow_mut(), question)
+}
+
+/// The `export_holdings` function writes the holdings of every account of `user` to a CSV file in the exports
+/// directory, named after the user and the time, and returns its location. The file is encrypted like the data files
+/// when encryption is enabled.
+fn export_holdings(config: &Config, user: &User) -> Result<PathBuf, ProjectError> {
+    let stock_map: HashMap<String, Stock> = read_from_hashmap(&config.stock_map_path())?;
+    let mut records = Vec::new();
+    for (name, account) in &user.accounts {
+        let holdings: BTreeMap<&String, &StockUnit> = account.holdings.iter().collect();
+        for stock_unit in holdings.values() {
+            records.push(HoldingRecord::new(user.username(), name, stock_unit, stock_map.get(&stock_unit.stock.ticker)));
+        }
+    }
+
+    let directory = config.exports_path();
+    fs::create_dir_all(&directory).map_err(|_| ConfigCreateDirectoryError(directory.clone()))?;
+    let path = directory.join(format!("{}-{}.csv", user.username(), now()));
+    let contents = crypto::seal_in(&config.configuration_directory, output::render_csv(&records)?.into_bytes())?;
+    fs::write(&path, contents).map_err(|_| IOExportWriteError(path.clone()))?;
+    Ok(path)
+}
+
+/// The `detail` function returns the value of flag `name` if it was given. Otherwise, when run from a terminal, it asks
+/// `question` until the answer can be parsed, with a blank answer (or no terminal) giving `default`.
+fn detail<T, D>(config: &Config, name: &str, question: &str, default: D) -> Result<T, ProjectError> where
+    T: std::str::FromStr,
+    D: Into<T> + std::fmt::Display, {
+    if let Some(x) = config.flag(name) {
+        return parse_or_err(&x.to_string())
+    }
+    prompt::ask_until_parsed(&mut *config.prompt.borrow_mut(), question, default, |x| notify(config, &x.to_string()))
+}
+
+/// Asks for a new password twice, returning a `PasswordMismatchError` if the two differ
+fn read_new_password(config: &Config) -> Result<String, ProjectError> {
+    let password = config.prompt.borrow_mut().ask_secret("New password: ")?;
+    if config.prompt.borrow_mut().ask_secret("Confirm password: ")? != password {
+        return Err(PasswordMismatchError)
+    }
+    Ok(password)
+}
+
+/// Returns how long a session may be idle, in seconds, or `None` if the session-timeout setting is 0
+fn session_timeout(config: &Config) -> Result<Option<u64>, ProjectError> {
+    // Read from the settings file rather than `config.settings`, so that no command line can extend a session
+    match Settings::load(&config.configuration_directory)?.session_timeout {
+        0 => Ok(None),
+        minutes => Ok(Some(minutes.saturating_mul(60))),
+    }
+}
+
+/// Formats a number of seconds as e.g. "1h 5m", "5m 12s" or "42s"
+fn format_duration(seconds: u64) -> String {
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, _) => format!("{}h {}m", h, m),
+    }
+}
+
+/// Reads the group map, which is empty if no group has been created yet
+pub(crate) fn read_group_map(config: &Config) -> Result<HashMap<String, Group>, ProjectError> {
+    match config.group_map_path() {
+        path if path.exists() => read_from_hashmap(&path),
+        _ => Ok(HashMap::new()),
+    }
+}
+
+/// Returns the user whose commands alone may be undone and redone, which is the logged in user unless they are an
+/// administrator, or nobody is, in which case any command may be
+fn own_commands_only(config: &Config) -> Result<Option<String>, ProjectError> {
+    if !config.user_map_path().exists() {
+        return Ok(None)
+    }
+    let user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    match State::init(config)?.current_user.and_then(|x| user_map.get(&x)) {
+        Some(user) if user.role() != Role::Admin && permission::has_admin(&user_map) => Ok(Some(String::from(user.username()))),
+        _ => Ok(None),
+    }
+}
+
+/// Returns the logged in `User` along with the `State`, or a `StateNoUserError` if nobody is logged in
+fn logged_in_user(config: &Config) -> Result<(User, State), ProjectError> {
+    let state = State::init(config)?;
+    let username = match &state.current_user {
+        Some(x) => x.clone(),
+        None => return Err(StateNoUserError),
+    };
+
+    let mut user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path())?;
+    match user_map.remove(&username) {
+        Some(user) => Ok((user, state)),
+        None => Err(HashMapKeyNotFoundError(username)),
+    }
+}
+
+/// Returns the name of the account of `user` a command applies to: the one given by `--account`, else the one chosen
+/// by `account switch`, else the default account. Returns an `AccountNotFoundError` if there is no such account.
+fn account_name(config: &Config, state: &State, user: &User) -> Result<String, ProjectError> {
+    let name = match config.flag("account") {
+        Some(x) => x.to_string(),
+        None => state.current_account.clone().unwrap_or_else(|| String::from(DEFAULT_ACCOUNT)),
+    };
+    user.account(&name)?;
+    Ok(name)
+}
+
+/// The current time in seconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs())
+}
+
 /// The `notify` function is a simple function that prints the `&str` `s` to the screen. The puropose of this
 /// function is to centralize functions that need to print a small notification message to the screen, such
 /// that if the procedure of this behavior is to be changed in the future - it can be modified in one place.
-fn notify(s: &str) {
-    println!("{}",s);
+/// Notifications go to standard error when structured output is selected, so they are not mistaken for output, and
+/// are left out entirely with `--quiet`.
+fn notify(config: &Config, s: &str) {
+    if config.flag("quiet").is_some() {
+        return
+    }
+    output::notify(config.settings.format, s);
 }
 
 /// The `parse_or_err<T>()` function is a simple wrapper function that will map the error output to a `ProjectError`
 /// of the right type.
 fn parse_or_err<T>(s: &String) -> Result<T, ProjectError> where T: std::str::FromStr {
-    s.parse().map_err(|_| InputParseError(String::from(s), format!("{}", std::any::type_name::<T>())))
+    s.parse().map_err(|_| InputParseError(String::from(s), std::any::type_name::<T>().to_string()))
+}
+
+/// Returns the arguments and options of the command of `config` as they are recorded in the audit log. Global options
+/// such as `--yes` are left out, as they do not change what the command does.
+fn audit_arguments(config: &Config) -> Vec<String> {
+    let mut arguments = config.remainder.clone();
+    for (name, value) in &config.flags {
+        if !config.command.spec().flags.iter().any(|x| x.name == name) {
+            continue
+        }
+        arguments.push(format!("--{}", name));
+        match value {
+            FlagValue::Switch => (),
+            FlagValue::Text(x) => arguments.push(x.clone()),
+            FlagValue::Integer(x) => arguments.push(x.to_string()),
+            FlagValue::Decimal(x) => arguments.push(x.to_string()),
+        }
+    }
+    arguments
 }
 
 /// The `read_from_hashmap` function takes a `Path` and returns the `HashMap<String, T>` located at that path
@@ -702,14 +2182,14 @@ fn parse_or_err<T>(s: &String) -> Result<T, ProjectError> where T: std::str::Fro
 fn read_from_hashmap<P, T>(path: &P) -> Result<HashMap<String, T>, ProjectError> where
     P: AsRef<Path>,
     T: serde::de::DeserializeOwned, {
-    let file = match fs::File::open(path) {
+    let contents = match fs::read(path) {
         Ok(x) => x,
         Err(_) => return Err(IOHashMapOpenError(PathBuf::from(path.as_ref())))
     };
 
-    let reader = io::BufReader::new(&file);
+    let contents = crypto::open(path.as_ref(), contents)?;
 
-    serde_json::from_reader(reader).map_err(|_| DeserializeJSONError(PathBuf::from(path.as_ref())))
+    serde_json::from_slice(&contents).map_err(|_| DeserializeJSONError(PathBuf::from(path.as_ref())))
 }
 
 /// The 'write_to_hashmap` function takes a `Path` and a `HashMap<String, User>` and writes the
@@ -718,14 +2198,18 @@ fn write_to_hashmap<P, T>(path: &P, hashmap: &HashMap<String, T>) -> Result<(),
     P: AsRef<Path>,
     T: serde::ser::Serialize, {
     
-    let serialized_hashmap = serde_json::to_string(hashmap).map_err(|_| SerializeJSONError)?;
+    let serialized_hashmap = serialize_hashmap(path, hashmap)?;
+    replace_file(path.as_ref(), &serialized_hashmap).map_err(|_| IOHashMapWriteError(PathBuf::from(path.as_ref())))
+}
 
-    let mut file = match fs::File::create(path) {
-        Ok(x) => x,
-        Err(_) => return Err(IOHashMapOpenError(PathBuf::from(path.as_ref()))),
-    };
+/// The `serialize_hashmap` function returns the contents `write_to_hashmap` would write to the file at `path`, sealed
+/// if its configuration directory is encrypted.
+fn serialize_hashmap<P, T>(path: &P, hashmap: &HashMap<String, T>) -> Result<Vec<u8>, ProjectError> where
+    P: AsRef<Path>,
+    T: serde::ser::Serialize, {
 
-    file.write_all(serialized_hashmap.as_bytes()).map_err(|_| IOHashMapWriteError(PathBuf::from(path.as_ref())))
+    let serialized_hashmap = serde_json::to_string(hashmap).map_err(|_| SerializeJSONError)?;
+    crypto::seal(path.as_ref(), serialized_hashmap.into_bytes())
 }
 
 fn modify_hashmap<P, F, T>(path: &P, f: F) -> Result<(), ProjectError> where 
@@ -826,4 +2310,203 @@ fn modify_hashmap<P, F, T>(path: &P, f: F) -> Result<(), ProjectError> where
 //             );
 //         }
 //     }
-// }
\ No newline at end of file
+// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::ScriptedPrompt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Returns a `Config` for a fresh data directory holding user `bob`, answering questions from `answers`
+    fn config_with_user(directory: &Path, answers: &[&str]) -> (Config, Rc<RefCell<ScriptedPrompt>>) {
+        let mut config = Config::test(directory);
+        init(&config).unwrap();
+        config.remainder = vec![String::from("bob")];
+        create_user(&config).unwrap();
+
+        let prompt = Rc::new(RefCell::new(ScriptedPrompt::new(answers)));
+        config.prompt = prompt.clone();
+        (config, prompt)
+    }
+
+    fn user_exists(config: &Config, username: &str) -> bool {
+        read_from_hashmap::<PathBuf, User>(&config.user_map_path()).unwrap().contains_key(username)
+    }
+
+    #[test]
+    fn deleted_users_are_logged_out_archived_and_exported() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut config, prompt) = config_with_user(dir.path(), &["y", "y"]);
+        let mut user_map: HashMap<String, User> = read_from_hashmap(&config.user_map_path()).unwrap();
+        let mut alice = User::new_from_username("alice").unwrap();
+        alice.add_stock(DEFAULT_ACCOUNT, &Stock::new_from_ticker("FOO").unwrap(), 2).unwrap();
+        user_map.insert(String::from("alice"), alice);
+        write_to_hashmap(&config.user_map_path(), &user_map).unwrap();
+        config.remainder = vec![String::from("home"), String::from("alice"), String::from("bob")];
+        create_group(&config).unwrap();
+
+        // Archiving keeps the holdings and logs the user out
+        config.remainder = vec![String::from("alice")];
+        login(&config).unwrap();
+        config.flags.insert(String::from("archive"), FlagValue::Switch);
+        delete_user(&config).unwrap();
+        assert!(!user_exists(&config, "alice"));
+        assert_eq!(State::init(&config).unwrap().current_user, None);
+        assert_eq!(read_group_map(&config).unwrap()["home"].members.iter().collect::<Vec<_>>(), ["bob"]);
+        // An archived user's name may not be taken, so that they may be restored
+        config.remainder = vec![String::from("bob"), String::from("username"), String::from("alice")];
+        assert!(matches!(edit_user(&config), Err(HashMapInsertError(_))));
+        assert!(user_exists(&config, "bob"));
+        config.remainder = vec![String::from("alice")];
+        restore_user(&config).unwrap();
+        assert!(matches!(restore_user(&config), Err(HashMapInsertError(_))));
+        assert_eq!(read_from_hashmap::<PathBuf, User>(&config.user_map_path()).unwrap()["alice"].accounts, user_map["alice"].accounts);
+
+        // Holdings must be confirmed even without confirm-delete, and are exported first
+        config.flags.clear();
+        config.settings.confirm_delete = false;
+        delete_user(&config).unwrap();
+        assert_eq!(prompt.borrow().questions.len(), 2);
+        assert!(!user_exists(&config, "alice"));
+        let export = fs::read_dir(config.exports_path()).unwrap().next().unwrap().unwrap().path();
+        assert!(fs::read_to_string(export).unwrap().contains("alice,main,FOO,"));
+    }
+
+    #[test]
+    fn profiles_created_in_relative_directories_are_registered_absolute() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::test(dir.path());
+        // Reach the temporary directory from the working directory without an absolute path
+        let cwd = env::current_dir().unwrap();
+        let relative: PathBuf = cwd.components().skip(1).map(|_| Path::new(".."))
+            .chain(dir.path().components().skip(1).map(|x| Path::new(x.as_os_str())))
+            .collect::<PathBuf>()
+            .join("work");
+        assert!(relative.is_relative());
+
+        config.remainder = vec![String::from("work"), String::from(relative.to_str().unwrap())];
+        create_profile(&config).unwrap();
+        let directory = Profiles::open(dir.path()).unwrap().directory(dir.path(), "work").unwrap();
+        assert!(directory.is_absolute());
+        assert_eq!(directory, cwd.join(&relative));
+        assert!(dir.path().join("work").is_dir());
+    }
+
+    #[test]
+    fn failed_logins_lock_out_until_they_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+        let mut state = State::init(&config).unwrap();
+
+        assert!(matches!(state.record_failed_login(&config, "bob").unwrap(), IncorrectPasswordError(_, 4)));
+        for _ in 2..MAX_LOGIN_ATTEMPTS {
+            assert!(matches!(state.record_failed_login(&config, "bob").unwrap(), IncorrectPasswordError(_, _)));
+        }
+        assert!(state.check_lockout("bob").is_ok());
+        assert!(matches!(state.record_failed_login(&config, "bob").unwrap(), LoginLockedError(_, LOCKOUT_SECONDS)));
+        // The lockout is kept in the state file, and does not affect other users
+        let mut state = State::init(&config).unwrap();
+        assert!(matches!(state.check_lockout("bob"), Err(LoginLockedError(_, _))));
+        assert!(state.check_lockout("alice").is_ok());
+
+        // Once it expires, the count starts again
+        state.failed_logins.get_mut("bob").unwrap().last -= LOCKOUT_SECONDS;
+        assert!(state.check_lockout("bob").is_ok());
+        assert!(matches!(state.record_failed_login(&config, "bob").unwrap(), IncorrectPasswordError(_, 4)));
+        let user_map = HashMap::from([(String::from("bob"), User::new_from_username("bob").unwrap())]);
+        state.try_set_user(&config, "bob", user_map).unwrap();
+        assert!(State::init(&config).unwrap().failed_logins.is_empty());
+    }
+
+    #[test]
+    fn idle_session_expires() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut config, _) = config_with_user(dir.path(), &[]);
+        login(&config).unwrap();
+
+        config.command = Command::PortfolioC(PortfolioCommand::List);
+        dispatch(&config).unwrap();
+        let mut state = State::init(&config).unwrap();
+        assert!(state.session().is_some_and(|x| x.last_activity >= x.login_time));
+
+        // Idle for one second longer than the timeout
+        let session = state.session.as_mut().unwrap();
+        session.last_activity -= config.settings.session_timeout * 60 + 1;
+        state.write(&config).unwrap();
+        // A timeout other than the stored setting cannot revive the session
+        config.settings.session_timeout = 0;
+        assert!(matches!(dispatch(&config), Err(SessionExpiredError(x)) if x == "bob"));
+        assert_eq!(State::init(&config).unwrap().current_user, None);
+        assert!(matches!(dispatch(&config), Err(StateNoUserError)));
+    }
+
+    #[test]
+    fn changes_are_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut config, _) = config_with_user(dir.path(), &[]);
+        login(&config).unwrap();
+
+        config.command = Command::StockC(StockCommand::Create);
+        config.remainder = vec![String::from("FOO")];
+        config.flags.insert(String::from("name"), FlagValue::Text(String::from("Foo Inc")));
+        config.flags.insert(String::from("value"), FlagValue::Decimal(10.0));
+        config.flags.insert(String::from("quiet"), FlagValue::Switch);
+        dispatch(&config).unwrap();
+
+        config.command = Command::HistoryC(HistoryCommand::Undo);
+        config.remainder.clear();
+        config.flags.clear();
+        dispatch(&config).unwrap();
+        // Nothing left to undo, so nothing is audited
+        assert!(dispatch(&config).is_err());
+
+        // Settings are not part of the data files, but changing them is audited all the same
+        config.command = Command::SettingsC(SettingsCommand::Set);
+        config.remainder = vec![String::from("decimal-places"), String::from("3")];
+        dispatch(&config).unwrap();
+
+        let entries = audit::read(&config).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!((entries[2].command.as_str(), entries[2].arguments.as_slice()), ("config set", &config.remainder[..]));
+        assert_eq!(entries[0].user.as_deref(), Some("bob"));
+        assert_eq!(entries[0].arguments, ["FOO", "--name", "Foo Inc", "--value", "10"]);
+        assert_eq!(entries[0].changes, ["created stock FOO (Foo Inc) at 10"]);
+        assert_eq!((entries[1].command.as_str(), entries[1].changes.as_slice()), ("undo", &[String::from("deleted stock FOO")][..]));
+    }
+
+    #[test]
+    fn read_only_sessions_refuse_changes_until_logout() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut config, _) = config_with_user(dir.path(), &[]);
+        config.flags.insert(String::from("read-only"), FlagValue::Switch);
+        login(&config).unwrap();
+        assert!(State::init(&config).unwrap().is_read_only());
+
+        // Even an administrator may only view
+        config.flags.clear();
+        config.command = Command::StockC(StockCommand::Create);
+        config.remainder = vec![String::from("FOO")];
+        assert!(matches!(dispatch(&config), Err(ReadOnlySessionError(x)) if x == "create-stock"));
+        config.command = Command::StockC(StockCommand::List);
+        config.remainder.clear();
+        dispatch(&config).unwrap();
+
+        // A guest has nobody logged in
+        config.command = Command::StateC(StateCommand::Login);
+        config.flags.insert(String::from("read-only"), FlagValue::Switch);
+        dispatch(&config).unwrap();
+        let state = State::init(&config).unwrap();
+        assert_eq!((state.current_user.as_deref(), state.is_read_only()), (None, true));
+        config.flags.clear();
+        assert!(matches!(dispatch(&config), Err(ConfigArgumentsError(_))));
+
+        config.command = Command::StateC(StateCommand::Logout);
+        dispatch(&config).unwrap();
+        config.command = Command::StockC(StockCommand::Create);
+        config.remainder = vec![String::from("FOO")];
+        config.flags.insert(String::from("name"), FlagValue::Text(String::from("Foo Inc")));
+        config.flags.insert(String::from("value"), FlagValue::Decimal(10.0));
+        assert!(matches!(dispatch(&config), Err(StateNoUserError)));
+    }
+}
@@ -8,17 +8,18 @@ use stock_tracker::Config; // To allow use of the `Config` type
 
 fn main() {
     // Process arguments
-    let config = match Config::new(env::args()) {
+    let args: Vec<String> = env::args().collect();
+    let config = match Config::from_args(args.clone()) {
         Ok(x) => x,
             Err(x) => {
-            eprintln!("Problem parsing arguments: {}", x);
+            stock_tracker::report_parse_error(&args, &x);
             process::exit(1);
         }
     };
 
     // Program Logic
     if let Err(e) = stock_tracker::run(&config) {
-        eprintln!("Application error: {}", e);
+        stock_tracker::report_error(&config, &e);
         process::exit(1);
     }
 }
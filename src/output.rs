@@ -0,0 +1,440 @@
+//! #output
+//!
+//! This holds the `Record` types which make up the structured output of read commands, and the functions which
+//! present them in the format chosen by the `format` setting (or `--format text|json|csv`).
+//!
+//! In JSON format a listing is an array of objects and a single value (e.g. `config get`) is one object. In CSV format
+//! every output is a header row followed by one row per record. The fields of each record are documented on its type
+//! and appear in the order given there. Notifications are written to standard error in both formats so standard
+//! output holds only records, and in JSON format errors are written to standard error as
+//! `{"error": {"kind": ..., "message": ...}}`.
+
+// external crates
+use serde::Serialize; // So records may be written as JSON and CSV
+
+// internal crates
+use crate::audit::AuditEntry;
+use crate::check::Violation;
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::settings::OutputFormat;
+use crate::stock::{Stock, StockUnit};
+use crate::user::User;
+use crate::Config;
+
+/// The `Record` trait is implemented by every type a read command emits in JSON or CSV format
+pub trait Record: Serialize {
+    /// The name of every field in order, which heads CSV output
+    const FIELDS: &'static [&'static str];
+}
+
+/// A `User` as listed by `list-users`
+#[derive(Serialize, Debug, PartialEq)]
+pub struct UserRecord {
+    pub username: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub middle_initial: String,
+    /// "admin", "member" or "read-only"
+    pub role: String,
+}
+
+impl Record for UserRecord {
+    const FIELDS: &'static [&'static str] = &["username", "first_name", "last_name", "middle_initial", "role"];
+}
+
+impl From<&User> for UserRecord {
+    fn from(user: &User) -> UserRecord {
+        UserRecord {
+            username: String::from(user.username()),
+            first_name: String::from(user.first_name()),
+            last_name: String::from(user.last_name()),
+            middle_initial: String::from(user.middle_initial()),
+            role: user.role().to_string(),
+        }
+    }
+}
+
+/// A `User` as shown in detail by `show-user`. Details which were not given are empty, and `created` and `updated` are in
+/// seconds since the Unix epoch, empty for users from before they were recorded.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct UserDetailRecord {
+    pub username: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub middle_initial: String,
+    pub role: String,
+    pub email: Option<String>,
+    pub base_currency: Option<String>,
+    /// "conservative", "moderate" or "aggressive"
+    pub risk_tolerance: Option<String>,
+    pub tax_jurisdiction: Option<String>,
+    /// The names of the user's accounts, separated by spaces
+    pub accounts: String,
+    pub has_password: bool,
+    pub created: Option<u64>,
+    pub updated: Option<u64>,
+}
+
+impl Record for UserDetailRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "username", "first_name", "last_name", "middle_initial", "role", "email", "base_currency", "risk_tolerance",
+        "tax_jurisdiction", "accounts", "has_password", "created", "updated",
+    ];
+}
+
+impl From<&User> for UserDetailRecord {
+    fn from(user: &User) -> UserDetailRecord {
+        let known = |x: u64| if x == 0 { None } else { Some(x) };
+        UserDetailRecord {
+            username: String::from(user.username()),
+            first_name: String::from(user.first_name()),
+            last_name: String::from(user.last_name()),
+            middle_initial: String::from(user.middle_initial()),
+            role: user.role().to_string(),
+            email: user.email().map(String::from),
+            base_currency: user.base_currency().map(String::from),
+            risk_tolerance: user.risk_tolerance().map(|x| x.to_string()),
+            tax_jurisdiction: user.tax_jurisdiction().map(String::from),
+            accounts: user.accounts.keys().cloned().collect::<Vec<String>>().join(" "),
+            has_password: user.has_password(),
+            created: known(user.created()),
+            updated: known(user.updated()),
+        }
+    }
+}
+
+/// A `Stock` as listed by `list-stocks`, with `value` per share in the base currency
+#[derive(Serialize, Debug, PartialEq)]
+pub struct StockRecord {
+    pub ticker: String,
+    pub company_name: String,
+    pub value: f64,
+}
+
+impl Record for StockRecord {
+    const FIELDS: &'static [&'static str] = &["ticker", "company_name", "value"];
+}
+
+impl From<&Stock> for StockRecord {
+    fn from(stock: &Stock) -> StockRecord {
+        StockRecord {
+            ticker: stock.ticker.clone(),
+            company_name: stock.company_name.clone(),
+            value: stock.value,
+        }
+    }
+}
+
+/// A holding as listed by `list-portfolio`. `account` is the account holding it, or "all" when every account is listed
+/// combined. `price` is the current value per share from the stock map (or the value recorded with the holding if the
+/// stock no longer exists), `market_value` is `quantity` times `price`, `cost_basis` is the total paid for the shares
+/// and `gain` is `market_value` less `cost_basis`.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct HoldingRecord {
+    pub username: String,
+    pub account: String,
+    pub ticker: String,
+    pub company_name: String,
+    pub quantity: u32,
+    pub price: f64,
+    pub market_value: f64,
+    pub cost_basis: f64,
+    pub gain: f64,
+}
+
+impl Record for HoldingRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "username", "account", "ticker", "company_name", "quantity", "price", "market_value", "cost_basis", "gain",
+    ];
+}
+
+impl HoldingRecord {
+    pub fn new(username: &str, account: &str, stock_unit: &StockUnit, current: Option<&Stock>) -> HoldingRecord {
+        let stock = current.unwrap_or(&stock_unit.stock);
+        let market_value = stock.value * f64::from(stock_unit.quantity);
+        HoldingRecord {
+            username: String::from(username),
+            account: String::from(account),
+            ticker: stock_unit.stock.ticker.clone(),
+            company_name: stock.company_name.clone(),
+            quantity: stock_unit.quantity,
+            price: stock.value,
+            market_value,
+            cost_basis: stock_unit.cost_basis(),
+            gain: market_value - stock_unit.cost_basis(),
+        }
+    }
+}
+
+/// A problem reported (or repaired) by `check`, where `kind` is e.g. "missing-stock"
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ViolationRecord {
+    pub kind: String,
+    pub description: String,
+}
+
+impl Record for ViolationRecord {
+    const FIELDS: &'static [&'static str] = &["kind", "description"];
+}
+
+impl From<&Violation> for ViolationRecord {
+    fn from(violation: &Violation) -> ViolationRecord {
+        ViolationRecord {
+            kind: String::from(violation.kind()),
+            description: violation.to_string(),
+        }
+    }
+}
+
+/// A profile as listed by `profile list`
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ProfileRecord {
+    pub name: String,
+    pub directory: String,
+    pub active: bool,
+}
+
+impl Record for ProfileRecord {
+    const FIELDS: &'static [&'static str] = &["name", "directory", "active"];
+}
+
+/// The login of the current user as shown by `whoami`. Times are in seconds since the Unix epoch and `session_age` is
+/// in seconds; `expires_at` is empty if sessions do not expire.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SessionRecord {
+    pub username: String,
+    pub login_time: u64,
+    pub last_activity: u64,
+    pub session_age: u64,
+    pub expires_at: Option<u64>,
+    /// Whether the session was started with `login --read-only`
+    pub read_only: bool,
+}
+
+impl Record for SessionRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "username", "login_time", "last_activity", "session_age", "expires_at", "read_only",
+    ];
+}
+
+/// An account as listed by `account list`, where `market_value` is the value of its holdings at current prices,
+/// `total` is that plus `cash` and `current` is whether it is the account commands apply to
+#[derive(Serialize, Debug, PartialEq)]
+pub struct AccountRecord {
+    pub username: String,
+    pub account: String,
+    pub cash: f64,
+    pub market_value: f64,
+    pub total: f64,
+    pub current: bool,
+}
+
+impl Record for AccountRecord {
+    const FIELDS: &'static [&'static str] = &["username", "account", "cash", "market_value", "total", "current"];
+}
+
+/// A group of users as listed by `group list`, with `members` separated by spaces
+#[derive(Serialize, Debug, PartialEq)]
+pub struct GroupRecord {
+    pub group: String,
+    pub members: String,
+}
+
+impl Record for GroupRecord {
+    const FIELDS: &'static [&'static str] = &["group", "members"];
+}
+
+/// A member's holding of a stock as listed by `group-portfolio`, or the holding of every member combined when `member`
+/// is "all". `weight` is the share of the group's combined market value.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct GroupHoldingRecord {
+    pub group: String,
+    pub ticker: String,
+    pub company_name: String,
+    pub member: String,
+    pub quantity: u32,
+    pub price: f64,
+    pub market_value: f64,
+    pub cost_basis: f64,
+    pub gain: f64,
+    pub weight: f64,
+}
+
+impl Record for GroupHoldingRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "group", "ticker", "company_name", "member", "quantity", "price", "market_value", "cost_basis", "gain", "weight",
+    ];
+}
+
+impl GroupHoldingRecord {
+    pub fn new(group: &str, member: &str, stock_unit: &StockUnit, current: Option<&Stock>, total: f64) -> GroupHoldingRecord {
+        let holding = HoldingRecord::new(member, "all", stock_unit, current);
+        GroupHoldingRecord {
+            group: String::from(group),
+            ticker: holding.ticker,
+            company_name: holding.company_name,
+            member: holding.username,
+            quantity: holding.quantity,
+            price: holding.price,
+            market_value: holding.market_value,
+            cost_basis: holding.cost_basis,
+            gain: holding.gain,
+            weight: if total > 0.0 { holding.market_value / total } else { 0.0 },
+        }
+    }
+}
+
+/// An entry of the audit log as listed by `audit`. `timestamp` is in seconds since the Unix epoch, `user` is empty if
+/// nobody was logged in, `arguments` are separated by spaces and `changes` by "; ".
+#[derive(Serialize, Debug, PartialEq)]
+pub struct AuditRecord {
+    pub timestamp: u64,
+    pub user: String,
+    pub command: String,
+    pub arguments: String,
+    pub changes: String,
+}
+
+impl Record for AuditRecord {
+    const FIELDS: &'static [&'static str] = &["timestamp", "user", "command", "arguments", "changes"];
+}
+
+impl From<&AuditEntry> for AuditRecord {
+    fn from(entry: &AuditEntry) -> AuditRecord {
+        AuditRecord {
+            timestamp: entry.timestamp,
+            user: entry.user.clone().unwrap_or_default(),
+            command: entry.command.clone(),
+            arguments: entry.arguments.join(" "),
+            changes: entry.changes.join("; "),
+        }
+    }
+}
+
+/// A setting as shown by `config get` and `config list`
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SettingRecord {
+    pub key: String,
+    pub value: String,
+}
+
+impl Record for SettingRecord {
+    const FIELDS: &'static [&'static str] = &["key", "value"];
+}
+
+/// The `print_records()` function prints `records` in the configured format, calling `text` to print them in text
+/// format instead.
+pub fn print_records<R, F>(config: &Config, records: &[R], text: F) -> Result<(), ProjectError> where
+    R: Record,
+    F: FnOnce(), {
+    match config.settings.format {
+        OutputFormat::Text => text(),
+        OutputFormat::Json => println!("{}", render_json(records)?),
+        OutputFormat::Csv => print!("{}", render_csv(records)?),
+    }
+    Ok(())
+}
+
+/// The `print_record()` function is like `print_records()` for commands which show a single record.
+pub fn print_record<R, F>(config: &Config, record: &R, text: F) -> Result<(), ProjectError> where
+    R: Record,
+    F: FnOnce(), {
+    match config.settings.format {
+        OutputFormat::Text => text(),
+        OutputFormat::Json => println!("{}", render_json(record)?),
+        OutputFormat::Csv => print!("{}", render_csv(std::slice::from_ref(record))?),
+    }
+    Ok(())
+}
+
+/// Renders `value` as pretty-printed JSON
+pub fn render_json<T: Serialize + ?Sized>(value: &T) -> Result<String, ProjectError> {
+    serde_json::to_string_pretty(value).map_err(|_| SerializeJSONError)
+}
+
+/// Renders `records` as CSV, headed by the record's field names even when there are no records
+pub fn render_csv<R: Record>(records: &[R]) -> Result<String, ProjectError> {
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+
+    writer.write_record(R::FIELDS).map_err(|_| SerializeCSVError)?;
+    for record in records {
+        writer.serialize(record).map_err(|_| SerializeCSVError)?;
+    }
+
+    let bytes = writer.into_inner().map_err(|_| SerializeCSVError)?;
+    String::from_utf8(bytes).map_err(|_| SerializeCSVError)
+}
+
+/// Renders `error` as the JSON object reported in JSON format
+pub fn render_error(error: &ProjectError) -> String {
+    serde_json::json!({ "error": { "kind": error.kind(), "message": error.to_string() } }).to_string()
+}
+
+/// The `print_error()` function reports `error` on standard error, prefixed by `context` in text and CSV format.
+pub fn print_error(format: OutputFormat, context: Option<&str>, error: &ProjectError) {
+    match (format, context) {
+        (OutputFormat::Json, _) => eprintln!("{}", render_error(error)),
+        (_, Some(context)) => eprintln!("{}: {}", context, error),
+        (_, None) => eprintln!("{}", error),
+    }
+}
+
+/// The `notify()` function prints the notification `s`, to standard error in JSON and CSV format so that it cannot
+/// be mistaken for output.
+pub fn notify(format: OutputFormat, s: &str) {
+    match format {
+        OutputFormat::Text => println!("{}", s),
+        OutputFormat::Json | OutputFormat::Csv => eprintln!("{}", s),
+    }
+}
+
+/// Returns the format requested by a `--format` option in `args`, for reporting errors which occur before the
+/// arguments are parsed. Text format is assumed if there is no such option or its value is not recognized.
+pub fn requested_format(args: &[String]) -> OutputFormat {
+    let mut format = OutputFormat::Text;
+    for (i, arg) in args.iter().enumerate() {
+        let value = match arg.strip_prefix("--format") {
+            Some("") => args.get(i + 1).map(String::as_str),
+            Some(x) => x.strip_prefix('='),
+            None => None,
+        };
+        if let Some(x) = value.and_then(|x| x.parse().ok()) {
+            format = x;
+        }
+    }
+    format
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|x| String::from(*x)).collect()
+    }
+
+    #[test]
+    fn csv_has_header_and_quotes_fields() {
+        let records = vec![StockRecord { ticker: String::from("FOO"), company_name: String::from("Foo, Inc."), value: 1.5 }];
+        assert_eq!(render_csv(&records).unwrap(), "ticker,company_name,value\nFOO,\"Foo, Inc.\",1.5\n");
+        assert_eq!(render_csv::<StockRecord>(&[]).unwrap(), "ticker,company_name,value\n");
+    }
+
+    #[test]
+    fn json_lists_records_and_errors() {
+        let records = vec![SettingRecord { key: String::from("format"), value: String::from("json") }];
+        let json: serde_json::Value = serde_json::from_str(&render_json(&records).unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!([{ "key": "format", "value": "json" }]));
+
+        let json: serde_json::Value = serde_json::from_str(&render_error(&HashMapKeyNotFoundError(String::from("FOO")))).unwrap();
+        assert_eq!(json["error"]["kind"], "HashMapKeyNotFoundError");
+    }
+
+    #[test]
+    fn requested_format_is_read_from_raw_args() {
+        assert_eq!(requested_format(&strings(&["stock_tracker", "ls", "--format", "json"])), OutputFormat::Json);
+        assert_eq!(requested_format(&strings(&["stock_tracker", "--format=csv", "bogus"])), OutputFormat::Csv);
+        assert_eq!(requested_format(&strings(&["stock_tracker", "--format", "xml"])), OutputFormat::Text);
+    }
+}
@@ -0,0 +1,166 @@
+//! #permission
+//!
+//! This holds the `authorize()` function, which decides whether the logged-in user's `Role`, and whether the session is
+//! read-only, allows a command to be run
+
+// std
+use std::collections::HashMap;
+
+// internal crates
+use crate::command::{Access, Command};
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::group::Group;
+use crate::user::{Role, User};
+use crate::{Config, State};
+
+/// Returns `true` if a user of `user_map` is an administrator, after which the `Access` of commands is enforced
+pub fn has_admin(user_map: &HashMap<String, User>) -> bool {
+    user_map.values().any(|x| x.role() == Role::Admin)
+}
+
+/// Returns `true` if `username` is the only administrator of `user_map` and there are other users, who would be left
+/// with nobody to manage them if it stopped being one
+pub fn is_last_admin(user_map: &HashMap<String, User>, username: &str) -> bool {
+    user_map.len() > 1 && user_map.values().filter(|x| x.role() == Role::Admin).map(User::username).eq([username])
+}
+
+/// The `authorize()` function returns a `PermissionDeniedError` if the logged-in user may not run the command of
+/// `config`, or a `StateNoUserError` if nobody is logged in to run it. A read-only session may not run any command
+/// which changes data, whatever the role of its user, which returns a `ReadOnlySessionError`.
+pub fn authorize(config: &Config) -> Result<(), ProjectError> {
+    if config.state_path().exists() && State::init(config)?.is_read_only() {
+        check_read_only(&config.command, config.flag("repair").is_some())?;
+    }
+
+    if config.command.access() == Access::Anyone || !config.user_map_path().exists() {
+        return Ok(())
+    }
+
+    let user_map: HashMap<String, User> = crate::read_from_hashmap(&config.user_map_path())?;
+    let current_user = if config.state_path().exists() { State::init(config)?.current_user } else { None };
+    let group_map = match config.command.access() {
+        Access::GroupMember => crate::read_group_map(config)?,
+        _ => HashMap::new(),
+    };
+    check(&config.command, &config.remainder, &user_map, &group_map, current_user.as_deref())
+}
+
+/// Checks the `Access` of `command`, run with the positional arguments `args`, against the role of `current_user` and,
+/// for `Access::GroupMember`, the members of the group in `group_map`
+fn check(command: &Command, args: &[String], user_map: &HashMap<String, User>, group_map: &HashMap<String, Group>,
+    current_user: Option<&str>) -> Result<(), ProjectError> {
+    if !has_admin(user_map) {
+        return Ok(())
+    }
+
+    let user = match current_user.and_then(|x| user_map.get(x)) {
+        Some(x) => x,
+        None if command.access() == Access::Anyone => return Ok(()),
+        None => return Err(StateNoUserError),
+    };
+
+    match (command.access(), user.role()) {
+        (_, Role::Admin) | (Access::Anyone | Access::User, _) | (Access::Member, Role::Member) => Ok(()),
+        (Access::OwnUser, Role::Member) if args.first().is_some_and(|x| x == user.username()) => Ok(()),
+        (Access::OwnUser, Role::Member) => {
+            Err(PermissionDeniedError(format!("{} of another user requires an administrator", command)))
+        },
+        (Access::GroupMember, _) => match args.first() {
+            Some(x) if group_map.get(x).is_some_and(|group| group.members.contains(user.username())) => Ok(()),
+            x => Err(PermissionDeniedError(format!("{} of group {} requires being one of its members or an administrator",
+                command, x.map(String::as_str).unwrap_or_default()))),
+        },
+        (_, role) => Err(PermissionDeniedError(format!("{} may not be run by {} user {}", command, role, user.username()))),
+    }
+}
+
+/// Checks that `command` does not change data, as a read-only session requires. `check` only changes data when run
+/// with `--repair`.
+fn check_read_only(command: &Command, repair: bool) -> Result<(), ProjectError> {
+    match command {
+        Command::Check if !repair => Ok(()),
+        x if x.changes_data() => Err(ReadOnlySessionError(x.to_string())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{AccountCommand, GroupCommand, HistoryCommand, PortfolioCommand, SettingsCommand, StockCommand, UserCommand};
+
+    fn user_map() -> HashMap<String, User> {
+        let mut user_map = HashMap::new();
+        for (username, role) in [("alice", Role::Admin), ("bob", Role::Member), ("carol", Role::ReadOnly)] {
+            let mut user = User::new_from_username(username).unwrap();
+            user.set_role(role);
+            user_map.insert(String::from(username), user);
+        }
+        user_map
+    }
+
+    fn allowed(command: Command, args: &[&str], current_user: Option<&str>) -> bool {
+        let args: Vec<String> = args.iter().map(|x| String::from(*x)).collect();
+        let mut home = Group::default();
+        home.members.extend([String::from("bob"), String::from("carol")]);
+        let group_map = HashMap::from([(String::from("home"), home), (String::from("work"), Group::default())]);
+        check(&command, &args, &user_map(), &group_map, current_user).is_ok()
+    }
+
+    #[test]
+    fn roles_limit_commands() {
+        let create_stock = Command::StockC(StockCommand::Create);
+        assert!(allowed(create_stock.clone(), &["FOO"], Some("alice")));
+        assert!(!allowed(create_stock.clone(), &["FOO"], Some("bob")));
+        assert!(!allowed(create_stock, &["FOO"], None));
+
+        let buy_stock = Command::PortfolioC(PortfolioCommand::Buy);
+        assert!(allowed(buy_stock.clone(), &["FOO", "1"], Some("bob")));
+        assert!(!allowed(buy_stock, &["FOO", "1"], Some("carol")));
+        assert!(allowed(Command::PortfolioC(PortfolioCommand::List), &[], Some("carol")));
+        assert!(allowed(Command::UserC(UserCommand::List), &[], None));
+        assert!(allowed(Command::HistoryC(HistoryCommand::Undo), &[], Some("bob")));
+        assert!(!allowed(Command::HistoryC(HistoryCommand::Undo), &[], Some("carol")));
+    }
+
+    #[test]
+    fn members_may_only_edit_themselves() {
+        let edit_user = Command::UserC(UserCommand::Edit);
+        assert!(allowed(edit_user.clone(), &["bob", "first-name", "Robert"], Some("bob")));
+        assert!(!allowed(edit_user.clone(), &["carol", "first-name", "Caroline"], Some("bob")));
+        assert!(allowed(edit_user.clone(), &["carol", "first-name", "Caroline"], Some("alice")));
+        assert!(!allowed(edit_user, &["carol", "first-name", "Caroline"], Some("carol")));
+    }
+
+    #[test]
+    fn groups_may_only_be_viewed_by_members() {
+        let group_portfolio = Command::GroupC(GroupCommand::Portfolio);
+        assert!(allowed(group_portfolio.clone(), &["home"], Some("bob")));
+        assert!(allowed(group_portfolio.clone(), &["home"], Some("carol")));
+        assert!(!allowed(group_portfolio.clone(), &["work"], Some("bob")));
+        assert!(allowed(group_portfolio.clone(), &["work"], Some("alice")));
+        assert!(!allowed(group_portfolio, &["home"], None));
+    }
+
+    #[test]
+    fn read_only_sessions_refuse_changes() {
+        assert!(check_read_only(&Command::StockC(StockCommand::List), false).is_ok());
+        assert!(check_read_only(&Command::Check, false).is_ok());
+        assert!(matches!(check_read_only(&Command::Check, true), Err(ReadOnlySessionError(_))));
+        assert!(matches!(check_read_only(&Command::PortfolioC(PortfolioCommand::Buy), false), Err(ReadOnlySessionError(_))));
+        assert!(matches!(check_read_only(&Command::HistoryC(HistoryCommand::Undo), false), Err(ReadOnlySessionError(_))));
+        assert!(matches!(check_read_only(&Command::SettingsC(SettingsCommand::Set), false), Err(ReadOnlySessionError(_))));
+        // Switching accounts only changes the session, as logging in does
+        assert!(check_read_only(&Command::AccountC(AccountCommand::Switch), false).is_ok());
+        assert!(matches!(check_read_only(&Command::AccountC(AccountCommand::Create), false), Err(ReadOnlySessionError(_))));
+    }
+
+    #[test]
+    fn nothing_is_enforced_without_an_admin() {
+        let mut user_map = user_map();
+        assert!(is_last_admin(&user_map, "alice"));
+        user_map.remove("alice");
+        assert!(check(&Command::Init, &[], &user_map, &HashMap::new(), None).is_ok());
+    }
+}
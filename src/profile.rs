@@ -0,0 +1,171 @@
+//! #profile
+//!
+//! This holds the `Profiles` type and related methods, which map profile names to their own configuration directories
+
+// std
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// external crates
+use serde::{Serialize, Deserialize}; // So we may write the profiles to a file
+
+// internal crates
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+
+/// The profile which always exists and whose data lives directly in the root directory
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The `Profiles` struct is the registry of named profiles, stored in the root directory alongside the data of the
+/// default profile.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Profiles {
+    /// The profile used when no `--profile` option is given
+    active: String,
+    /// The configuration directory of each profile other than the default profile
+    profiles: BTreeMap<String, PathBuf>,
+}
+
+impl Default for Profiles {
+    fn default() -> Profiles {
+        Profiles { active: String::from(DEFAULT_PROFILE), profiles: BTreeMap::new() }
+    }
+}
+
+impl Profiles {
+
+    /// `open()` reads the registry from the root directory, or returns a registry with only the default profile if
+    /// none exists yet.
+    pub fn open(root: &Path) -> Result<Profiles, ProjectError> {
+        let path = Profiles::path(root);
+
+        if !path.exists() {
+            return Ok(Profiles::default())
+        }
+
+        let contents = fs::read(&path).map_err(|_| IOProfilesOpenError(path.clone()))?;
+        serde_json::from_slice(&contents).map_err(|_| DeserializeJSONError(path))
+    }
+
+    /// Writes the registry to a temporary file and renames it over the old one, so that an interrupted write cannot
+    /// leave a truncated registry behind.
+    pub fn write(&self, root: &Path) -> Result<(), ProjectError> {
+        let path = Profiles::path(root);
+        let serialized_profiles = serde_json::to_string(self).map_err(|_| SerializeJSONError)?;
+
+        crate::replace_file(&path, serialized_profiles.as_bytes()).map_err(|_| IOProfilesWriteError(path))
+    }
+
+    /// Simple method to return the location of the registry within the root directory
+    pub fn path(root: &Path) -> PathBuf {
+        root.join("Profiles.JSON")
+    }
+
+    /// Returns the name of the active profile
+    pub fn active(&self) -> &str {
+        &self.active
+    }
+
+    /// Returns every profile name and its configuration directory, sorted by name
+    pub fn list(&self, root: &Path) -> Vec<(String, PathBuf)> {
+        let mut list = vec![(String::from(DEFAULT_PROFILE), PathBuf::from(root))];
+        list.extend(self.profiles.iter().map(|(name, directory)| (name.clone(), directory.clone())));
+        list.sort();
+        list
+    }
+
+    /// Returns the configuration directory of profile `name`
+    pub fn directory(&self, root: &Path, name: &str) -> Result<PathBuf, ProjectError> {
+        if name == DEFAULT_PROFILE {
+            return Ok(PathBuf::from(root))
+        }
+        self.profiles.get(name).cloned().ok_or_else(|| ProfileNotFoundError(String::from(name)))
+    }
+
+    /// `create()` registers profile `name`. Its data is kept in `directory` if provided, otherwise in a directory
+    /// named after it under the root directory's `profiles` directory.
+    pub fn create(&mut self, root: &Path, name: &str, directory: Option<PathBuf>) -> Result<PathBuf, ProjectError> {
+        validate_name(name)?;
+        if name == DEFAULT_PROFILE || self.profiles.contains_key(name) {
+            return Err(HashMapInsertError(String::from(name)))
+        }
+
+        let directory = directory.unwrap_or_else(|| Profiles::managed_directory(root, name));
+        self.profiles.insert(String::from(name), directory.clone());
+        Ok(directory)
+    }
+
+    /// `switch()` makes profile `name` the active profile
+    pub fn switch(&mut self, name: &str) -> Result<(), ProjectError> {
+        if name != DEFAULT_PROFILE && !self.profiles.contains_key(name) {
+            return Err(ProfileNotFoundError(String::from(name)))
+        }
+        self.active = String::from(name);
+        Ok(())
+    }
+
+    /// `delete()` unregisters profile `name`, returning its configuration directory. Neither the default nor the
+    /// active profile may be deleted.
+    pub fn delete(&mut self, name: &str) -> Result<PathBuf, ProjectError> {
+        if name == DEFAULT_PROFILE || name == self.active {
+            return Err(ProfileInUseError(String::from(name)))
+        }
+        self.profiles.remove(name).ok_or_else(|| ProfileNotFoundError(String::from(name)))
+    }
+
+    /// Returns the directory a profile's data is kept in when no directory is given on creation. Only these
+    /// directories are removed along with their profile.
+    pub fn managed_directory(root: &Path, name: &str) -> PathBuf {
+        root.join("profiles").join(name)
+    }
+}
+
+/// Profile names become directory names, so only letters, digits, `-` and `_` are allowed
+fn validate_name(name: &str) -> Result<(), ProjectError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(ProfileNameError(String::from(name)))
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_persist_and_protect_active() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let mut profiles = Profiles::open(root).unwrap();
+        assert_eq!(profiles.active(), DEFAULT_PROFILE);
+        let paper = profiles.create(root, "paper", None).unwrap();
+        assert_eq!(paper, root.join("profiles").join("paper"));
+        assert!(matches!(profiles.create(root, "paper", None), Err(HashMapInsertError(_))));
+        assert!(matches!(profiles.create(root, "../real", None), Err(ProfileNameError(_))));
+        profiles.switch("paper").unwrap();
+        profiles.write(root).unwrap();
+
+        let mut profiles = Profiles::open(root).unwrap();
+        assert_eq!(profiles.active(), "paper");
+        assert_eq!(profiles.directory(root, "paper").unwrap(), paper);
+        assert!(matches!(profiles.delete("paper"), Err(ProfileInUseError(_))));
+        profiles.switch(DEFAULT_PROFILE).unwrap();
+        assert_eq!(profiles.delete("paper").unwrap(), paper);
+        assert!(matches!(profiles.directory(root, "paper"), Err(ProfileNotFoundError(_))));
+    }
+
+    #[test]
+    fn profiles_are_replaced_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let profiles = Profiles::open(root).unwrap();
+        profiles.write(root).unwrap();
+        profiles.write(root).unwrap();
+        let files = fs::read_dir(root).unwrap().map(|x| x.unwrap().file_name()).collect::<Vec<_>>();
+        assert_eq!(files, ["Profiles.JSON"]);
+        assert!(matches!(profiles.write(&root.join("missing")), Err(IOProfilesWriteError(_))));
+    }
+}
@@ -0,0 +1,198 @@
+//! #prompt
+//!
+//! This holds the `Prompt` trait, through which every question is asked, and its implementations for the terminal,
+//! for unattended use and for tests
+
+// std
+use std::cell::RefCell;
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::io;
+use std::io::{IsTerminal, Write};
+use std::rc::Rc;
+
+// internal crates
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+
+/// A shared handle to the `Prompt` of a `Config`
+pub type PromptHandle = Rc<RefCell<dyn Prompt>>;
+
+/// The `Prompt` trait represents a source of answers to the questions a command asks
+pub trait Prompt: fmt::Debug {
+    /// Asks `question` and returns the answer without surrounding whitespace
+    fn ask(&mut self, question: &str) -> Result<String, ProjectError>;
+    /// Like `ask()`, but the answer is not echoed, e.g. for passphrases
+    fn ask_secret(&mut self, question: &str) -> Result<String, ProjectError>;
+    /// Whether questions may be answered, so that optional questions can be skipped when they cannot
+    fn is_interactive(&self) -> bool;
+}
+
+/// A `Prompt` which asks on the terminal
+#[derive(Debug, Default)]
+pub struct TerminalPrompt;
+
+impl Prompt for TerminalPrompt {
+    fn ask(&mut self, question: &str) -> Result<String, ProjectError> {
+        println!("{}", question);
+        io::stdout().flush().map_err(|_| InvalidInputError)?;
+
+        let mut ans = String::new();
+        io::stdin().read_line(&mut ans).map_err(|_| InvalidInputError)?;
+        Ok(String::from(ans.trim()))
+    }
+
+    fn ask_secret(&mut self, question: &str) -> Result<String, ProjectError> {
+        rpassword::prompt_password(question).map_err(|_| InvalidInputError)
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+/// A `Prompt` for unattended use, which fails rather than waiting for an answer that will never come
+#[derive(Debug, Default)]
+pub struct NonInteractivePrompt;
+
+impl Prompt for NonInteractivePrompt {
+    fn ask(&mut self, question: &str) -> Result<String, ProjectError> {
+        Err(InputRequiredError(String::from(question.trim())))
+    }
+
+    fn ask_secret(&mut self, question: &str) -> Result<String, ProjectError> {
+        self.ask(question)
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// A `Prompt` which gives prepared answers in order, recording the questions asked. It fails like
+/// `NonInteractivePrompt` once the answers run out.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct ScriptedPrompt {
+    /// The answers yet to be given
+    pub answers: VecDeque<String>,
+    /// Every question asked so far
+    pub questions: Vec<String>,
+}
+
+#[cfg(test)]
+impl ScriptedPrompt {
+    pub fn new(answers: &[&str]) -> ScriptedPrompt {
+        ScriptedPrompt {
+            answers: answers.iter().map(|x| String::from(*x)).collect(),
+            questions: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Prompt for ScriptedPrompt {
+    fn ask(&mut self, question: &str) -> Result<String, ProjectError> {
+        self.questions.push(String::from(question.trim()));
+        self.answers.pop_front().ok_or_else(|| InputRequiredError(String::from(question.trim())))
+    }
+
+    fn ask_secret(&mut self, question: &str) -> Result<String, ProjectError> {
+        self.ask(question)
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+/// The `confirm()` function asks the yes or no `question` through `prompt`, returning whether the answer was yes. An
+/// answer which is neither returns an `InvalidInputError`.
+pub fn confirm(prompt: &mut dyn Prompt, question: &str) -> Result<bool, ProjectError> {
+    match prompt.ask(question)?.to_lowercase().as_str() {
+        // In the case where the user is sure
+        "y" | "yes" => Ok(true),
+        // In the case where the user declines
+        "q" | "quit" | "n" | "no" => Ok(false),
+        // In the case where the user input is not recognized
+        _ => Err(InvalidInputError),
+    }
+}
+
+/// The `ask_until_parsed()` function asks `question` through `prompt` until the answer can be parsed, passing the error
+/// for each answer which cannot be to `on_error`. A blank answer, or a prompt which is not interactive, gives `default`.
+pub fn ask_until_parsed<T, D, F>(prompt: &mut dyn Prompt, question: &str, default: D, mut on_error: F)
+    -> Result<T, ProjectError> where
+    T: FromStr,
+    D: Into<T> + fmt::Display,
+    F: FnMut(&ProjectError), {
+    if !prompt.is_interactive() {
+        return Ok(default.into())
+    }
+
+    loop {
+        let ans = prompt.ask(&format!("{} [{}]:", question, default))?;
+        if ans.is_empty() {
+            return Ok(default.into())
+        }
+        match ans.parse() {
+            Ok(x) => return Ok(x),
+            Err(_) => on_error(&InputParseError(ans, std::any::type_name::<T>().to_string())),
+        }
+    }
+}
+
+/// Returns the `Prompt` to use: the terminal, unless `no_input` is set or standard input is not a terminal
+pub fn select(no_input: bool) -> PromptHandle {
+    if no_input || !io::stdin().is_terminal() {
+        Rc::new(RefCell::new(NonInteractivePrompt))
+    } else {
+        Rc::new(RefCell::new(TerminalPrompt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripted_prompt_answers_in_order_then_fails() {
+        let mut prompt = ScriptedPrompt::new(&["yes", "hunter2"]);
+        assert_eq!(prompt.ask("Sure?\n").unwrap(), "yes");
+        assert_eq!(prompt.ask_secret("Passphrase: ").unwrap(), "hunter2");
+        assert!(matches!(prompt.ask("Again?"), Err(InputRequiredError(x)) if x == "Again?"));
+        assert_eq!(prompt.questions, vec!["Sure?", "Passphrase:", "Again?"]);
+    }
+
+    #[test]
+    fn confirmations_accept_yes_or_no() {
+        let mut prompt = ScriptedPrompt::new(&["n", "Yes", "Q", "maybe"]);
+        assert!(!confirm(&mut prompt, "Delete?").unwrap());
+        assert!(confirm(&mut prompt, "Delete?").unwrap());
+        assert!(!confirm(&mut prompt, "Delete?").unwrap());
+        assert!(matches!(confirm(&mut prompt, "Delete?"), Err(InvalidInputError)));
+        assert!(matches!(confirm(&mut NonInteractivePrompt, "Delete?"), Err(InputRequiredError(_))));
+    }
+
+    #[test]
+    fn details_are_asked_until_parsed() {
+        let mut prompt = ScriptedPrompt::new(&["a lot", "12.5", ""]);
+        let mut errors = Vec::new();
+        let value: f64 = ask_until_parsed(&mut prompt, "Value of one share", 0.0, |x| errors.push(x.to_string())).unwrap();
+        assert_eq!((value, errors.len()), (12.5, 1)); // The value is asked again after "a lot"
+        let name: String = ask_until_parsed(&mut prompt, "Middle initial", "", |_| ()).unwrap();
+        assert_eq!(name, "");
+        assert_eq!(prompt.questions, vec!["Value of one share [0]:", "Value of one share [0]:", "Middle initial []:"]);
+
+        let value: f64 = ask_until_parsed(&mut NonInteractivePrompt, "Value of one share", 1.5, |_| ()).unwrap();
+        assert_eq!(value, 1.5);
+    }
+
+    #[test]
+    fn non_interactive_prompt_never_answers() {
+        assert!(matches!(NonInteractivePrompt.ask("Sure?"), Err(InputRequiredError(_))));
+        assert!(matches!(select(true).borrow_mut().ask_secret("Passphrase: "), Err(InputRequiredError(_))));
+    }
+}
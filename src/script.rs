@@ -0,0 +1,144 @@
+//! #script
+//!
+//! This holds the `OnError` and `Failure` types and the `run()` function, which carries out a script of commands, one
+//! per line
+
+// std
+use std::fmt;
+use std::str::FromStr;
+
+// internal crates
+use crate::command::Command;
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::tokenize;
+use crate::Config;
+
+/// The `OnError` enum represents what a script does when one of its lines fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnError {
+    /// Stop at the first line which fails
+    Stop,
+    /// Carry on with the next line, reporting every failure at the end
+    Continue,
+}
+
+impl FromStr for OnError {
+    type Err = ProjectError;
+
+    fn from_str(s: &str) -> Result<OnError, ProjectError> {
+        match s.to_lowercase().as_str() {
+            "stop" => Ok(OnError::Stop),
+            "continue" => Ok(OnError::Continue),
+            _ => Err(InputParseError(String::from(s), String::from("OnError"))),
+        }
+    }
+}
+
+/// The `Failure` struct represents a line of a script which failed and why
+#[derive(Debug)]
+pub struct Failure {
+    /// The line number, counting from 1
+    pub line: usize,
+    /// The text of the line
+    pub text: String,
+    pub error: ProjectError,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} ({}): {}", self.line, self.text, self.error)
+    }
+}
+
+/// The `Summary` struct represents the outcome of a script
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// The number of commands carried out, whether or not they succeeded
+    pub commands: usize,
+    pub failures: Vec<Failure>,
+}
+
+/// The `run()` function carries out each line of `source` as a command with `execute`, applying the global flags of
+/// `config` to each. Blank lines and lines beginning with `#` are skipped, and `exit` ends the script early. Lines are
+/// split into words as in console mode. `console` and `run-script` fail, as a script may not run them.
+pub fn run<F>(config: &Config, source: &str, on_error: OnError, mut execute: F) -> Summary where
+    F: FnMut(&Config) -> Result<(), ProjectError>, {
+    let mut summary = Summary::default();
+
+    for (i, text) in source.lines().enumerate() {
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue
+        }
+
+        let result = tokenize::split(text)
+            .and_then(|words| config.for_line(words))
+            .and_then(|line_config| match line_config.command {
+                Command::Exit => Ok(None),
+                // A script cannot enter console mode, nor run a script, which could run itself without end
+                Command::Console | Command::RunScript => Err(InvalidInputError),
+                _ => execute(&line_config).map(Some),
+            });
+
+        match result {
+            Ok(None) => break, // `exit`
+            Ok(Some(())) => summary.commands += 1,
+            Err(error) => {
+                summary.commands += 1;
+                summary.failures.push(Failure { line: i + 1, text: String::from(text), error });
+                if on_error == OnError::Stop {
+                    break
+                }
+            },
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "# Set up\n\ncreate-stock FOO\nfrobnicate\n  list-stocks  \nexit\nlist-users\n";
+
+    fn run_source(on_error: OnError) -> (Summary, Vec<Command>) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+
+        let mut executed = Vec::new();
+        let summary = run(&config, SOURCE, on_error, |line_config| {
+            executed.push(line_config.command.clone());
+            Ok(())
+        });
+        (summary, executed)
+    }
+
+    #[test]
+    fn continue_reports_every_failure_and_exit_ends_the_script() {
+        let (summary, executed) = run_source(OnError::Continue);
+        assert_eq!(executed.len(), 2);
+        assert_eq!(summary.commands, 3);
+        assert_eq!(summary.failures.len(), 1);
+        assert_eq!(summary.failures[0].line, 4);
+        assert!(matches!(summary.failures[0].error, CommandInvalidError));
+    }
+
+    #[test]
+    fn stop_ends_at_the_first_failure() {
+        let (summary, executed) = run_source(OnError::Stop);
+        assert_eq!(executed, vec![Command::StockC(crate::command::StockCommand::Create)]);
+        assert_eq!(summary.failures.len(), 1);
+    }
+
+    #[test]
+    fn scripts_may_not_run_scripts() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::test(dir.path());
+
+        let summary = run(&config, "run-script self.txt\nconsole\n", OnError::Continue, |_| Ok(()));
+        assert_eq!(summary.failures.len(), 2);
+        assert!(summary.failures.iter().all(|x| matches!(x.error, InvalidInputError)));
+    }
+}
@@ -0,0 +1,249 @@
+//! #settings
+//!
+//! This holds the `Settings` type and related methods, which are read from the TOML settings file of a configuration
+//! directory
+
+// std
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// external crates
+use serde::{Serialize, Deserialize}; // So we may read and write the settings file
+
+// internal crates
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+use crate::validate;
+
+/// The name of every setting, as used in the settings file, by `config get`/`config set` and as a `--name VALUE`
+/// command-line option. `lot-matching` and `quote-provider` are reserved: they are kept, but nothing reads them yet.
+pub const KEYS: [&str; 7] = [
+    "base-currency", "format", "decimal-places", "confirm-delete", "lot-matching", "quote-provider", "session-timeout",
+];
+
+/// The most decimal places values may be shown with
+pub const MAX_DECIMAL_PLACES: usize = 10;
+
+/// Returns the values setting `key` may take, if it only takes a fixed set of values
+pub fn choices(key: &str) -> &'static [&'static str] {
+    match key {
+        "format"            => &["text", "json", "csv"],
+        "confirm-delete"    => &["true", "false"],
+        "lot-matching"      => &["fifo", "lifo", "average-cost"],
+        "quote-provider"    => &["manual"],
+        _                   => &[],
+    }
+}
+
+/// The `OutputFormat` enum represents the ways command output may be presented
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Text meant to be read by a person
+    Text,
+    /// JSON, as documented in the `output` module
+    Json,
+    /// CSV with a header row, as documented in the `output` module
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = ProjectError;
+
+    fn from_str(s: &str) -> Result<OutputFormat, ProjectError> {
+        from_value(s)
+    }
+}
+
+/// The `LotMatching` enum represents the methods of choosing which purchased shares are sold first
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LotMatching {
+    Fifo,
+    Lifo,
+    AverageCost,
+}
+
+/// The `QuoteProvider` enum represents the sources `Stock` values may be updated from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuoteProvider {
+    /// Values are only changed with `edit-stock`
+    Manual,
+}
+
+/// The `Settings` struct represents the defaults and behavior a user may configure. Any setting missing from the
+/// settings file takes its default value.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Settings {
+    /// The currency code values are shown in
+    pub base_currency: String,
+    /// How command output is presented
+    pub format: OutputFormat,
+    /// The number of decimal places values are shown with
+    pub decimal_places: usize,
+    /// Whether deleting a user, stock or profile asks for confirmation first
+    pub confirm_delete: bool,
+    /// Which purchased shares are sold first. Reserved, as nothing reads it yet.
+    pub lot_matching: LotMatching,
+    /// Where `Stock` values come from. Reserved, as nothing reads it yet.
+    pub quote_provider: QuoteProvider,
+    /// The minutes a login may go without a command before the user must log in again, or 0 for no limit
+    pub session_timeout: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            base_currency: String::from("USD"),
+            format: OutputFormat::Text,
+            decimal_places: 2,
+            confirm_delete: true,
+            lot_matching: LotMatching::Fifo,
+            quote_provider: QuoteProvider::Manual,
+            session_timeout: 60,
+        }
+    }
+}
+
+impl Settings {
+
+    /// `load()` reads the settings file of `directory`, or returns the default settings if there is none.
+    pub fn load(directory: &Path) -> Result<Settings, ProjectError> {
+        let path = Settings::path(directory);
+
+        if !path.exists() {
+            return Ok(Settings::default())
+        }
+
+        let contents = fs::read_to_string(&path).map_err(|_| IOSettingsOpenError(path.clone()))?;
+        toml::from_str(&contents).map_err(|_| DeserializeTOMLError(path))
+    }
+
+    pub fn write(&self, directory: &Path) -> Result<(), ProjectError> {
+        let path = Settings::path(directory);
+        let serialized_settings = toml::to_string(self).map_err(|_| SerializeTOMLError)?;
+
+        fs::write(&path, serialized_settings).map_err(|_| IOSettingsWriteError(path))
+    }
+
+    /// Simple method to return the location of the settings file within `directory`
+    pub fn path(directory: &Path) -> PathBuf {
+        directory.join("Settings.toml")
+    }
+
+    /// Returns the value of setting `key` as it would be written to the settings file
+    pub fn get(&self, key: &str) -> Result<String, ProjectError> {
+        Ok(match key {
+            "base-currency"     => self.base_currency.clone(),
+            "format"            => to_value(&self.format)?,
+            "decimal-places"    => self.decimal_places.to_string(),
+            "confirm-delete"    => self.confirm_delete.to_string(),
+            "lot-matching"      => to_value(&self.lot_matching)?,
+            "quote-provider"    => to_value(&self.quote_provider)?,
+            "session-timeout"   => self.session_timeout.to_string(),
+            _                   => return Err(SettingsKeyError(String::from(key))),
+        })
+    }
+
+    /// Sets setting `key` to `value`, returning an `InputParseError` if `value` is not valid for the setting, or the
+    /// error of `validate::currency()` if a base currency is not a three letter code
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ProjectError> {
+        match key {
+            "base-currency"     => { validate::currency(value)?; self.base_currency = value.to_uppercase() },
+            "format"            => self.format = from_value(value)?,
+            "decimal-places"    => self.decimal_places = parse(value).and_then(decimal_places)?,
+            "confirm-delete"    => self.confirm_delete = parse(value)?,
+            "lot-matching"      => self.lot_matching = from_value(value)?,
+            "quote-provider"    => self.quote_provider = from_value(value)?,
+            "session-timeout"   => self.session_timeout = parse(value)?,
+            _                   => return Err(SettingsKeyError(String::from(key))),
+        }
+        Ok(())
+    }
+
+    /// Returns every setting and its value in the order of `KEYS`
+    pub fn list(&self) -> Result<Vec<(&'static str, String)>, ProjectError> {
+        KEYS.iter().map(|key| Ok((*key, self.get(key)?))).collect()
+    }
+
+    /// Formats `value` as an amount of the base currency with the configured number of decimal places
+    pub fn format_money(&self, value: f64) -> String {
+        let sign = if value < 0.0 { "-" } else { "" };
+        match self.base_currency.as_str() {
+            "USD" => format!("{}${:.*}", sign, self.decimal_places, value.abs()),
+            currency => format!("{}{:.*} {}", sign, self.decimal_places, value.abs(), currency),
+        }
+    }
+}
+
+//
+// Assistive functions
+//
+
+/// Decimal places are capped, as values are padded with every one of them
+fn decimal_places(n: usize) -> Result<usize, ProjectError> {
+    match n {
+        0..=MAX_DECIMAL_PLACES => Ok(n),
+        _ => Err(InputParseError(n.to_string(), format!("a number of decimal places up to {}", MAX_DECIMAL_PLACES))),
+    }
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, ProjectError> {
+    value.parse().map_err(|_| InputParseError(String::from(value), std::any::type_name::<T>().to_string()))
+}
+
+/// Reads a setting enum from its kebab-case name, e.g. "average-cost"
+fn from_value<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, ProjectError> {
+    T::deserialize(serde::de::value::StrDeserializer::<serde::de::value::Error>::new(&value.to_lowercase()))
+        .map_err(|_| InputParseError(String::from(value), short_type_name::<T>()))
+}
+
+/// Returns the name of type `T` without its module path, e.g. "LotMatching"
+fn short_type_name<T>() -> String {
+    std::any::type_name::<T>().rsplit("::").next().unwrap_or_default().to_string()
+}
+
+/// Writes a setting enum as its kebab-case name
+fn to_value<T: Serialize>(value: &T) -> Result<String, ProjectError> {
+    match serde_json::to_value(value).map_err(|_| SerializeJSONError)? {
+        serde_json::Value::String(x) => Ok(x),
+        _ => Err(SerializeJSONError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Settings::load(dir.path()).unwrap(), Settings::default());
+
+        let mut settings = Settings::default();
+        settings.set("lot-matching", "average-cost").unwrap();
+        settings.set("decimal-places", "4").unwrap();
+        settings.set("base-currency", "eur").unwrap();
+        settings.write(dir.path()).unwrap();
+
+        let settings = Settings::load(dir.path()).unwrap();
+        assert_eq!(settings.get("lot-matching").unwrap(), "average-cost");
+        assert_eq!(settings.format_money(1.5), "1.5000 EUR");
+        assert_eq!(Settings::default().format_money(-3.0), "-$3.00");
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let mut settings = Settings::default();
+        assert!(matches!(settings.set("colour", "red"), Err(SettingsKeyError(_))));
+        assert!(matches!(settings.set("decimal-places", "-1"), Err(InputParseError(_, _))));
+        assert!(matches!(settings.set("lot-matching", "random"), Err(InputParseError(_, _))));
+        assert!(matches!(settings.set("decimal-places", "1000000"), Err(InputParseError(_, _))));
+        assert!(matches!(settings.set("base-currency", "eu"), Err(InvalidFormatError(_, _))));
+        assert!(matches!(settings.set("base-currency", "€"), Err(InvalidCharacterError(_, _))));
+        assert_eq!(settings, Settings::default());
+    }
+}
@@ -21,7 +21,7 @@ pub enum Property<'a> {
 }
 
 /// A representative value of one share of a company's stock
-#[derive(Serialize, Clone, Deserialize, Debug)]
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
 pub struct Stock {
     /// A company's ticker, typically a series of capital letters e.g. FOO, BAR, etc.
     pub ticker: String,
@@ -33,7 +33,7 @@ pub struct Stock {
 
 impl Stock {
     pub fn new() -> Result<Stock, ProjectError> {
-        return Ok( Stock {
+        Ok( Stock {
             ticker: String::from("ticker"),
             company_name: String::from("company_name"),
             value: 0.0,
@@ -41,16 +41,24 @@ impl Stock {
     }
 
     pub fn new_from_ticker(ticker: &str) -> Result<Stock, ProjectError> {
-        return Ok( Stock {
+        Ok( Stock {
             ticker: String::from(ticker),
             company_name: String::from("company_name"),
             value: 0.0,
         })
     }
 
+    pub fn new_from_details(ticker: &str, company_name: &str, value: f64) -> Result<Stock, ProjectError> {
+        Ok( Stock {
+            ticker: String::from(ticker),
+            company_name: String::from(company_name),
+            value,
+        })
+    }
+
     /// The `get_property()` function returns a mutable reference to the property of the `Stock` requested based on a `String s`
     /// which matches the name of a `User`'s corresponding property
-    pub fn get_property(&mut self, s: &str) -> Result<Property, ProjectError> {
+    pub fn get_property(&mut self, s: &str) -> Result<Property<'_>, ProjectError> {
         match String::from(s).to_lowercase().as_str() {
             "t" | "ticker"                          => Ok(Property::Ticker(&mut self.ticker)),
             "cn" | "company-name" | "companyname"   => Ok(Property::CompanyName(&mut self.company_name)),
@@ -67,26 +75,39 @@ impl fmt::Display for Stock {
 }
 
 /// A representative of amount of stocks one owns
-#[derive(Serialize, Clone, Deserialize, Debug)]
+#[derive(Serialize, Clone, Deserialize, Debug, PartialEq)]
 pub struct StockUnit {
     /// The Stock signature of the company
     pub stock: Stock,
     /// The quantity of shares of `Stock`
     pub quantity: u32,
+    /// The total paid for the shares. Holdings bought before this was recorded have `None`, and are taken to have cost
+    /// the value of `stock` per share.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
 }
 
 impl StockUnit {
+    /// Creates a holding of `quantity` shares bought at the current value of `stock`
     pub fn new(stock: Stock, quantity: u32) -> Result<StockUnit, ProjectError> {
-        return Ok( StockUnit {
-            stock: stock,
-            quantity: quantity,
+        let cost = Some(stock.value * f64::from(quantity));
+        Ok( StockUnit {
+            stock,
+            quantity,
+            cost,
         })
     }
 
-    /// This method adds `quantity` to `self.quantity` and returns an `InvalidInputError` if the provided value is less
-    /// than or equal to zero.
-    pub fn add_stock(&mut self, quantity: u32) -> Result<(), ProjectError> {
+    /// Returns the total paid for the shares
+    pub fn cost_basis(&self) -> f64 {
+        self.cost.unwrap_or(self.stock.value * f64::from(self.quantity))
+    }
+
+    /// This method adds `quantity` shares bought at `price` each to `self.quantity` and returns an `InvalidInputError`
+    /// if the provided value is less than or equal to zero.
+    pub fn add_stock(&mut self, quantity: u32, price: f64) -> Result<(), ProjectError> {
         if quantity > 0 {
+            self.cost = Some(self.cost_basis() + price * f64::from(quantity));
             self.quantity += quantity;
             Ok(())
         } else {
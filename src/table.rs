@@ -0,0 +1,222 @@
+//! #table
+//!
+//! This holds the `Table` type, which list commands use to present their text output as aligned columns
+
+// std
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+
+/// The text shown in place of the end of a truncated cell
+const ELLIPSIS: char = '…';
+/// The narrowest a column of text is truncated to
+const MIN_WIDTH: usize = 4;
+/// The space between columns
+const GAP: &str = "  ";
+
+/// The `Align` enum represents which side of its column a cell's text is placed against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// The `Tone` enum represents how a cell is colored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tone {
+    Plain,
+    /// Green, for gains
+    Gain,
+    /// Red, for losses
+    Loss,
+}
+
+/// A single value of a `Table`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    pub text: String,
+    pub align: Align,
+    pub tone: Tone,
+}
+
+impl Cell {
+    /// A left-aligned cell of text, which may be truncated to fit the terminal
+    pub fn text<S: Into<String>>(text: S) -> Cell {
+        Cell { text: text.into(), align: Align::Left, tone: Tone::Plain }
+    }
+
+    /// A right-aligned cell for a number, which is never truncated
+    pub fn number<S: Into<String>>(text: S) -> Cell {
+        Cell { text: text.into(), align: Align::Right, tone: Tone::Plain }
+    }
+
+    /// Like `number()`, but green if `change` is positive and red if it is negative
+    pub fn change<S: Into<String>>(text: S, change: f64) -> Cell {
+        let tone = if change > 0.0 { Tone::Gain } else if change < 0.0 { Tone::Loss } else { Tone::Plain };
+        Cell { text: text.into(), align: Align::Right, tone }
+    }
+}
+
+/// The `Style` struct represents the abilities of the output a `Table` is rendered for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    /// Whether ANSI colors may be used
+    pub color: bool,
+    /// The width rows are truncated to fit, if any
+    pub width: Option<usize>,
+}
+
+impl Style {
+    /// Returns the style of standard output: colored and fit to the terminal when it is a terminal, with color turned off
+    /// by a non-empty `NO_COLOR` variable, and neither when output is redirected.
+    pub fn detect() -> Style {
+        if !io::stdout().is_terminal() {
+            return Style { color: false, width: None }
+        }
+        Style {
+            color: env::var_os("NO_COLOR").is_none_or(|x| x.is_empty()),
+            width: crossterm::terminal::size().ok().map(|(columns, _)| usize::from(columns)),
+        }
+    }
+}
+
+/// The `Table` struct represents rows of `Cell`s under a row of headers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Table {
+        Table { headers: headers.iter().map(|x| String::from(*x)).collect(), rows: Vec::new() }
+    }
+
+    /// Adds a row, which should have a cell for every header
+    pub fn push(&mut self, row: Vec<Cell>) {
+        self.rows.push(row);
+    }
+
+    /// Renders the table with a line under the headers, each column as wide as its widest cell. If the rows are wider
+    /// than `style.width`, the widest columns of text are truncated until they fit (or cannot be truncated further).
+    pub fn render(&self, style: Style) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|x| length(x)).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(length(&cell.text));
+            }
+        }
+
+        if let Some(max) = style.width {
+            let gaps = GAP.len() * widths.len().saturating_sub(1);
+            while widths.iter().sum::<usize>() + gaps > max {
+                // The widest column of text which may still be truncated
+                let widest = (0..widths.len())
+                    .filter(|i| widths[*i] > MIN_WIDTH && self.rows.iter().all(|row| row.get(*i).is_none_or(|x| x.align == Align::Left)))
+                    .max_by_key(|i| widths[*i]);
+                match widest {
+                    Some(i) => widths[i] -= 1,
+                    None => break,
+                }
+            }
+        }
+
+        let mut out = String::new();
+        let headers: Vec<Cell> = self.headers.iter().enumerate()
+            .map(|(i, x)| Cell { text: x.clone(), align: self.column_align(i), tone: Tone::Plain })
+            .collect();
+        out.push_str(&render_row(&headers, &widths, style, style.color));
+        let rule: Vec<Cell> = widths.iter().map(|x| Cell::text("-".repeat(*x))).collect();
+        out.push_str(&render_row(&rule, &widths, style, false));
+        for row in &self.rows {
+            out.push_str(&render_row(row, &widths, style, false));
+        }
+        out
+    }
+
+    /// Prints the table to standard output in the style it supports
+    pub fn print(&self) {
+        print!("{}", self.render(Style::detect()));
+    }
+
+    /// Headers are aligned like the cells beneath them
+    fn column_align(&self, i: usize) -> Align {
+        self.rows.first().and_then(|row| row.get(i)).map_or(Align::Left, |x| x.align)
+    }
+}
+
+//
+// Assistive functions
+//
+
+/// The number of characters `s` takes up
+fn length(s: &str) -> usize {
+    s.chars().count()
+}
+
+fn render_row(row: &[Cell], widths: &[usize], style: Style, bold: bool) -> String {
+    let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| {
+        let text = truncate(&cell.text, *width);
+        let padding = " ".repeat(width - length(&text));
+        let text = match (style.color, cell.tone) {
+            (true, Tone::Gain) => format!("\x1b[32m{}\x1b[0m", text),
+            (true, Tone::Loss) => format!("\x1b[31m{}\x1b[0m", text),
+            _ if bold => format!("\x1b[1m{}\x1b[0m", text),
+            _ => text,
+        };
+        match cell.align {
+            Align::Left => format!("{}{}", text, padding),
+            Align::Right => format!("{}{}", padding, text),
+        }
+    }).collect();
+    format!("{}\n", cells.join(GAP).trim_end())
+}
+
+/// Shortens `s` to `width` characters, ending it with an ellipsis if anything was cut
+fn truncate(s: &str, width: usize) -> String {
+    if length(s) <= width {
+        return String::from(s)
+    }
+    let mut text: String = s.chars().take(width.saturating_sub(1)).collect();
+    text.push(ELLIPSIS);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: Style = Style { color: false, width: None };
+
+    fn table() -> Table {
+        let mut table = Table::new(&["Ticker", "Company", "Gain"]);
+        table.push(vec![Cell::text("FOO"), Cell::text("Foo Holdings Inc"), Cell::change("$12.50", 12.5)]);
+        table.push(vec![Cell::text("BARBAZ"), Cell::text("Bar"), Cell::change("-$3.00", -3.0)]);
+        table
+    }
+
+    #[test]
+    fn columns_are_aligned() {
+        assert_eq!(table().render(PLAIN), "\
+Ticker  Company             Gain
+------  ----------------  ------
+FOO     Foo Holdings Inc  $12.50
+BARBAZ  Bar               -$3.00
+");
+    }
+
+    #[test]
+    fn text_is_truncated_to_fit() {
+        let rendered = table().render(Style { color: false, width: Some(26) });
+        assert!(rendered.lines().all(|x| length(x) <= 26));
+        assert!(rendered.contains("FOO     Foo Holdi…  $12.50"));
+    }
+
+    #[test]
+    fn gains_and_losses_are_colored() {
+        let rendered = table().render(Style { color: true, width: None });
+        assert!(rendered.contains("\x1b[32m$12.50\x1b[0m"));
+        assert!(rendered.contains("\x1b[31m-$3.00\x1b[0m"));
+        assert!(!table().render(PLAIN).contains('\x1b'));
+    }
+}
@@ -0,0 +1,98 @@
+//! #tokenize
+//!
+//! This holds the `split()` function, which splits a line of console or script input into words the way a shell would
+
+// internal crates
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+
+/// The `split()` function splits `line` into words separated by whitespace. As in a shell:
+///
+/// - text between single quotes is taken literally, e.g. `'a "b" c'`
+/// - text between double quotes is taken literally, except that `\"` and `\\` stand for `"` and `\`
+/// - outside of quotes, a backslash takes the character after it literally, e.g. `Foo\ Inc`
+/// - quoted and unquoted text next to each other form one word, and `""` is an empty word
+///
+/// Returns an `UnterminatedQuoteError` if a quote is not closed, and an `UnterminatedEscapeError` if the line ends
+/// with a backslash, rather than guessing what was meant.
+pub fn split(line: &str) -> Result<Vec<String>, ProjectError> {
+    let mut words = Vec::new();
+    // The word being read, if any; `Some` even when empty so that `""` is kept
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(x) = word.take() {
+                    words.push(x);
+                }
+            },
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(x) => word.push(x),
+                        None => return Err(UnterminatedQuoteError('\'')),
+                    }
+                }
+            },
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(x @ ('"' | '\\')) => word.push(x),
+                            Some(x) => { word.push('\\'); word.push(x) },
+                            None => return Err(UnterminatedQuoteError('"')),
+                        },
+                        Some(x) => word.push(x),
+                        None => return Err(UnterminatedQuoteError('"')),
+                    }
+                }
+            },
+            '\\' => match chars.next() {
+                Some(x) => word.get_or_insert_with(String::new).push(x),
+                None => return Err(UnterminatedEscapeError),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(x) = word {
+        words.push(x);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        split(line).unwrap()
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(words("  edit-stock FOO\tvalue 12.5 "), vec!["edit-stock", "FOO", "value", "12.5"]);
+        assert!(words("   ").is_empty());
+    }
+
+    #[test]
+    fn quotes_and_escapes_group_words() {
+        assert_eq!(words(r#"edit-stock FOO company-name "Foo Holdings Inc""#),
+            vec!["edit-stock", "FOO", "company-name", "Foo Holdings Inc"]);
+        assert_eq!(words(r#"a 'it"s' "say \"hi\" \n" Foo\ Inc"#), vec!["a", "it\"s", "say \"hi\" \\n", "Foo Inc"]);
+        assert_eq!(words(r#"x"y"'z' "" ''"#), vec!["xyz", "", ""]);
+    }
+
+    #[test]
+    fn unterminated_input_is_an_error() {
+        assert!(matches!(split(r#"edit-stock FOO company-name "Foo"#), Err(UnterminatedQuoteError('"'))));
+        assert!(matches!(split("login 'bob"), Err(UnterminatedQuoteError('\''))));
+        assert!(matches!(split(r"login bob\"), Err(UnterminatedEscapeError)));
+    }
+}
@@ -3,13 +3,19 @@
 //! This holds the `User` type and related methods
 
 // std
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::str::FromStr;
 
 // external crates
+use argon2::Argon2; // For salted, memory-hard password hashes
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::password_hash::rand_core::OsRng;
 use serde::{Serialize, Deserialize}; // So we may prepare the HashMap to be written to a file
 
 // internal crates
+use crate::account;
+use crate::account::{Account, DEFAULT_ACCOUNT};
 use crate::stock::Stock;
 use crate::stock::StockUnit;
 use crate::error::ProjectError;
@@ -22,12 +28,85 @@ pub enum Property<'a> {
     FirstName(&'a mut String),
     LastName(&'a mut String),
     MiddleInitial(&'a mut String),
+    Email(&'a mut Option<String>),
+    BaseCurrency(&'a mut Option<String>),
+    RiskTolerance(&'a mut Option<RiskTolerance>),
+    TaxJurisdiction(&'a mut Option<String>),
+}
+
+/// The `RiskTolerance` enum represents how much risk a `User` is willing to take with their investments
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RiskTolerance {
+    Conservative,
+    Moderate,
+    Aggressive,
+}
+
+impl FromStr for RiskTolerance {
+    type Err = ProjectError;
+
+    fn from_str(s: &str) -> Result<RiskTolerance, ProjectError> {
+        match s.to_lowercase().as_str() {
+            "conservative" | "low" => Ok(RiskTolerance::Conservative),
+            "moderate" | "medium" => Ok(RiskTolerance::Moderate),
+            "aggressive" | "high" => Ok(RiskTolerance::Aggressive),
+            _ => Err(InputParseError(String::from(s), String::from("RiskTolerance"))),
+        }
+    }
+}
+
+impl fmt::Display for RiskTolerance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskTolerance::Conservative => write!(f, "conservative"),
+            RiskTolerance::Moderate => write!(f, "moderate"),
+            RiskTolerance::Aggressive => write!(f, "aggressive"),
+        }
+    }
+}
+
+/// The `Role` enum represents what a `User` is allowed to do
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// May run every command, including managing users and stocks
+    Admin,
+    /// May buy stocks and edit their own user
+    #[default]
+    Member,
+    /// May only view data
+    ReadOnly,
+}
+
+impl FromStr for Role {
+    type Err = ProjectError;
+
+    fn from_str(s: &str) -> Result<Role, ProjectError> {
+        match s.to_lowercase().as_str() {
+            "admin" => Ok(Role::Admin),
+            "member" => Ok(Role::Member),
+            "read-only" | "readonly" => Ok(Role::ReadOnly),
+            _ => Err(InputParseError(String::from(s), String::from("Role"))),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Admin => write!(f, "admin"),
+            Role::Member => write!(f, "member"),
+            Role::ReadOnly => write!(f, "read-only"),
+        }
+    }
 }
 
 /// A complete representation of a user and all of their corresponding data.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(from = "StoredUser")]
 pub struct User {
-    /// A user's username. Special characters such as !,?,&,| are not valid.
+    /// A user's username. Special characters such as !,?,&,| are not valid, see `validate::username()`.
     username: String,
     /// A user's first name
     first_name: String,
@@ -35,87 +114,395 @@ pub struct User {
     last_name: String,
     /// A user's middle initial
     middle_initial: String,
-    /// A collection of the user's stocks
-    pub portfolio: Option::<HashMap::<String, StockUnit>>,
+    /// The user's accounts by name, each with its own holdings and cash. There is always at least one.
+    pub accounts: BTreeMap<String, Account>,
+    /// A salted Argon2id hash of the user's password in PHC string format, if the user has set one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    password_hash: Option<String>,
+    /// What the user is allowed to do. Users from before roles existed are members.
+    #[serde(default)]
+    role: Role,
+    /// The user's email address, if given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+    /// The currency code the user prefers amounts in, if given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_currency: Option<String>,
+    /// How much risk the user is willing to take, if given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    risk_tolerance: Option<RiskTolerance>,
+    /// Where the user is taxed, as an ISO 3166 country code optionally followed by a subdivision, e.g. US-CA
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tax_jurisdiction: Option<String>,
+    /// When the user was created, in seconds since the Unix epoch. Users from before this was recorded have 0.
+    #[serde(default)]
+    created: u64,
+    /// When the user's details were last changed, in seconds since the Unix epoch, or 0 if never recorded
+    #[serde(default)]
+    updated: u64,
+}
+
+/// A `User` as it may be found in the user map, including users written before accounts existed, whose holdings are
+/// kept in `portfolio`
+#[derive(Deserialize)]
+struct StoredUser {
+    username: String,
+    first_name: String,
+    last_name: String,
+    middle_initial: String,
+    #[serde(default)]
+    portfolio: Option<HashMap<String, StockUnit>>,
+    #[serde(default)]
+    accounts: BTreeMap<String, Account>,
+    #[serde(default)]
+    password_hash: Option<String>,
+    #[serde(default)]
+    role: Role,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    base_currency: Option<String>,
+    #[serde(default)]
+    risk_tolerance: Option<RiskTolerance>,
+    #[serde(default)]
+    tax_jurisdiction: Option<String>,
+    #[serde(default)]
+    created: u64,
+    #[serde(default)]
+    updated: u64,
+}
+
+impl From<StoredUser> for User {
+    fn from(stored: StoredUser) -> User {
+        let mut accounts = stored.accounts;
+        // The portfolio of a user from before accounts existed becomes their default account
+        if let Some(portfolio) = stored.portfolio {
+            accounts.entry(String::from(DEFAULT_ACCOUNT)).or_default().holdings.extend(portfolio);
+        }
+        if accounts.is_empty() {
+            accounts.insert(String::from(DEFAULT_ACCOUNT), Account::default());
+        }
+
+        User {
+            username: stored.username,
+            first_name: stored.first_name,
+            last_name: stored.last_name,
+            middle_initial: stored.middle_initial,
+            accounts,
+            password_hash: stored.password_hash,
+            role: stored.role,
+            email: stored.email,
+            base_currency: stored.base_currency,
+            risk_tolerance: stored.risk_tolerance,
+            tax_jurisdiction: stored.tax_jurisdiction,
+            created: stored.created,
+            updated: stored.updated,
+        }
+    }
 }
 
 
 impl User {
 
     pub fn new() -> Result<User, ProjectError> {
-        return Ok(User {
+        Ok(User {
             username: String::from("username"),
             first_name: String::from("first_name"),
             last_name: String::from("last_name"),
             middle_initial: String::from("middle_initial"),
-            portfolio: None,
+            accounts: default_accounts(),
+            password_hash: None,
+            role: Role::Member,
+            email: None,
+            base_currency: None,
+            risk_tolerance: None,
+            tax_jurisdiction: None,
+            created: 0,
+            updated: 0,
         })
     }
 
     pub fn new_from_username(username: &str) -> Result<User, ProjectError> {
-        return Ok(User {
+        Ok(User {
             username: String::from(username),
             first_name: String::from("first_name"),
             last_name: String::from("last_name"),
             middle_initial: String::from("middle_initial"),
-            portfolio: None,
+            accounts: default_accounts(),
+            password_hash: None,
+            role: Role::Member,
+            email: None,
+            base_currency: None,
+            risk_tolerance: None,
+            tax_jurisdiction: None,
+            created: 0,
+            updated: 0,
         })
     }
 
+    pub fn new_from_details(username: &str, first_name: &str, last_name: &str, middle_initial: &str) -> Result<User, ProjectError> {
+        Ok(User {
+            username: String::from(username),
+            first_name: String::from(first_name),
+            last_name: String::from(last_name),
+            middle_initial: String::from(middle_initial),
+            accounts: default_accounts(),
+            password_hash: None,
+            role: Role::Member,
+            email: None,
+            base_currency: None,
+            risk_tolerance: None,
+            tax_jurisdiction: None,
+            created: 0,
+            updated: 0,
+        })
+    }
+
+    /// Returns the `User`'s username, which should match its key in the user map
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Returns the `User`'s first name
+    pub fn first_name(&self) -> &str {
+        &self.first_name
+    }
+
+    /// Returns the `User`'s last name
+    pub fn last_name(&self) -> &str {
+        &self.last_name
+    }
+
+    /// Returns the `User`'s middle initial
+    pub fn middle_initial(&self) -> &str {
+        &self.middle_initial
+    }
+
+    /// Returns the `User`'s first name, middle initial and last name, e.g. "Bob Q. Smith"
+    pub fn full_name(&self) -> String {
+        match self.middle_initial.chars().next() {
+            Some(x) => format!("{} {}. {}", self.first_name, x.to_uppercase(), self.last_name),
+            None => format!("{} {}", self.first_name, self.last_name),
+        }
+    }
+
+    /// Returns the `User`'s email address, if given
+    pub fn email(&self) -> Option<&str> {
+        self.email.as_deref()
+    }
+
+    /// Returns the currency code the `User` prefers, if given
+    pub fn base_currency(&self) -> Option<&str> {
+        self.base_currency.as_deref()
+    }
+
+    /// Returns how much risk the `User` is willing to take, if given
+    pub fn risk_tolerance(&self) -> Option<RiskTolerance> {
+        self.risk_tolerance
+    }
+
+    /// Returns where the `User` is taxed, if given
+    pub fn tax_jurisdiction(&self) -> Option<&str> {
+        self.tax_jurisdiction.as_deref()
+    }
+
+    /// Returns when the `User` was created, or 0 if unknown
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    /// Returns when the `User`'s details were last changed, or 0 if unknown
+    pub fn updated(&self) -> u64 {
+        self.updated
+    }
+
+    /// Records that the `User` was created at `time`, which is also when its details were last changed
+    pub fn mark_created(&mut self, time: u64) {
+        self.created = time;
+        self.updated = time;
+    }
+
+    /// Records that the `User`'s details were changed at `time`
+    pub fn mark_updated(&mut self, time: u64) {
+        self.updated = time;
+    }
+
+    /// Returns the `User`'s role
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn set_role(&mut self, role: Role) {
+        self.role = role;
+    }
+
+    /// Whether logging in as the `User` requires a password
+    pub fn has_password(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    /// Sets the `User`'s password, keeping only a salted hash of it. An empty password is not accepted.
+    pub fn set_password(&mut self, password: &str) -> Result<(), ProjectError> {
+        if password.is_empty() {
+            return Err(InvalidInputError)
+        }
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default().hash_password(password.as_bytes(), &salt).map_err(|_| PasswordHashError)?;
+        self.password_hash = Some(hash.to_string());
+        Ok(())
+    }
+
+    /// Removes the `User`'s password, so that logging in no longer requires one
+    pub fn clear_password(&mut self) {
+        self.password_hash = None;
+    }
+
+    /// Whether `password` is the `User`'s password. A `User` without a password accepts any.
+    pub fn verify_password(&self, password: &str) -> bool {
+        match &self.password_hash {
+            Some(x) => PasswordHash::new(x).is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok()),
+            None => true,
+        }
+    }
+
+    /// Whether `other` has the same password hash as the `User`, or neither has a password. A password set again to
+    /// the same value has a new salt, and so counts as different.
+    pub fn same_password(&self, other: &User) -> bool {
+        self.password_hash == other.password_hash
+    }
+
+    /// Sets the `User`'s username. Note: this does not change the `User`'s key in the user map.
+    pub fn set_username(&mut self, username: &str) {
+        self.username = String::from(username);
+    }
+
     /// The `get_property()` function returns a mutable reference to the property of the `User` requested based on a `String s`
     /// which matches the name of a `User`'s corresponding property
-    pub fn get_property(&mut self, s: &str) -> Result<Property, ProjectError>{
+    pub fn get_property(&mut self, s: &str) -> Result<Property<'_>, ProjectError>{
         match String::from(s).to_lowercase().as_str() {
-            "u" | "username"                            => Ok(Property::Username(&mut self.username)),
-            "fn" | "first-name" | "firstname"           => Ok(Property::FirstName(&mut self.first_name)),
-            "ln" | "last-name" | "lastname"             => Ok(Property::LastName(&mut self.last_name)),
-            "mi" | "middle-initial" | "middleinitial"   => Ok(Property::MiddleInitial(&mut self.middle_initial)),
-            _                                           => Err(InvalidInputError),
+            "u" | "username"                                => Ok(Property::Username(&mut self.username)),
+            "fn" | "first-name" | "firstname"               => Ok(Property::FirstName(&mut self.first_name)),
+            "ln" | "last-name" | "lastname"                 => Ok(Property::LastName(&mut self.last_name)),
+            "mi" | "middle-initial" | "middleinitial"       => Ok(Property::MiddleInitial(&mut self.middle_initial)),
+            "e" | "email"                                   => Ok(Property::Email(&mut self.email)),
+            "bc" | "base-currency" | "basecurrency"         => Ok(Property::BaseCurrency(&mut self.base_currency)),
+            "rt" | "risk-tolerance" | "risktolerance"       => Ok(Property::RiskTolerance(&mut self.risk_tolerance)),
+            "tj" | "tax-jurisdiction" | "taxjurisdiction"   => Ok(Property::TaxJurisdiction(&mut self.tax_jurisdiction)),
+            _                                               => Err(InvalidInputError),
         }
     }
 
-    /// The `add_stock()` function allows a user to add a `StockUnit` with a given `Stock` and `u32` quantity
-    pub fn add_stock(&mut self, stock: &Stock, qt: u32) -> Result<(), ProjectError> {
-        match &mut self.portfolio {
-            Some(hashmap) => match hashmap.try_insert(stock.ticker.clone(), StockUnit::new(stock.clone(), qt)?) {
-                    Ok(_) => {Ok(())},
-                    Err(_) => self.add_stock_additional(stock, qt),
-                }
-            None => { // Generate a new hashmap for `portfolio` and add our stock_unit to it.
-                let mut hashmap = HashMap::<String, StockUnit>::new();
-                hashmap.insert(stock.ticker.clone(), StockUnit::new(stock.clone(), qt)?);
-                self.portfolio = Some(hashmap);
-                Ok(())
-            },
+    /// Returns the `User`'s account named `name`, or an `AccountNotFoundError`
+    pub fn account(&self, name: &str) -> Result<&Account, ProjectError> {
+        match self.accounts.get(name) {
+            Some(x) => Ok(x),
+            None => Err(AccountNotFoundError(self.username.clone(), String::from(name))),
         }
     }
 
-    fn add_stock_additional(&mut self, stock: &Stock, qt: u32) -> Result<(), ProjectError> {
-        match &mut self.portfolio {
-            Some(hashmap) => {
-                let stock_unit = hashmap.get_mut(&stock.ticker).unwrap(); // We can be confident get will be Some()
-                stock_unit.add_stock(qt)
-            }, None => Err(ImpossibleStateError)
+    /// Like `account()`, but the account may be modified
+    pub fn account_mut(&mut self, name: &str) -> Result<&mut Account, ProjectError> {
+        match self.accounts.get_mut(name) {
+            Some(x) => Ok(x),
+            None => Err(AccountNotFoundError(self.username.clone(), String::from(name))),
         }
     }
 
+    /// The `create_account()` function opens an account named `name` holding `cash`, returning an
+    /// `AccountExistsError` if the `User` already has one of that name or an `InvalidNumberError` if `cash` cannot be stored.
+    pub fn create_account(&mut self, name: &str, cash: f64) -> Result<(), ProjectError> {
+        account::validate_name(name)?;
+        if self.accounts.contains_key(name) {
+            return Err(AccountExistsError(self.username.clone(), String::from(name)))
+        }
+        self.accounts.insert(String::from(name), Account::new(cash)?);
+        Ok(())
+    }
+
+    /// The `add_stock()` function adds `qt` shares of `stock` to the `User`'s account named `account`
+    pub fn add_stock(&mut self, account: &str, stock: &Stock, qt: u32) -> Result<(), ProjectError> {
+        self.account_mut(account)?.add_stock(stock, qt)
+    }
+
+}
+
+/// Every new `User` starts with an empty default account
+fn default_accounts() -> BTreeMap<String, Account> {
+    BTreeMap::from([(String::from(DEFAULT_ACCOUNT), Account::default())])
 }
 
 impl fmt::Display for User {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:\n{} {}. {}",
-            self.username,
-            self.first_name,
-            String::from(&self.middle_initial).to_uppercase().chars().next().unwrap(),
-            self.last_name)
+        write!(f, "{}:\n{}", self.username, self.full_name())
     }
 }
 
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn password_is_stored_hashed_and_verified() {
+        let mut user = User::new_from_username("bob").unwrap();
+        assert!(!user.has_password() && user.verify_password("anything"));
+        assert!(matches!(user.set_password(""), Err(InvalidInputError)));
+
+        user.set_password("hunter2").unwrap();
+        let json = serde_json::to_string(&user).unwrap();
+        assert!(json.contains("$argon2id$") && !json.contains("hunter2"));
+        let user: User = serde_json::from_str(&json).unwrap();
+        assert!(user.verify_password("hunter2"));
+        assert!(!user.verify_password("hunter3"));
+    }
+
+    #[test]
+    fn legacy_portfolio_becomes_default_account() {
+        let json = r#"{"username":"bob","first_name":"Bob","last_name":"Smith","middle_initial":"Q","portfolio":
+            {"FOO":{"stock":{"ticker":"FOO","company_name":"Foo Inc","value":2.0},"quantity":3}}}"#;
+        let mut user: User = serde_json::from_str(json).unwrap();
+        assert_eq!(user.account(DEFAULT_ACCOUNT).unwrap().holdings["FOO"].quantity, 3);
+
+        user.create_account("ira", 50.0).unwrap();
+        assert!(matches!(user.create_account("ira", 0.0), Err(AccountExistsError(_, _))));
+        assert!(matches!(user.create_account("my ira", 0.0), Err(AccountNameError(_))));
+        let json = serde_json::to_string(&user).unwrap();
+        assert!(!json.contains("portfolio"));
+        assert_eq!(serde_json::from_str::<User>(&json).unwrap(), user);
+    }
+
+    #[test]
+    fn profile_details_are_parsed_by_type() {
+        assert_eq!("high".parse::<RiskTolerance>().unwrap(), RiskTolerance::Aggressive);
+        assert_eq!("Low".parse::<RiskTolerance>().unwrap(), RiskTolerance::Conservative);
+        assert!(matches!("reckless".parse::<RiskTolerance>(), Err(InputParseError(_, _))));
+
+        let mut user = User::new_from_username("bob").unwrap();
+        assert!(matches!(user.get_property("rt"), Ok(Property::RiskTolerance(_))));
+        assert!(matches!(user.get_property("tax-jurisdiction"), Ok(Property::TaxJurisdiction(_))));
+        assert!(user.get_property("shoe-size").is_err());
+        if let Ok(Property::RiskTolerance(x)) = user.get_property("risk-tolerance") {
+            *x = Some(RiskTolerance::Moderate);
+        }
+        assert_eq!(user.risk_tolerance(), Some(RiskTolerance::Moderate));
+        assert_eq!(user.email(), None);
+
+        assert_eq!(user.created(), 0);
+        user.mark_created(100);
+        user.mark_updated(200);
+        assert_eq!((user.created(), user.updated()), (100, 200));
+        let json = serde_json::to_string(&user).unwrap();
+        assert_eq!(serde_json::from_str::<User>(&json).unwrap(), user);
+    }
+
+    #[test]
+    fn display_allows_no_middle_initial() {
+        assert_eq!(User::new_from_details("bob", "Bob", "Smith", "q").unwrap().to_string(), "bob:\nBob Q. Smith");
+        assert_eq!(User::new_from_details("bob", "Bob", "Smith", "").unwrap().to_string(), "bob:\nBob Smith");
+    }
 }
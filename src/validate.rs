@@ -0,0 +1,222 @@
+//! #validate
+//!
+//! This holds the rules usernames, tickers and the other values of users and stocks must follow, which are checked
+//! whenever a user or stock is created or edited
+
+// std
+use std::fmt;
+
+// internal crates
+use crate::error::ProjectError;
+use crate::error::ProjectError::*;
+
+/// The longest username allowed
+pub const MAX_USERNAME_LENGTH: usize = 32;
+/// The longest ticker allowed
+pub const MAX_TICKER_LENGTH: usize = 10;
+/// The longest first or last name allowed
+pub const MAX_NAME_LENGTH: usize = 50;
+/// The longest company name allowed
+pub const MAX_COMPANY_NAME_LENGTH: usize = 100;
+/// The longest email address allowed
+pub const MAX_EMAIL_LENGTH: usize = 254;
+
+/// The `Field` enum represents the values which are validated, so that an error may say which one broke a rule
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Username,
+    FirstName,
+    LastName,
+    MiddleInitial,
+    Ticker,
+    CompanyName,
+    Value,
+    Cash,
+    Email,
+    BaseCurrency,
+    TaxJurisdiction,
+}
+
+impl Field {
+    /// Describes the characters the field may contain
+    pub fn allowed(&self) -> &'static str {
+        match self {
+            Field::Username => "only letters, digits, -, _ and . are allowed",
+            Field::Ticker => "only capital letters, digits, - and . are allowed",
+            Field::MiddleInitial => "only a letter is allowed",
+            Field::FirstName | Field::LastName | Field::CompanyName => "control characters are not allowed",
+            Field::Value | Field::Cash => "only digits are allowed",
+            Field::Email => "spaces and control characters are not allowed",
+            Field::BaseCurrency => "only letters are allowed",
+            Field::TaxJurisdiction => "only letters, digits and - are allowed",
+        }
+    }
+
+    /// Gives an example of a valid value of the field, for fields which must follow a format
+    pub fn example(&self) -> &'static str {
+        match self {
+            Field::Email => "name@example.com",
+            Field::BaseCurrency => "USD",
+            Field::TaxJurisdiction => "US or US-CA",
+            _ => "",
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Field::Username => write!(f, "Username"),
+            Field::FirstName => write!(f, "First name"),
+            Field::LastName => write!(f, "Last name"),
+            Field::MiddleInitial => write!(f, "Middle initial"),
+            Field::Ticker => write!(f, "Ticker"),
+            Field::CompanyName => write!(f, "Company name"),
+            Field::Value => write!(f, "Value"),
+            Field::Cash => write!(f, "Cash"),
+            Field::Email => write!(f, "Email"),
+            Field::BaseCurrency => write!(f, "Base currency"),
+            Field::TaxJurisdiction => write!(f, "Tax jurisdiction"),
+        }
+    }
+}
+
+/// Usernames are typed on the command line, so they are limited to letters, digits, `-`, `_` and `.`
+pub fn username(s: &str) -> Result<(), ProjectError> {
+    check(Field::Username, s, MAX_USERNAME_LENGTH, |c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
+
+/// Tickers are capital letters and digits, with `-` or `.` for share classes such as BRK.B
+pub fn ticker(s: &str) -> Result<(), ProjectError> {
+    check(Field::Ticker, s, MAX_TICKER_LENGTH, |c| c.is_ascii_uppercase() || c.is_ascii_digit() || "-.".contains(c))
+}
+
+/// First and last names may hold any printable characters, to allow for names from every language
+pub fn name(field: Field, s: &str) -> Result<(), ProjectError> {
+    check(field, s, MAX_NAME_LENGTH, |c| !c.is_control())
+}
+
+/// A middle initial is a single letter, or empty for users without a middle name
+pub fn middle_initial(s: &str) -> Result<(), ProjectError> {
+    if s.is_empty() {
+        return Ok(())
+    }
+    check(Field::MiddleInitial, s, 1, char::is_alphabetic)
+}
+
+pub fn company_name(s: &str) -> Result<(), ProjectError> {
+    check(Field::CompanyName, s, MAX_COMPANY_NAME_LENGTH, |c| !c.is_control())
+}
+
+/// The value of a share may not be negative, infinite or not a number
+pub fn value(x: f64) -> Result<(), ProjectError> {
+    if !x.is_finite() || x < 0.0 {
+        return Err(InvalidNumberError(Field::Value, x))
+    }
+    Ok(())
+}
+
+/// The cash an account is opened with may not be negative, infinite or not a number, which could not be stored
+pub fn cash(x: f64) -> Result<(), ProjectError> {
+    if !x.is_finite() || x < 0.0 {
+        return Err(InvalidNumberError(Field::Cash, x))
+    }
+    Ok(())
+}
+
+/// An email address has a name and a domain with at least one dot, separated by a single @
+pub fn email(s: &str) -> Result<(), ProjectError> {
+    check(Field::Email, s, MAX_EMAIL_LENGTH, |c| !c.is_whitespace() && !c.is_control())?;
+    match s.split_once('@') {
+        Some((name, domain)) if !name.is_empty() && !domain.contains('@') && domain.split('.').count() > 1
+            && domain.split('.').all(|x| !x.is_empty()) => Ok(()),
+        _ => Err(InvalidFormatError(Field::Email, String::from(s))),
+    }
+}
+
+/// A currency is given by its three letter ISO 4217 code, e.g. USD
+pub fn currency(s: &str) -> Result<(), ProjectError> {
+    check(Field::BaseCurrency, s, 3, |c| c.is_ascii_alphabetic())?;
+    if s.len() != 3 {
+        return Err(InvalidFormatError(Field::BaseCurrency, String::from(s)))
+    }
+    Ok(())
+}
+
+/// A tax jurisdiction is a two letter ISO 3166 country code, optionally followed by `-` and a subdivision of up to
+/// three letters or digits, e.g. US-CA
+pub fn tax_jurisdiction(s: &str) -> Result<(), ProjectError> {
+    check(Field::TaxJurisdiction, s, 6, |c| c.is_ascii_alphanumeric() || c == '-')?;
+    let (country, subdivision) = match s.split_once('-') {
+        Some((x, y)) => (x, Some(y)),
+        None => (s, None),
+    };
+    let valid_country = country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic());
+    if !valid_country || subdivision.is_some_and(|x| x.is_empty() || x.contains('-')) {
+        return Err(InvalidFormatError(Field::TaxJurisdiction, String::from(s)))
+    }
+    Ok(())
+}
+
+//
+// Assistive functions
+//
+
+/// Checks that `s` is not empty, is at most `max` characters long and has only characters which are `allowed`, in that
+/// order
+fn check<F>(field: Field, s: &str, max: usize, allowed: F) -> Result<(), ProjectError> where
+    F: Fn(char) -> bool, {
+    if s.is_empty() {
+        return Err(EmptyFieldError(field))
+    }
+    if s.chars().count() > max {
+        return Err(FieldTooLongError(field, max))
+    }
+    match s.chars().find(|c| !allowed(*c)) {
+        Some(c) => Err(InvalidCharacterError(field, c)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usernames_and_tickers_follow_their_rules() {
+        assert!(username("bob.smith-2").is_ok());
+        assert!(matches!(username("bob!"), Err(InvalidCharacterError(Field::Username, '!'))));
+        assert!(matches!(username(""), Err(EmptyFieldError(Field::Username))));
+        assert!(matches!(username(&"a".repeat(33)), Err(FieldTooLongError(Field::Username, 32))));
+
+        assert!(ticker("BRK.B").is_ok());
+        assert!(matches!(ticker("foo"), Err(InvalidCharacterError(Field::Ticker, 'f'))));
+    }
+
+    #[test]
+    fn names_and_values_follow_their_rules() {
+        assert!(name(Field::FirstName, "José María").is_ok());
+        assert!(matches!(name(Field::LastName, ""), Err(EmptyFieldError(Field::LastName))));
+        assert!(middle_initial("").is_ok() && middle_initial("Q").is_ok());
+        assert!(matches!(middle_initial("QR"), Err(FieldTooLongError(Field::MiddleInitial, 1))));
+        assert!(matches!(middle_initial("."), Err(InvalidCharacterError(Field::MiddleInitial, '.'))));
+        assert!(matches!(company_name("Foo\nInc"), Err(InvalidCharacterError(Field::CompanyName, '\n'))));
+
+        assert!(value(0.0).is_ok());
+        assert!(matches!(value(-1.0), Err(InvalidNumberError(Field::Value, _))));
+        assert!(value(f64::NAN).is_err());
+        assert!(cash(25.0).is_ok());
+        assert!(matches!(cash(f64::INFINITY), Err(InvalidNumberError(Field::Cash, _))));
+    }
+
+    #[test]
+    fn profile_fields_follow_their_formats() {
+        assert!(email("bob@example.com").is_ok());
+        assert!(matches!(email("bob@example"), Err(InvalidFormatError(Field::Email, _))));
+        assert!(matches!(email("bob smith@example.com"), Err(InvalidCharacterError(Field::Email, ' '))));
+        assert!(currency("eur").is_ok());
+        assert!(matches!(currency("EU"), Err(InvalidFormatError(Field::BaseCurrency, _))));
+        assert!(tax_jurisdiction("US-CA").is_ok() && tax_jurisdiction("GB").is_ok());
+        assert!(matches!(tax_jurisdiction("USA"), Err(InvalidFormatError(Field::TaxJurisdiction, _))));
+    }
+}